@@ -24,8 +24,9 @@ use miette::{miette, IntoDiagnostic, Result};
 use oci_distribution::Reference;
 use once_cell::sync::Lazy;
 use opts::{
-    BuildOpts, BuildTagPushOpts, CheckKeyPairOpts, GenerateImageNameOpts, GenerateKeyPairOpts,
-    GenerateTagsOpts, GetMetadataOpts, PushOpts, RunOpts, SignOpts, TagOpts, VerifyOpts,
+    AttestOpts, BuildOpts, BuildTagPushOpts, CheckKeyPairOpts, GenerateImageNameOpts,
+    GenerateKeyPairOpts, GenerateTagsOpts, GetMetadataOpts, ManifestPushOpts, PushOpts, RunOpts,
+    SignOpts, TagOpts, VerifyAttestationOpts, VerifyOpts,
 };
 use types::{
     BuildDriverType, CiDriverType, DetermineDriver, ImageMetadata, InspectDriverType, Platform,
@@ -33,23 +34,33 @@ use types::{
 };
 use uuid::Uuid;
 
-use crate::logging::Logger;
+use crate::{
+    dry_run::{is_dry_run, DRY_RUN_DIGEST},
+    logging::Logger,
+};
 
 pub use self::{
     buildah_driver::BuildahDriver, cosign_driver::CosignDriver, docker_driver::DockerDriver,
-    github_driver::GithubDriver, gitlab_driver::GitlabDriver, local_driver::LocalDriver,
-    podman_driver::PodmanDriver, skopeo_driver::SkopeoDriver, traits::*,
+    env_driver::EnvDriver, forgejo_driver::ForgejoDriver, github_driver::GithubDriver,
+    gitlab_driver::GitlabDriver, local_driver::LocalDriver, podman_driver::PodmanDriver,
+    skopeo_driver::SkopeoDriver, traits::*,
 };
+#[cfg(feature = "oci")]
+pub use oci_driver::OciDriver;
 #[cfg(feature = "sigstore")]
 pub use sigstore_driver::SigstoreDriver;
 
 mod buildah_driver;
 mod cosign_driver;
 mod docker_driver;
+mod env_driver;
+mod forgejo_driver;
 mod functions;
 mod github_driver;
 mod gitlab_driver;
 mod local_driver;
+#[cfg(feature = "oci")]
+mod oci_driver;
 pub mod opts;
 mod podman_driver;
 #[cfg(feature = "sigstore")]
@@ -167,6 +178,22 @@ impl Driver {
         }
     }
 
+    /// Initializes only the CI driver.
+    ///
+    /// This is for commands that don't use any container
+    /// tools, like generating a `Containerfile` offline.
+    ///
+    /// # Panics
+    /// Will panic if the driver lock is poisoned.
+    pub fn init_ci() {
+        trace!("Driver::init_ci()");
+
+        let mut driver = SELECTED_CI_DRIVER.write().expect("Should lock");
+        driver.determine_driver();
+        trace!("Driver set {driver:?}");
+        drop(driver);
+    }
+
     /// Gets the current build's UUID
     #[must_use]
     pub fn get_build_id() -> Uuid {
@@ -233,6 +260,20 @@ impl Driver {
         Ok(os_version)
     }
 
+    /// Gets the digest of an image that was pushed.
+    ///
+    /// Nothing is pushed in dry-run mode, so a
+    /// placeholder digest is returned instead.
+    ///
+    /// # Errors
+    /// Will error if the image can't be inspected.
+    pub fn get_pushed_digest(opts: &GetMetadataOpts) -> Result<String> {
+        if is_dry_run() {
+            return Ok(DRY_RUN_DIGEST.to_string());
+        }
+        Ok(Self::get_metadata(opts)?.digest)
+    }
+
     pub fn get_build_driver() -> BuildDriverType {
         impl_driver_type!(SELECTED_BUILD_DRIVER)
     }
@@ -321,6 +362,10 @@ impl BuildDriver for Driver {
         impl_build_driver!(push(opts))
     }
 
+    fn manifest_push(opts: &ManifestPushOpts) -> Result<String> {
+        impl_build_driver!(manifest_push(opts))
+    }
+
     fn login() -> Result<()> {
         impl_build_driver!(login())
     }
@@ -363,6 +408,14 @@ impl SigningDriver for Driver {
         impl_signing_driver!(verify(opts))
     }
 
+    fn attest(opts: &AttestOpts) -> Result<()> {
+        impl_signing_driver!(attest(opts))
+    }
+
+    fn verify_attestation(opts: &VerifyAttestationOpts) -> Result<()> {
+        impl_signing_driver!(verify_attestation(opts))
+    }
+
     fn signing_login() -> Result<()> {
         impl_signing_driver!(signing_login())
     }
@@ -374,6 +427,8 @@ macro_rules! impl_inspect_driver {
             InspectDriverType::Skopeo => SkopeoDriver::$func($($args,)*),
             InspectDriverType::Podman => PodmanDriver::$func($($args,)*),
             InspectDriverType::Docker => DockerDriver::$func($($args,)*),
+            #[cfg(feature = "oci")]
+            InspectDriverType::Oci => OciDriver::$func($($args,)*),
         }
     };
 }
@@ -409,6 +464,8 @@ macro_rules! impl_ci_driver {
             CiDriverType::Local => LocalDriver::$func($($args,)*),
             CiDriverType::Gitlab => GitlabDriver::$func($($args,)*),
             CiDriverType::Github => GithubDriver::$func($($args,)*),
+            CiDriverType::Forgejo => ForgejoDriver::$func($($args,)*),
+            CiDriverType::Env => EnvDriver::$func($($args,)*),
         }
     };
 }
@@ -445,12 +502,15 @@ impl CiDriver for Driver {
         impl_ci_driver!(generate_image_name(opts))
     }
 
-    fn default_ci_file_path() -> std::path::PathBuf {
+    fn default_ci_file_path() -> Result<std::path::PathBuf> {
         impl_ci_driver!(default_ci_file_path())
     }
 }
 
-#[cfg(feature = "rechunk")]
+#[cfg(feature = "sbom")]
+impl SbomDriver for Driver {}
+
+#[cfg(any(feature = "rechunk", feature = "diff"))]
 impl ContainerMountDriver for Driver {
     fn create_container(image: &Reference) -> Result<types::ContainerId> {
         PodmanDriver::create_container(image)
@@ -477,6 +537,19 @@ impl ContainerMountDriver for Driver {
     }
 }
 
+#[cfg(feature = "diff")]
+impl ImageMountDriver for Driver {
+    fn load_archive(archive: &std::path::Path, image: &Reference) -> Result<()> {
+        PodmanDriver::load_archive(archive, image)
+    }
+
+    fn image_labels(
+        image: &Reference,
+    ) -> Result<std::collections::HashMap<String, serde_json::Value>> {
+        PodmanDriver::image_labels(image)
+    }
+}
+
 #[cfg(feature = "rechunk")]
 impl OciCopy for Driver {
     fn copy_oci_dir(
@@ -1,15 +1,27 @@
-use std::{io::Write, process::Stdio};
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
 
 use blue_build_utils::{cmd, credentials::Credentials};
-use log::{debug, error, info, trace};
+use log::{debug, error, info, trace, warn};
 use miette::{bail, miette, IntoDiagnostic, Result};
 use semver::Version;
 use serde::Deserialize;
+use tempfile::NamedTempFile;
+use uuid::Uuid;
 
-use crate::{drivers::types::Platform, logging::CommandLogging};
+use crate::{
+    drivers::types::Platform,
+    dry_run::{is_dry_run, DryRunCommand, DRY_RUN_DIGEST},
+    logging::CommandLogging,
+};
 
 use super::{
-    opts::{BuildOpts, PushOpts, TagOpts},
+    functions::registry_cache,
+    opts::{BuildOpts, ManifestPushOpts, PushOpts, TagOpts},
     BuildDriver, DriverVersion,
 };
 
@@ -52,6 +64,19 @@ impl BuildDriver for BuildahDriver {
     fn build(opts: &BuildOpts) -> Result<()> {
         trace!("BuildahDriver::build({opts:#?})");
 
+        // Caches are only used when building with layers
+        let (cache_from, cache_to) = if opts.squash {
+            if opts.cache_from.is_some() || opts.cache_to.is_some() {
+                warn!("Build caches are not used when squashing");
+            }
+            (None, None)
+        } else {
+            (
+                registry_cache("buildah", opts.cache_from),
+                registry_cache("buildah", opts.cache_to),
+            )
+        };
+
         let command = cmd!(
             "buildah",
             "build",
@@ -61,6 +86,9 @@ impl BuildDriver for BuildahDriver {
             ],
             "--pull=true",
             format!("--layers={}", !opts.squash),
+            if let Some(repo) = cache_from => format!("--cache-from={repo}"),
+            if let Some(repo) = cache_to => format!("--cache-to={repo}"),
+            for secret in opts.secrets.iter() => format!("--secret={secret}"),
             "-f",
             &*opts.containerfile,
             "-t",
@@ -86,7 +114,7 @@ impl BuildDriver for BuildahDriver {
         let mut command = cmd!("buildah", "tag", &*opts.src_image, &*opts.dest_image,);
 
         trace!("{command:?}");
-        if command.status().into_diagnostic()?.success() {
+        if command.dry_run_status().into_diagnostic()?.success() {
             info!("Successfully tagged {}!", opts.dest_image);
         } else {
             bail!("Failed to tag image {}", opts.dest_image);
@@ -120,6 +148,28 @@ impl BuildDriver for BuildahDriver {
         Ok(())
     }
 
+    fn manifest_push(opts: &ManifestPushOpts) -> Result<String> {
+        trace!("BuildahDriver::manifest_push({opts:#?})");
+
+        let manifest = format!("localhost/bluebuild-manifest-{}", Uuid::new_v4());
+
+        let mut command = cmd!("buildah", "manifest", "create", &manifest);
+        trace!("{command:?}");
+        if !command.dry_run_status().into_diagnostic()?.success() {
+            bail!("Failed to create manifest list for {}", opts.image);
+        }
+
+        let result = buildah_manifest_add_push(&manifest, opts);
+
+        let mut command = cmd!("buildah", "manifest", "rm", &manifest);
+        trace!("{command:?}");
+        if !command.dry_run_output().into_diagnostic()?.status.success() {
+            warn!("Failed to remove local manifest list {manifest}");
+        }
+
+        result
+    }
+
     fn login() -> Result<()> {
         trace!("BuildahDriver::login()");
 
@@ -143,6 +193,9 @@ impl BuildDriver for BuildahDriver {
                 .stderr(Stdio::piped());
 
             trace!("{command:?}");
+            if command.print_if_dry_run() {
+                return Ok(());
+            }
             let mut child = command.spawn().into_diagnostic()?;
 
             write!(
@@ -185,3 +238,105 @@ impl BuildDriver for BuildahDriver {
         Ok(())
     }
 }
+
+fn buildah_manifest_add_push(manifest: &str, opts: &ManifestPushOpts) -> Result<String> {
+    for image in &opts.images {
+        let mut command = buildah_manifest_add(manifest, image);
+        trace!("{command:?}");
+
+        if !command.dry_run_status().into_diagnostic()?.success() {
+            bail!("Failed to add {image} to manifest list for {}", opts.image);
+        }
+    }
+
+    let digest_file = NamedTempFile::new().into_diagnostic()?;
+
+    let command = buildah_manifest_push(manifest, &opts.image, digest_file.path());
+    trace!("{command:?}");
+
+    let status = command
+        .build_status(&opts.image, "Pushing Manifest")
+        .into_diagnostic()?;
+
+    if !status.success() {
+        bail!("Failed to push manifest list {}", opts.image);
+    }
+    info!("Successfully pushed manifest list {}!", opts.image);
+
+    if is_dry_run() {
+        return Ok(DRY_RUN_DIGEST.into());
+    }
+
+    Ok(fs::read_to_string(digest_file.path())
+        .into_diagnostic()?
+        .trim()
+        .to_string())
+}
+
+fn buildah_manifest_add(manifest: &str, image: &str) -> Command {
+    cmd!(
+        "buildah",
+        "manifest",
+        "add",
+        manifest,
+        format!("docker://{image}"),
+    )
+}
+
+fn buildah_manifest_push(manifest: &str, image: &str, digest_file: &Path) -> Command {
+    // The per-platform images have already been pushed,
+    // so only the manifest list itself needs to be pushed.
+    cmd!(
+        "buildah",
+        "manifest",
+        "push",
+        "--all=false",
+        "--digestfile",
+        digest_file,
+        manifest,
+        format!("docker://{image}"),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{buildah_manifest_add, buildah_manifest_push};
+
+    #[test]
+    fn manifest_commands() {
+        let manifest = "localhost/bluebuild-manifest-test";
+
+        let command = buildah_manifest_add(manifest, "ghcr.io/octocat/test:latest-amd64");
+        assert_eq!(command.get_program(), "buildah");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            [
+                "manifest",
+                "add",
+                manifest,
+                "docker://ghcr.io/octocat/test:latest-amd64",
+            ]
+        );
+
+        let command = buildah_manifest_push(
+            manifest,
+            "ghcr.io/octocat/test:latest",
+            Path::new("/tmp/digest"),
+        );
+        assert_eq!(command.get_program(), "buildah");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            [
+                "manifest",
+                "push",
+                "--all=false",
+                "--digestfile",
+                "/tmp/digest",
+                manifest,
+                "docker://ghcr.io/octocat/test:latest",
+            ]
+        );
+    }
+}
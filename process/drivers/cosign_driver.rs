@@ -1,4 +1,10 @@
-use std::{fmt::Debug, fs, io::Write, path::Path, process::Stdio};
+use std::{
+    fmt::Debug,
+    fs,
+    io::Write,
+    path::Path,
+    process::{Output, Stdio},
+};
 
 use blue_build_utils::{
     cmd,
@@ -6,16 +12,29 @@ use blue_build_utils::{
     credentials::Credentials,
 };
 use log::{debug, trace};
-use miette::{bail, miette, Context, IntoDiagnostic, Result};
+use miette::{bail, miette, Context, IntoDiagnostic, Report, Result};
 
-use crate::drivers::opts::VerifyType;
+use crate::{drivers::opts::VerifyType, dry_run::DryRunCommand};
 
 use super::{
     functions::get_private_key,
-    opts::{CheckKeyPairOpts, GenerateKeyPairOpts, SignOpts, VerifyOpts},
-    SigningDriver,
+    opts::{
+        AttestOpts, CheckKeyPairOpts, GenerateKeyPairOpts, SignOpts, VerifyAttestationOpts,
+        VerifyOpts,
+    },
+    SigningDriver, VERIFICATION_FAILED,
 };
 
+/// Messages that `cosign` prints when it checked an image and
+/// found no signature or attestation that could be verified.
+const VERIFICATION_FAILURES: &[&str] = &[
+    "no matching signatures",
+    "no signatures found",
+    "no matching attestations",
+    "none of the expected identities matched",
+    "invalid signature",
+];
+
 #[derive(Debug)]
 pub struct CosignDriver;
 
@@ -98,6 +117,9 @@ impl SigningDriver for CosignDriver {
             );
 
             trace!("{command:?}");
+            if command.print_if_dry_run() {
+                return Ok(());
+            }
             let mut child = command.spawn().into_diagnostic()?;
 
             write!(
@@ -133,7 +155,7 @@ impl SigningDriver for CosignDriver {
         );
 
         trace!("{command:?}");
-        if !command.status().into_diagnostic()?.success() {
+        if !command.dry_run_status().into_diagnostic()?.success() {
             bail!("Failed to sign {image_digest}");
         }
 
@@ -157,16 +179,103 @@ impl SigningDriver for CosignDriver {
                     ),
                 };
             },
-            image_name_tag
+            image_name_tag,
+            stdout = Stdio::null(),
+        );
+
+        trace!("{command:?}");
+        let output = command.dry_run_output().into_diagnostic()?;
+        if !output.status.success() {
+            return Err(verify_error(
+                &output,
+                &format!("Failed to verify {image_name_tag}"),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn attest(opts: &AttestOpts) -> Result<()> {
+        trace!("CosignDriver::attest({opts:?})");
+
+        let path = opts.dir.as_ref().map_or_else(|| Path::new("."), |dir| dir);
+        let image_digest: &str = opts.image.as_ref();
+        let mut command = cmd!(
+            "cosign",
+            "attest",
+            if let Ok(key) = get_private_key(path) => format!("--key={key}"),
+            "--predicate",
+            &*opts.predicate,
+            "--type",
+            &*opts.predicate_type,
+            image_digest,
+            COSIGN_PASSWORD => "",
+            COSIGN_YES => "true",
         );
 
         trace!("{command:?}");
-        if !command.status().into_diagnostic()?.success() {
-            bail!("Failed to verify {image_name_tag}");
+        if !command.dry_run_status().into_diagnostic()?.success() {
+            bail!("Failed to attest {image_digest}");
         }
 
         Ok(())
     }
+
+    fn verify_attestation(opts: &VerifyAttestationOpts) -> Result<()> {
+        trace!("CosignDriver::verify_attestation({opts:?})");
+
+        let image_name_tag: &str = opts.image.as_ref();
+        let mut command = cmd!(
+            "cosign",
+            "verify-attestation",
+            "--type",
+            &*opts.predicate_type,
+            |c| {
+                match &opts.verify_type {
+                    VerifyType::File(path) => cmd!(c, format!("--key={}", path.display())),
+                    VerifyType::Keyless { issuer, identity } => cmd!(
+                        c,
+                        "--certificate-identity-regexp",
+                        identity as &str,
+                        "--certificate-oidc-issuer",
+                        issuer as &str,
+                    ),
+                }
+            },
+            image_name_tag,
+            stdout = Stdio::null(),
+        );
+
+        trace!("{command:?}");
+        let output = command.dry_run_output().into_diagnostic()?;
+        if !output.status.success() {
+            return Err(verify_error(
+                &output,
+                &format!(
+                    "Failed to verify {} attestation for {image_name_tag}",
+                    opts.predicate_type
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Creates the error for a `cosign verify*` command that failed, which is
+/// marked as [`VERIFICATION_FAILED`] when cosign was able to check the image.
+fn verify_error(output: &Output, message: &str) -> Report {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.trim();
+
+    if VERIFICATION_FAILURES
+        .iter()
+        .any(|failure| stderr.contains(failure))
+    {
+        miette!(code = VERIFICATION_FAILED, "{message}:\n{stderr}")
+    } else {
+        miette!("{message}:\n{stderr}")
+    }
 }
 
 #[cfg(test)]
@@ -25,13 +25,14 @@ mod metadata;
 use crate::{
     drivers::{
         opts::{
-            BuildOpts, BuildTagPushOpts, GetMetadataOpts, PushOpts, RunOpts, RunOptsEnv,
-            RunOptsVolume, TagOpts,
+            BuildOpts, BuildTagPushOpts, GetMetadataOpts, ManifestPushOpts, PushOpts, RunOpts,
+            RunOptsEnv, RunOptsVolume, TagOpts,
         },
         traits::{BuildDriver, DriverVersion, InspectDriver, RunDriver},
-        types::ImageMetadata,
         types::Platform,
+        types::{CacheTarget, ImageMetadata},
     },
+    dry_run::{is_dry_run, DryRunCommand, DRY_RUN_DIGEST},
     logging::CommandLogging,
     signal_handler::{add_cid, remove_cid, ContainerRuntime, ContainerSignalId},
 };
@@ -48,6 +49,11 @@ struct DockerVersionJson {
     pub client: DockerVerisonJsonClient,
 }
 
+#[derive(Debug, Deserialize)]
+struct DockerManifestDigest {
+    pub digest: String,
+}
+
 static DOCKER_SETUP: Lazy<Mutex<bool>> = Lazy::new(|| Mutex::new(false));
 
 #[derive(Debug)]
@@ -87,7 +93,7 @@ impl DockerDriver {
                 "--driver=docker-container",
                 "--name=bluebuild",
             )
-            .output()
+            .dry_run_output()
             .into_diagnostic()?;
 
             if !create_out.status.success() {
@@ -126,6 +132,10 @@ impl BuildDriver for DockerDriver {
             warn!("Squash is deprecated for docker so this build will not squash");
         }
 
+        if opts.cache_to.is_some() {
+            warn!("Exporting the build cache is only supported when building with buildx");
+        }
+
         trace!("docker build -t {} -f {CONTAINER_FILE} .", opts.image);
         let status = cmd!(
             "docker",
@@ -134,13 +144,21 @@ impl BuildDriver for DockerDriver {
                 "--platform",
                 opts.platform.to_string(),
             ],
+            if let Some(cache_from) = opts.cache_from.and_then(cache_from_arg) => [
+                "--cache-from",
+                cache_from,
+            ],
+            for secret in opts.secrets.iter() => [
+                "--secret",
+                secret.to_string(),
+            ],
             "-t",
             &*opts.image,
             "-f",
             &*opts.containerfile,
             ".",
         )
-        .status()
+        .dry_run_status()
         .into_diagnostic()?;
 
         if status.success() {
@@ -156,7 +174,7 @@ impl BuildDriver for DockerDriver {
 
         trace!("docker tag {} {}", opts.src_image, opts.dest_image);
         let status = cmd!("docker", "tag", &*opts.src_image, &*opts.dest_image,)
-            .status()
+            .dry_run_status()
             .into_diagnostic()?;
 
         if status.success() {
@@ -172,7 +190,7 @@ impl BuildDriver for DockerDriver {
 
         trace!("docker push {}", opts.image);
         let status = cmd!("docker", "push", &*opts.image)
-            .status()
+            .dry_run_status()
             .into_diagnostic()?;
 
         if status.success() {
@@ -183,6 +201,61 @@ impl BuildDriver for DockerDriver {
         Ok(())
     }
 
+    fn manifest_push(opts: &ManifestPushOpts) -> Result<String> {
+        trace!("DockerDriver::manifest_push({opts:#?})");
+
+        let use_builder = !env::var(DOCKER_HOST).is_ok_and(|dh| !dh.is_empty());
+        if use_builder {
+            Self::setup()?;
+        }
+
+        let command = docker_manifest_create(opts, use_builder);
+        trace!("{command:?}");
+
+        if !command
+            .build_status(&opts.image, "Pushing Manifest")
+            .into_diagnostic()?
+            .success()
+        {
+            bail!("Failed to push manifest list {}", opts.image);
+        }
+        info!("Successfully pushed manifest list {}!", opts.image);
+
+        if is_dry_run() {
+            return Ok(DRY_RUN_DIGEST.into());
+        }
+
+        let mut command = cmd!(
+            "docker",
+            "buildx",
+            |command|? {
+                if !env::var(DOCKER_HOST).is_ok_and(|dh| !dh.is_empty()) {
+                    cmd!(command, "--builder=bluebuild");
+                }
+            },
+            "imagetools",
+            "inspect",
+            "--format",
+            "{{json .Manifest}}",
+            &*opts.image,
+        );
+        trace!("{command:?}");
+
+        let output = command.output().into_diagnostic()?;
+
+        if !output.status.success() {
+            bail!(
+                "Failed to inspect manifest list {}:\n{}",
+                opts.image,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let manifest: DockerManifestDigest =
+            serde_json::from_slice(&output.stdout).into_diagnostic()?;
+        Ok(manifest.digest)
+    }
+
     fn login() -> Result<()> {
         trace!("DockerDriver::login()");
 
@@ -205,6 +278,9 @@ impl BuildDriver for DockerDriver {
             );
 
             trace!("{command:?}");
+            if command.print_if_dry_run() {
+                return Ok(());
+            }
             let mut child = command.spawn().into_diagnostic()?;
 
             write!(
@@ -313,6 +389,18 @@ impl BuildDriver for DockerDriver {
                     "--cache-to",
                     "type=gha",
                 ],
+            if let Some(cache_from) = opts.cache_from.and_then(cache_from_arg) => [
+                "--cache-from",
+                cache_from,
+            ],
+            if let Some(cache_to) = opts.cache_to => [
+                "--cache-to",
+                cache_to_arg(cache_to),
+            ],
+            for secret in opts.secrets.iter() => [
+                "--secret",
+                secret.to_string(),
+            ],
         );
 
         let final_images = match (opts.image.as_deref(), opts.archive_path.as_deref()) {
@@ -376,6 +464,36 @@ impl BuildDriver for DockerDriver {
     }
 }
 
+/// The buildx `--cache-from` value for a cache target.
+///
+/// Local caches that haven't been exported yet are skipped
+/// as there is nothing to import.
+fn cache_from_arg(target: &CacheTarget) -> Option<String> {
+    match target {
+        CacheTarget::Registry(repo) => Some(format!("type=registry,ref={repo}")),
+        CacheTarget::Local(dir) if dir.join("index.json").is_file() => {
+            Some(format!("type=local,src={}", dir.display()))
+        }
+        CacheTarget::Local(dir) => {
+            debug!("No build cache exists at {}", dir.display());
+            None
+        }
+    }
+}
+
+/// The buildx `--cache-to` value for a cache target.
+///
+/// All layers are exported so that the intermediate
+/// stages can be reused as well.
+fn cache_to_arg(target: &CacheTarget) -> String {
+    match target {
+        CacheTarget::Registry(repo) => {
+            format!("type=registry,ref={repo},mode=max,image-manifest=true,oci-mediatypes=true")
+        }
+        CacheTarget::Local(dir) => format!("type=local,dest={},mode=max", dir.display()),
+    }
+}
+
 impl InspectDriver for DockerDriver {
     fn get_metadata(opts: &GetMetadataOpts) -> Result<ImageMetadata> {
         get_metadata_cache(opts)
@@ -489,3 +607,44 @@ fn docker_run(opts: &RunOpts, cid_file: &Path) -> Command {
 
     command
 }
+
+fn docker_manifest_create(opts: &ManifestPushOpts, use_builder: bool) -> Command {
+    cmd!(
+        "docker",
+        "buildx",
+        if use_builder => "--builder=bluebuild",
+        "imagetools",
+        "create",
+        "-t",
+        &*opts.image,
+        for image in opts.images.iter() => &**image,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use crate::drivers::opts::ManifestPushOpts;
+
+    use super::docker_manifest_create;
+
+    #[rstest]
+    #[case(true, &["buildx", "--builder=bluebuild", "imagetools", "create", "-t", "ghcr.io/octocat/test:latest", "ghcr.io/octocat/test:latest-amd64", "ghcr.io/octocat/test:latest-arm64"])]
+    #[case(false, &["buildx", "imagetools", "create", "-t", "ghcr.io/octocat/test:latest", "ghcr.io/octocat/test:latest-amd64", "ghcr.io/octocat/test:latest-arm64"])]
+    fn manifest_create(#[case] use_builder: bool, #[case] expected: &[&str]) {
+        let command = docker_manifest_create(
+            &ManifestPushOpts::builder()
+                .image("ghcr.io/octocat/test:latest")
+                .images(vec![
+                    "ghcr.io/octocat/test:latest-amd64".into(),
+                    "ghcr.io/octocat/test:latest-arm64".into(),
+                ])
+                .build(),
+            use_builder,
+        );
+
+        assert_eq!(command.get_program(), "docker");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), expected);
+    }
+}
@@ -0,0 +1,445 @@
+use std::{fmt::Display, path::PathBuf, str::FromStr};
+
+use blue_build_utils::constants::{
+    BB_CI_BRANCH_VAR, BB_CI_COMMIT_SHA_VAR, BB_CI_DEFAULT_BRANCH_VAR, BB_CI_PRESET,
+    BB_CI_PR_NUMBER_VAR, BB_CI_REGISTRY_VAR, BB_CI_REPO_URL_VAR, BUILDKITE, BUILDKITE_BRANCH,
+    BUILDKITE_COMMIT, BUILDKITE_PIPELINE_DEFAULT_BRANCH, BUILDKITE_PULL_REQUEST, BUILDKITE_REPO,
+    CI, CI_COMMIT_BRANCH, CI_COMMIT_PULL_REQUEST, CI_COMMIT_SHA, CI_REPO_DEFAULT_BRANCH,
+    CI_REPO_URL, DRONE, DRONE_COMMIT_BRANCH, DRONE_COMMIT_SHA, DRONE_PULL_REQUEST,
+    DRONE_REPO_BRANCH, DRONE_REPO_LINK, JENKINS_BRANCH_NAME, JENKINS_CHANGE_ID, JENKINS_GIT_COMMIT,
+    JENKINS_GIT_URL, JENKINS_URL,
+};
+use colored::Colorize;
+use log::{trace, warn};
+use miette::{bail, miette, Report};
+
+#[cfg(not(test))]
+use blue_build_utils::get_env_var;
+
+#[cfg(test)]
+use blue_build_utils::test_utils::get_env_var;
+
+use super::{functions::generate_ci_tags, opts::GenerateTagsOpts, CiDriver};
+
+/// A CI driver that reads its information from
+/// environment variables named by the user.
+///
+/// The names of the variables are set with the
+/// `BB_CI_*_VAR` variables. Any that aren't set
+/// fall back to the `BB_CI_PRESET` for the CI system,
+/// which is detected automatically when not set.
+pub struct EnvDriver;
+
+impl EnvDriver {
+    /// Whether the user has configured this driver or
+    /// we are running on a CI system with a preset
+    /// that has all of the required variables set.
+    pub(super) fn is_configured() -> bool {
+        Self::is_explicit()
+            || (Preset::get().is_some()
+                && Field::REQUIRED
+                    .iter()
+                    .all(|&field| Self::get(field).is_ok()))
+    }
+
+    /// Whether the driver was explicitly configured by the user.
+    pub(super) fn is_explicit() -> bool {
+        get_env_var(BB_CI_PRESET).is_ok()
+            || Field::ALL
+                .iter()
+                .any(|field| get_env_var(field.config_var()).is_ok())
+    }
+
+    /// Gets the name of the environment variable that holds `field`.
+    fn var_name(field: Field) -> Option<String> {
+        get_env_var(field.config_var())
+            .ok()
+            .or_else(|| Preset::get()?.var(field).map(ToString::to_string))
+    }
+
+    /// Gets the value of `field`.
+    ///
+    /// Values that are empty or `false` are treated as unset
+    /// as some CI systems set the PR number to `false`
+    /// when not running for a PR.
+    fn get(field: Field) -> miette::Result<String> {
+        let Some(var) = Self::var_name(field) else {
+            bail!(
+                "No environment variable is configured for the {field}, set {} to the name of the variable that holds it",
+                field.config_var().bold(),
+            );
+        };
+
+        get_env_var(&var)
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty() && value != "false")
+            .inspect(|v| trace!("{var}={v}"))
+            .ok_or_else(|| miette!("The {field} variable {} is not set", var.bold()))
+    }
+}
+
+impl CiDriver for EnvDriver {
+    fn on_default_branch() -> bool {
+        Self::get(Field::DefaultBranch).is_ok_and(|default_branch| {
+            Self::get(Field::Branch).is_ok_and(|branch| default_branch == branch)
+        })
+    }
+
+    fn keyless_cert_identity() -> miette::Result<String> {
+        Err(miette!(
+            "Keyless signing is not supported with environment variable CI configuration"
+        ))
+    }
+
+    fn oidc_provider() -> miette::Result<String> {
+        Err(miette!(
+            "Keyless signing is not supported with environment variable CI configuration"
+        ))
+    }
+
+    fn generate_tags(opts: &GenerateTagsOpts) -> miette::Result<Vec<String>> {
+        generate_ci_tags()
+            .opts(opts)
+            .on_default_branch(Self::on_default_branch())
+            .branch(&Self::get(Field::Branch)?)
+            .commit_sha(&Self::get(Field::CommitSha)?)
+            .maybe_pr_number(Self::get(Field::PrNumber).ok().as_deref())
+            .call()
+    }
+
+    fn get_repo_url() -> miette::Result<String> {
+        Self::get(Field::RepoUrl).map(|url| url.trim_end_matches(".git").to_string())
+    }
+
+    /// Gets the registry from the configured variable.
+    ///
+    /// None of the presets provide a registry, so when no variable
+    /// is configured for it `localhost` is used like the `LocalDriver`.
+    fn get_registry() -> miette::Result<String> {
+        if Self::var_name(Field::Registry).is_none() {
+            warn!(
+                "No environment variable is configured for the registry, using localhost. Set {} to the name of the variable that holds it",
+                BB_CI_REGISTRY_VAR.bold()
+            );
+            return Ok(String::from("localhost"));
+        }
+
+        Ok(Self::get(Field::Registry)?.trim().to_lowercase())
+    }
+
+    fn default_ci_file_path() -> miette::Result<PathBuf> {
+        Err(miette!(
+            "Generating a CI file is not supported with environment variable CI configuration"
+        ))
+    }
+}
+
+/// A piece of CI information that is read from an environment variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Branch,
+    DefaultBranch,
+    CommitSha,
+    PrNumber,
+    Registry,
+    RepoUrl,
+}
+
+impl Field {
+    const ALL: [Self; 6] = [
+        Self::Branch,
+        Self::DefaultBranch,
+        Self::CommitSha,
+        Self::PrNumber,
+        Self::Registry,
+        Self::RepoUrl,
+    ];
+
+    /// The fields that are needed to generate tags
+    /// and labels for an image.
+    const REQUIRED: [Self; 3] = [Self::Branch, Self::CommitSha, Self::RepoUrl];
+
+    /// The variable used to set the name of the
+    /// environment variable that holds this field.
+    const fn config_var(self) -> &'static str {
+        match self {
+            Self::Branch => BB_CI_BRANCH_VAR,
+            Self::DefaultBranch => BB_CI_DEFAULT_BRANCH_VAR,
+            Self::CommitSha => BB_CI_COMMIT_SHA_VAR,
+            Self::PrNumber => BB_CI_PR_NUMBER_VAR,
+            Self::Registry => BB_CI_REGISTRY_VAR,
+            Self::RepoUrl => BB_CI_REPO_URL_VAR,
+        }
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Branch => "branch name",
+            Self::DefaultBranch => "default branch",
+            Self::CommitSha => "commit SHA",
+            Self::PrNumber => "PR number",
+            Self::Registry => "registry",
+            Self::RepoUrl => "repository URL",
+        })
+    }
+}
+
+/// The environment variables used by common CI systems.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Preset {
+    Jenkins,
+    Woodpecker,
+    Buildkite,
+    Drone,
+}
+
+impl Preset {
+    /// Gets the preset set in `BB_CI_PRESET` or
+    /// detects it from the CI system's variables.
+    fn get() -> Option<Self> {
+        if let Ok(preset) = get_env_var(BB_CI_PRESET) {
+            return preset.parse().inspect_err(|e| warn!("{e}")).ok();
+        }
+
+        if get_env_var(CI).is_ok_and(|ci| ci == "woodpecker") {
+            Some(Self::Woodpecker)
+        } else if get_env_var(BUILDKITE).is_ok() {
+            Some(Self::Buildkite)
+        } else if get_env_var(DRONE).is_ok() {
+            Some(Self::Drone)
+        } else if get_env_var(JENKINS_URL).is_ok() {
+            Some(Self::Jenkins)
+        } else {
+            None
+        }
+    }
+
+    /// The variable that holds `field` for this CI system.
+    const fn var(self, field: Field) -> Option<&'static str> {
+        match (self, field) {
+            (Self::Jenkins, Field::Branch) => Some(JENKINS_BRANCH_NAME),
+            (Self::Jenkins, Field::CommitSha) => Some(JENKINS_GIT_COMMIT),
+            (Self::Jenkins, Field::PrNumber) => Some(JENKINS_CHANGE_ID),
+            (Self::Jenkins, Field::RepoUrl) => Some(JENKINS_GIT_URL),
+            (Self::Woodpecker, Field::Branch) => Some(CI_COMMIT_BRANCH),
+            (Self::Woodpecker, Field::DefaultBranch) => Some(CI_REPO_DEFAULT_BRANCH),
+            (Self::Woodpecker, Field::CommitSha) => Some(CI_COMMIT_SHA),
+            (Self::Woodpecker, Field::PrNumber) => Some(CI_COMMIT_PULL_REQUEST),
+            (Self::Woodpecker, Field::RepoUrl) => Some(CI_REPO_URL),
+            (Self::Buildkite, Field::Branch) => Some(BUILDKITE_BRANCH),
+            (Self::Buildkite, Field::DefaultBranch) => Some(BUILDKITE_PIPELINE_DEFAULT_BRANCH),
+            (Self::Buildkite, Field::CommitSha) => Some(BUILDKITE_COMMIT),
+            (Self::Buildkite, Field::PrNumber) => Some(BUILDKITE_PULL_REQUEST),
+            (Self::Buildkite, Field::RepoUrl) => Some(BUILDKITE_REPO),
+            (Self::Drone, Field::Branch) => Some(DRONE_COMMIT_BRANCH),
+            (Self::Drone, Field::DefaultBranch) => Some(DRONE_REPO_BRANCH),
+            (Self::Drone, Field::CommitSha) => Some(DRONE_COMMIT_SHA),
+            (Self::Drone, Field::PrNumber) => Some(DRONE_PULL_REQUEST),
+            (Self::Drone, Field::RepoUrl) => Some(DRONE_REPO_LINK),
+            (_, Field::Registry) | (Self::Jenkins, Field::DefaultBranch) => None,
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_str() {
+            "jenkins" => Self::Jenkins,
+            "woodpecker" => Self::Woodpecker,
+            "buildkite" => Self::Buildkite,
+            "drone" => Self::Drone,
+            _ => {
+                bail!(
+                    "Unknown {BB_CI_PRESET} {}, expected one of jenkins, woodpecker, buildkite or drone",
+                    s.bold()
+                );
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use blue_build_utils::{
+        constants::{
+            BB_CI_BRANCH_VAR, BB_CI_COMMIT_SHA_VAR, BB_CI_DEFAULT_BRANCH_VAR, BB_CI_PRESET,
+            BB_CI_PR_NUMBER_VAR, BB_CI_REGISTRY_VAR, BUILDKITE, BUILDKITE_BRANCH, BUILDKITE_COMMIT,
+            BUILDKITE_PIPELINE_DEFAULT_BRANCH, BUILDKITE_PULL_REQUEST, BUILDKITE_REPO, CI,
+            CI_COMMIT_BRANCH, CI_COMMIT_PULL_REQUEST, CI_COMMIT_SHA, CI_REPO_DEFAULT_BRANCH,
+            CI_REPO_URL,
+        },
+        string_vec,
+        test_utils::set_env_var,
+    };
+    use oci_distribution::Reference;
+    use rstest::rstest;
+
+    use crate::drivers::{opts::GenerateTagsOpts, types::Platform, CiDriver};
+
+    use super::{EnvDriver, Preset};
+
+    const COMMIT_SHA: &str = "1234567";
+    const BR_REF_NAME: &str = "test";
+
+    fn setup_default_branch() {
+        setup();
+        set_env_var(CI_COMMIT_BRANCH, "main");
+    }
+
+    fn setup_pr_branch() {
+        setup();
+        set_env_var(CI_COMMIT_BRANCH, BR_REF_NAME);
+        set_env_var(CI_COMMIT_PULL_REQUEST, "12");
+    }
+
+    fn setup_branch() {
+        setup();
+        set_env_var(CI_COMMIT_BRANCH, BR_REF_NAME);
+    }
+
+    fn setup() {
+        set_env_var(CI, "woodpecker");
+        set_env_var(CI_REPO_DEFAULT_BRANCH, "main");
+        set_env_var(CI_COMMIT_SHA, "1234567890");
+        set_env_var(CI_REPO_URL, "https://example.com/test-owner/test-repo.git");
+        set_env_var(BB_CI_REGISTRY_VAR, "MY_REGISTRY");
+        set_env_var("MY_REGISTRY", "Registry.example.com/Test-Owner");
+    }
+
+    #[test]
+    fn get_registry() {
+        setup_default_branch();
+
+        let registry = EnvDriver::get_registry().unwrap();
+
+        assert_eq!(registry, "registry.example.com/test-owner");
+    }
+
+    #[test]
+    fn get_repo_url() {
+        setup_branch();
+
+        let url = EnvDriver::get_repo_url().unwrap();
+
+        assert_eq!(url, "https://example.com/test-owner/test-repo");
+    }
+
+    #[test]
+    fn on_default_branch_true() {
+        setup_default_branch();
+
+        assert!(EnvDriver::on_default_branch());
+    }
+
+    #[test]
+    fn on_default_branch_false() {
+        setup_pr_branch();
+
+        assert!(!EnvDriver::on_default_branch());
+    }
+
+    #[test]
+    fn detect_preset() {
+        assert_eq!(Preset::get(), None);
+        assert!(!EnvDriver::is_configured());
+
+        set_env_var(BUILDKITE, "true");
+        assert_eq!(Preset::get(), Some(Preset::Buildkite));
+        assert!(!EnvDriver::is_configured());
+
+        set_env_var(BUILDKITE_BRANCH, BR_REF_NAME);
+        set_env_var(BUILDKITE_COMMIT, "1234567890");
+        assert!(!EnvDriver::is_configured());
+
+        set_env_var(
+            BUILDKITE_REPO,
+            "https://example.com/test-owner/test-repo.git",
+        );
+        assert!(EnvDriver::is_configured());
+        assert!(!EnvDriver::is_explicit());
+
+        set_env_var(BB_CI_PRESET, "Drone");
+        assert_eq!(Preset::get(), Some(Preset::Drone));
+        assert!(EnvDriver::is_explicit());
+    }
+
+    #[test]
+    fn pr_number_false() {
+        set_env_var(BUILDKITE, "true");
+        set_env_var(BUILDKITE_BRANCH, BR_REF_NAME);
+        set_env_var(BUILDKITE_PIPELINE_DEFAULT_BRANCH, "main");
+        set_env_var(BUILDKITE_COMMIT, "1234567890");
+        set_env_var(BUILDKITE_PULL_REQUEST, "false");
+
+        let oci_ref: Reference = "ghcr.io/ublue-os/silverblue-main".parse().unwrap();
+        let mut tags = EnvDriver::generate_tags(
+            &GenerateTagsOpts::builder()
+                .oci_ref(&oci_ref)
+                .platform(Platform::LinuxAmd64)
+                .build(),
+        )
+        .unwrap();
+        tags.sort();
+
+        assert_eq!(tags, string_vec![format!("{COMMIT_SHA}-40"), "br-test-40"]);
+    }
+
+    #[test]
+    fn user_named_vars() {
+        set_env_var(BB_CI_BRANCH_VAR, "MY_BRANCH");
+        set_env_var(BB_CI_DEFAULT_BRANCH_VAR, "MY_DEFAULT_BRANCH");
+        set_env_var(BB_CI_COMMIT_SHA_VAR, "MY_SHA");
+        set_env_var(BB_CI_PR_NUMBER_VAR, "MY_PR");
+        set_env_var("MY_BRANCH", "main");
+        set_env_var("MY_DEFAULT_BRANCH", "main");
+
+        assert!(EnvDriver::is_explicit());
+        assert!(EnvDriver::on_default_branch());
+        assert_eq!(EnvDriver::get_registry().unwrap(), "localhost");
+
+        set_env_var(BB_CI_REGISTRY_VAR, "MY_REGISTRY");
+        EnvDriver::get_registry().unwrap_err();
+    }
+
+    #[test]
+    fn preset_without_registry() {
+        set_env_var(BUILDKITE, "true");
+        set_env_var(BUILDKITE_BRANCH, BR_REF_NAME);
+        set_env_var(BUILDKITE_COMMIT, "1234567890");
+        set_env_var(
+            BUILDKITE_REPO,
+            "https://example.com/test-owner/test-repo.git",
+        );
+
+        assert!(EnvDriver::is_configured());
+        assert_eq!(EnvDriver::get_registry().unwrap(), "localhost");
+    }
+
+    #[rstest]
+    #[case::default_branch(setup_default_branch, "latest")]
+    #[case::pr_branch(setup_pr_branch, "pr-12-40")]
+    #[case::branch(setup_branch, "br-test-40")]
+    fn generate_tags(#[case] setup: impl FnOnce(), #[case] expected: &str) {
+        setup();
+        let oci_ref: Reference = "ghcr.io/ublue-os/silverblue-main".parse().unwrap();
+
+        let tags = EnvDriver::generate_tags(
+            &GenerateTagsOpts::builder()
+                .oci_ref(&oci_ref)
+                .platform(Platform::LinuxAmd64)
+                .build(),
+        )
+        .unwrap();
+
+        assert!(tags.iter().any(|tag| tag == expected), "{tags:?}");
+        assert!(
+            tags.iter().any(|tag| *tag == format!("{COMMIT_SHA}-40")),
+            "{tags:?}"
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use std::path::PathBuf;
+
+use blue_build_utils::constants::{
+    ACTIONS_ID_TOKEN_REQUEST_URL, FORGEJO_REPOSITORY, FORGEJO_REPOSITORY_OWNER, FORGEJO_SERVER_URL,
+    GITHUB_EVENT_NAME, GITHUB_EVENT_PATH, GITHUB_REF, GITHUB_REF_NAME, GITHUB_REPOSITORY_OWNER,
+    GITHUB_RESPOSITORY, GITHUB_SERVER_URL, GITHUB_SHA, GITHUB_WORKFLOW_REF, PR_EVENT_NUMBER,
+};
+use log::trace;
+use miette::bail;
+
+#[cfg(not(test))]
+use blue_build_utils::get_env_var;
+
+#[cfg(test)]
+use blue_build_utils::test_utils::get_env_var;
+
+use super::{
+    functions::generate_ci_tags, github_driver::event::Event, opts::GenerateTagsOpts, CiDriver,
+};
+
+pub struct ForgejoDriver;
+
+impl ForgejoDriver {
+    /// The URL of the Forgejo/Gitea instance without a trailing slash.
+    fn server_url() -> miette::Result<String> {
+        get_env_var(FORGEJO_SERVER_URL)
+            .or_else(|_| get_env_var(GITHUB_SERVER_URL))
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+    }
+
+    /// The host of the Forgejo/Gitea instance which
+    /// also serves as its container registry.
+    fn server_host() -> miette::Result<String> {
+        let url = Self::server_url()?;
+        Ok(url
+            .split_once("://")
+            .map_or(url.as_str(), |(_, host)| host)
+            .to_string())
+    }
+
+    /// The PR number is taken from `GH_PR_EVENT_NUMBER` if it
+    /// was set in the workflow, otherwise from a `refs/pull/<num>/head` ref.
+    fn pr_number() -> miette::Result<String> {
+        get_env_var(PR_EVENT_NUMBER).or_else(|_| {
+            let commit_ref = get_env_var(GITHUB_REF)?;
+            commit_ref
+                .strip_prefix("refs/pull/")
+                .and_then(|pr| pr.split('/').next())
+                .filter(|pr| !pr.is_empty())
+                .map(ToString::to_string)
+                .ok_or_else(|| miette::miette!("{GITHUB_REF} {commit_ref} is not a PR ref"))
+        })
+    }
+}
+
+impl CiDriver for ForgejoDriver {
+    fn on_default_branch() -> bool {
+        get_env_var(GITHUB_EVENT_PATH)
+            .is_ok_and(|path| Event::try_new(path).is_ok_and(|e| e.on_default_branch()))
+    }
+
+    fn keyless_cert_identity() -> miette::Result<String> {
+        Ok(format!(
+            "{}/{}",
+            Self::server_url()?,
+            get_env_var(GITHUB_WORKFLOW_REF)?
+        ))
+    }
+
+    fn oidc_provider() -> miette::Result<String> {
+        if get_env_var(ACTIONS_ID_TOKEN_REQUEST_URL).is_err() {
+            bail!(
+                "This Forgejo instance does not provide OIDC tokens to the workflow, {} is not set",
+                ACTIONS_ID_TOKEN_REQUEST_URL
+            );
+        }
+        Ok(format!("{}/api/actions", Self::server_url()?))
+    }
+
+    fn generate_tags(opts: &GenerateTagsOpts) -> miette::Result<Vec<String>> {
+        const PR_EVENTS: [&str; 2] = ["pull_request", "pull_request_target"];
+        let ref_name = get_env_var(GITHUB_REF_NAME).inspect(|v| trace!("{GITHUB_REF_NAME}={v}"))?;
+        let commit_sha = get_env_var(GITHUB_SHA).inspect(|v| trace!("{GITHUB_SHA}={v}"))?;
+        let pr_number = get_env_var(GITHUB_EVENT_NAME)
+            .inspect(|v| trace!("{GITHUB_EVENT_NAME}={v}"))
+            .is_ok_and(|event_name| PR_EVENTS.contains(&&*event_name))
+            .then(|| Self::pr_number().inspect(|v| trace!("pr_number={v}")))
+            .and_then(Result::ok);
+
+        generate_ci_tags()
+            .opts(opts)
+            .on_default_branch(Self::on_default_branch())
+            .branch(&ref_name)
+            .commit_sha(&commit_sha)
+            .maybe_pr_number(pr_number.as_deref())
+            .call()
+    }
+
+    fn get_repo_url() -> miette::Result<String> {
+        Ok(format!(
+            "{}/{}",
+            Self::server_url()?,
+            get_env_var(FORGEJO_REPOSITORY).or_else(|_| get_env_var(GITHUB_RESPOSITORY))?
+        ))
+    }
+
+    fn get_registry() -> miette::Result<String> {
+        Ok(format!(
+            "{}/{}",
+            Self::server_host()?,
+            get_env_var(FORGEJO_REPOSITORY_OWNER)
+                .or_else(|_| get_env_var(GITHUB_REPOSITORY_OWNER))?
+        )
+        .trim()
+        .to_lowercase())
+    }
+
+    fn default_ci_file_path() -> miette::Result<PathBuf> {
+        Ok(PathBuf::from(".forgejo/workflows/build.yml"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use blue_build_utils::{
+        constants::{
+            ACTIONS_ID_TOKEN_REQUEST_URL, GITHUB_EVENT_NAME, GITHUB_EVENT_PATH, GITHUB_REF,
+            GITHUB_REF_NAME, GITHUB_REPOSITORY_OWNER, GITHUB_RESPOSITORY, GITHUB_SERVER_URL,
+            GITHUB_SHA, GITHUB_WORKFLOW_REF,
+        },
+        test_utils::set_env_var,
+    };
+    use oci_distribution::Reference;
+    use rstest::rstest;
+
+    use crate::drivers::{opts::GenerateTagsOpts, types::Platform, CiDriver};
+
+    use super::ForgejoDriver;
+
+    const COMMIT_SHA: &str = "1234567";
+    const BR_REF_NAME: &str = "test";
+
+    fn setup_default_branch() {
+        setup();
+        set_env_var(
+            GITHUB_EVENT_PATH,
+            "../test-files/github-events/default-branch.json",
+        );
+        set_env_var(GITHUB_REF, "refs/heads/main");
+        set_env_var(GITHUB_REF_NAME, "main");
+    }
+
+    fn setup_pr_branch() {
+        setup();
+        set_env_var(
+            GITHUB_EVENT_PATH,
+            "../test-files/github-events/pr-branch.json",
+        );
+        set_env_var(GITHUB_EVENT_NAME, "pull_request");
+        set_env_var(GITHUB_REF, "refs/pull/12/head");
+        set_env_var(GITHUB_REF_NAME, BR_REF_NAME);
+    }
+
+    fn setup_branch() {
+        setup();
+        set_env_var(GITHUB_EVENT_PATH, "../test-files/github-events/branch.json");
+        set_env_var(GITHUB_REF, "refs/heads/test");
+        set_env_var(GITHUB_REF_NAME, BR_REF_NAME);
+    }
+
+    fn setup() {
+        set_env_var(GITHUB_EVENT_NAME, "push");
+        set_env_var(GITHUB_SHA, "1234567890");
+        set_env_var(GITHUB_SERVER_URL, "https://code.example.com/");
+        set_env_var(GITHUB_REPOSITORY_OWNER, "Test-Owner");
+        set_env_var(GITHUB_RESPOSITORY, "Test-Owner/test-repo");
+        set_env_var(
+            GITHUB_WORKFLOW_REF,
+            "Test-Owner/test-repo/.forgejo/workflows/build.yml@refs/heads/main",
+        );
+    }
+
+    #[test]
+    fn get_registry() {
+        setup_default_branch();
+
+        let registry = ForgejoDriver::get_registry().unwrap();
+
+        assert_eq!(registry, "code.example.com/test-owner");
+    }
+
+    #[test]
+    fn get_repo_url() {
+        setup_branch();
+
+        let url = ForgejoDriver::get_repo_url().unwrap();
+
+        assert_eq!(url, "https://code.example.com/Test-Owner/test-repo");
+    }
+
+    #[test]
+    fn on_default_branch_true() {
+        setup_default_branch();
+
+        assert!(ForgejoDriver::on_default_branch());
+    }
+
+    #[test]
+    fn on_default_branch_false() {
+        setup_pr_branch();
+
+        assert!(!ForgejoDriver::on_default_branch());
+    }
+
+    #[test]
+    fn keyless() {
+        setup_default_branch();
+
+        ForgejoDriver::oidc_provider().unwrap_err();
+
+        set_env_var(
+            ACTIONS_ID_TOKEN_REQUEST_URL,
+            "https://code.example.com/api/actions/_apis/pipelines/workflows/1/idtoken",
+        );
+
+        assert_eq!(
+            ForgejoDriver::oidc_provider().unwrap(),
+            "https://code.example.com/api/actions"
+        );
+        assert_eq!(
+            ForgejoDriver::keyless_cert_identity().unwrap(),
+            "https://code.example.com/Test-Owner/test-repo/.forgejo/workflows/build.yml@refs/heads/main"
+        );
+    }
+
+    fn setup_pr_target() {
+        setup_pr_branch();
+        set_env_var(GITHUB_EVENT_NAME, "pull_request_target");
+    }
+
+    #[rstest]
+    #[case::default_branch(setup_default_branch, "latest")]
+    #[case::pr_branch(setup_pr_branch, "pr-12-40")]
+    #[case::pr_target(setup_pr_target, "pr-12-40")]
+    #[case::branch(setup_branch, "br-test-40")]
+    fn generate_tags(#[case] setup: impl FnOnce(), #[case] expected: &str) {
+        setup();
+        let oci_ref: Reference = "ghcr.io/ublue-os/silverblue-main".parse().unwrap();
+
+        let tags = ForgejoDriver::generate_tags(
+            &GenerateTagsOpts::builder()
+                .oci_ref(&oci_ref)
+                .platform(Platform::LinuxAmd64)
+                .build(),
+        )
+        .unwrap();
+
+        assert!(tags.iter().any(|tag| tag == expected), "{tags:?}");
+        assert!(
+            tags.iter().any(|tag| *tag == format!("{COMMIT_SHA}-40")),
+            "{tags:?}"
+        );
+    }
+}
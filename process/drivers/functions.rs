@@ -2,11 +2,113 @@ use std::{env, path::Path};
 
 use blue_build_utils::{
     constants::{BB_PRIVATE_KEY, COSIGN_PRIVATE_KEY, COSIGN_PRIV_PATH, COSIGN_PUB_PATH},
-    string,
+    string, string_vec,
 };
+use bon::builder;
+use log::{trace, warn};
 use miette::{bail, Result};
 
-use super::opts::PrivateKey;
+use super::{
+    opts::{GenerateTagsOpts, PrivateKey},
+    types::CacheTarget,
+    Driver,
+};
+
+/// Generates the tags for an image built on a CI system.
+///
+/// Builds on the default branch are tagged `latest` and with a
+/// timestamp, builds for a PR with `pr-<number>`, and builds for
+/// any other branch with `br-<branch>`. Every build is also tagged
+/// with the short commit SHA. Alt tags replace `latest` on the
+/// default branch and are added to the PR and branch tags otherwise.
+#[builder]
+pub(super) fn generate_ci_tags(
+    opts: &GenerateTagsOpts<'_>,
+    on_default_branch: bool,
+    branch: &str,
+    commit_sha: &str,
+    /// The number of the PR, if the build is for one.
+    pr_number: Option<&str>,
+) -> Result<Vec<String>> {
+    let timestamp = blue_build_utils::get_tag_timestamp();
+    let os_version = Driver::get_os_version()
+        .oci_ref(opts.oci_ref)
+        .platform(opts.platform)
+        .call()
+        .inspect(|v| trace!("os_version={v}"))?;
+    let short_sha = commit_sha.get(..7).unwrap_or(commit_sha);
+
+    let tags = match (on_default_branch, opts.alt_tags.as_ref(), pr_number) {
+        (true, None, _) => {
+            string_vec![
+                "latest",
+                &timestamp,
+                format!("{os_version}"),
+                format!("{timestamp}-{os_version}"),
+                format!("{short_sha}-{os_version}"),
+            ]
+        }
+        (true, Some(alt_tags), _) => alt_tags
+            .iter()
+            .flat_map(|alt| {
+                string_vec![
+                    &**alt,
+                    format!("{alt}-{os_version}"),
+                    format!("{timestamp}-{alt}-{os_version}"),
+                    format!("{short_sha}-{alt}-{os_version}"),
+                ]
+            })
+            .collect(),
+        (false, None, Some(pr_num)) => {
+            vec![
+                format!("pr-{pr_num}-{os_version}"),
+                format!("{short_sha}-{os_version}"),
+            ]
+        }
+        (false, None, None) => {
+            vec![
+                format!("br-{branch}-{os_version}"),
+                format!("{short_sha}-{os_version}"),
+            ]
+        }
+        (false, Some(alt_tags), Some(pr_num)) => alt_tags
+            .iter()
+            .flat_map(|alt| {
+                vec![
+                    format!("pr-{pr_num}-{alt}-{os_version}"),
+                    format!("{short_sha}-{alt}-{os_version}"),
+                ]
+            })
+            .collect(),
+        (false, Some(alt_tags), None) => alt_tags
+            .iter()
+            .flat_map(|alt| {
+                vec![
+                    format!("br-{branch}-{alt}-{os_version}"),
+                    format!("{short_sha}-{alt}-{os_version}"),
+                ]
+            })
+            .collect(),
+    };
+    trace!("{tags:?}");
+
+    Ok(tags)
+}
+
+/// Gets the repository of a registry cache for drivers
+/// that don't support local cache directories.
+pub(super) fn registry_cache<'a>(driver: &str, target: Option<&'a CacheTarget>) -> Option<&'a str> {
+    match target? {
+        CacheTarget::Registry(repo) => Some(repo),
+        CacheTarget::Local(dir) => {
+            warn!(
+                "The {driver} driver only supports registry caches, ignoring {}",
+                dir.display()
+            );
+            None
+        }
+    }
+}
 
 pub(super) fn get_private_key<P>(path: P) -> Result<PrivateKey>
 where
@@ -50,3 +152,112 @@ where
         },
     )
 }
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use blue_build_utils::string_vec;
+    use oci_distribution::Reference;
+    use rstest::rstest;
+
+    use crate::{
+        drivers::{opts::GenerateTagsOpts, types::Platform},
+        test::{TEST_TAG_1, TEST_TAG_2, TIMESTAMP},
+    };
+
+    use super::generate_ci_tags;
+
+    const COMMIT_SHA: &str = "1234567";
+    const BR_REF_NAME: &str = "test";
+
+    #[rstest]
+    #[case::default_branch(
+        true,
+        None,
+        None,
+        string_vec![
+            format!("{}-40", &*TIMESTAMP),
+            "latest",
+            &*TIMESTAMP,
+            format!("{COMMIT_SHA}-40"),
+            "40",
+        ],
+    )]
+    #[case::default_branch_alt_tags(
+        true,
+        None,
+        Some(bon::vec![TEST_TAG_1, TEST_TAG_2]),
+        string_vec![
+            TEST_TAG_1,
+            format!("{TEST_TAG_1}-40"),
+            format!("{}-{TEST_TAG_1}-40", &*TIMESTAMP),
+            format!("{COMMIT_SHA}-{TEST_TAG_1}-40"),
+            TEST_TAG_2,
+            format!("{TEST_TAG_2}-40"),
+            format!("{}-{TEST_TAG_2}-40", &*TIMESTAMP),
+            format!("{COMMIT_SHA}-{TEST_TAG_2}-40"),
+        ],
+    )]
+    #[case::pr_branch(
+        false,
+        Some("12"),
+        None,
+        string_vec!["pr-12-40", format!("{COMMIT_SHA}-40")],
+    )]
+    #[case::pr_branch_alt_tags(
+        false,
+        Some("12"),
+        Some(bon::vec![TEST_TAG_1, TEST_TAG_2]),
+        string_vec![
+            format!("pr-12-{TEST_TAG_1}-40"),
+            format!("{COMMIT_SHA}-{TEST_TAG_1}-40"),
+            format!("pr-12-{TEST_TAG_2}-40"),
+            format!("{COMMIT_SHA}-{TEST_TAG_2}-40"),
+        ],
+    )]
+    #[case::branch(
+        false,
+        None,
+        None,
+        string_vec![format!("{COMMIT_SHA}-40"), "br-test-40"],
+    )]
+    #[case::branch_alt_tags(
+        false,
+        None,
+        Some(bon::vec![TEST_TAG_1, TEST_TAG_2]),
+        string_vec![
+            format!("br-{BR_REF_NAME}-{TEST_TAG_1}-40"),
+            format!("{COMMIT_SHA}-{TEST_TAG_1}-40"),
+            format!("br-{BR_REF_NAME}-{TEST_TAG_2}-40"),
+            format!("{COMMIT_SHA}-{TEST_TAG_2}-40"),
+        ],
+    )]
+    fn ci_tags(
+        #[case] on_default_branch: bool,
+        #[case] pr_number: Option<&str>,
+        #[case] alt_tags: Option<Vec<Cow<'_, str>>>,
+        #[case] mut expected: Vec<String>,
+    ) {
+        expected.sort();
+        let oci_ref: Reference = "ghcr.io/ublue-os/silverblue-main".parse().unwrap();
+
+        let mut tags = generate_ci_tags()
+            .opts(
+                &GenerateTagsOpts::builder()
+                    .oci_ref(&oci_ref)
+                    .maybe_alt_tags(alt_tags)
+                    .platform(Platform::LinuxAmd64)
+                    .build(),
+            )
+            .on_default_branch(on_default_branch)
+            .branch(BR_REF_NAME)
+            .commit_sha("1234567890")
+            .maybe_pr_number(pr_number)
+            .call()
+            .unwrap();
+        tags.sort();
+
+        assert_eq!(tags, expected);
+    }
+}
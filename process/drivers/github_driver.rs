@@ -1,11 +1,8 @@
 use std::path::PathBuf;
 
-use blue_build_utils::{
-    constants::{
-        GITHUB_EVENT_NAME, GITHUB_EVENT_PATH, GITHUB_REF_NAME, GITHUB_SHA, GITHUB_TOKEN_ISSUER_URL,
-        GITHUB_WORKFLOW_REF, PR_EVENT_NUMBER,
-    },
-    string_vec,
+use blue_build_utils::constants::{
+    GITHUB_EVENT_NAME, GITHUB_EVENT_PATH, GITHUB_REF_NAME, GITHUB_SHA, GITHUB_TOKEN_ISSUER_URL,
+    GITHUB_WORKFLOW_REF, PR_EVENT_NUMBER,
 };
 use event::Event;
 use log::trace;
@@ -16,9 +13,9 @@ use blue_build_utils::get_env_var;
 #[cfg(test)]
 use blue_build_utils::test_utils::get_env_var;
 
-use super::{opts::GenerateTagsOpts, CiDriver, Driver};
+use super::{functions::generate_ci_tags, opts::GenerateTagsOpts, CiDriver};
 
-mod event;
+pub(super) mod event;
 
 pub struct GithubDriver;
 
@@ -38,81 +35,21 @@ impl CiDriver for GithubDriver {
 
     fn generate_tags(opts: &GenerateTagsOpts) -> miette::Result<Vec<String>> {
         const PR_EVENT: &str = "pull_request";
-        let timestamp = blue_build_utils::get_tag_timestamp();
-        let os_version = Driver::get_os_version()
-            .oci_ref(opts.oci_ref)
-            .platform(opts.platform)
-            .call()
-            .inspect(|v| trace!("os_version={v}"))?;
         let ref_name = get_env_var(GITHUB_REF_NAME).inspect(|v| trace!("{GITHUB_REF_NAME}={v}"))?;
-        let short_sha = {
-            let mut short_sha = get_env_var(GITHUB_SHA).inspect(|v| trace!("{GITHUB_SHA}={v}"))?;
-            short_sha.truncate(7);
-            short_sha
-        };
-
-        let tags = match (
-            Self::on_default_branch(),
-            opts.alt_tags.as_ref(),
-            get_env_var(GITHUB_EVENT_NAME).inspect(|v| trace!("{GITHUB_EVENT_NAME}={v}")),
-            get_env_var(PR_EVENT_NUMBER).inspect(|v| trace!("{PR_EVENT_NUMBER}={v}")),
-        ) {
-            (true, None, _, _) => {
-                string_vec![
-                    "latest",
-                    &timestamp,
-                    format!("{os_version}"),
-                    format!("{timestamp}-{os_version}"),
-                    format!("{short_sha}-{os_version}"),
-                ]
-            }
-            (true, Some(alt_tags), _, _) => alt_tags
-                .iter()
-                .flat_map(|alt| {
-                    string_vec![
-                        &**alt,
-                        format!("{alt}-{os_version}"),
-                        format!("{timestamp}-{alt}-{os_version}"),
-                        format!("{short_sha}-{alt}-{os_version}"),
-                    ]
-                })
-                .collect(),
-            (false, None, Ok(event_name), Ok(event_num)) if event_name == PR_EVENT => {
-                vec![
-                    format!("pr-{event_num}-{os_version}"),
-                    format!("{short_sha}-{os_version}"),
-                ]
-            }
-            (false, None, _, _) => {
-                vec![
-                    format!("br-{ref_name}-{os_version}"),
-                    format!("{short_sha}-{os_version}"),
-                ]
-            }
-            (false, Some(alt_tags), Ok(event_name), Ok(event_num)) if event_name == PR_EVENT => {
-                alt_tags
-                    .iter()
-                    .flat_map(|alt| {
-                        vec![
-                            format!("pr-{event_num}-{alt}-{os_version}"),
-                            format!("{short_sha}-{alt}-{os_version}"),
-                        ]
-                    })
-                    .collect()
-            }
-            (false, Some(alt_tags), _, _) => alt_tags
-                .iter()
-                .flat_map(|alt| {
-                    vec![
-                        format!("br-{ref_name}-{alt}-{os_version}"),
-                        format!("{short_sha}-{alt}-{os_version}"),
-                    ]
-                })
-                .collect(),
-        };
-        trace!("{tags:?}");
-
-        Ok(tags)
+        let commit_sha = get_env_var(GITHUB_SHA).inspect(|v| trace!("{GITHUB_SHA}={v}"))?;
+        let pr_number = get_env_var(GITHUB_EVENT_NAME)
+            .inspect(|v| trace!("{GITHUB_EVENT_NAME}={v}"))
+            .is_ok_and(|event_name| event_name == PR_EVENT)
+            .then(|| get_env_var(PR_EVENT_NUMBER).inspect(|v| trace!("{PR_EVENT_NUMBER}={v}")))
+            .and_then(Result::ok);
+
+        generate_ci_tags()
+            .opts(opts)
+            .on_default_branch(Self::on_default_branch())
+            .branch(&ref_name)
+            .commit_sha(&commit_sha)
+            .maybe_pr_number(pr_number.as_deref())
+            .call()
     }
 
     fn get_repo_url() -> miette::Result<String> {
@@ -133,8 +70,8 @@ impl CiDriver for GithubDriver {
         .to_lowercase())
     }
 
-    fn default_ci_file_path() -> PathBuf {
-        PathBuf::from(".github/workflows/build.yml")
+    fn default_ci_file_path() -> miette::Result<PathBuf> {
+        Ok(PathBuf::from(".github/workflows/build.yml"))
     }
 }
 
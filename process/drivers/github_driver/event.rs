@@ -12,7 +12,7 @@ use blue_build_utils::test_utils::get_env_var;
 use blue_build_utils::get_env_var;
 
 #[derive(Debug, Deserialize, Clone)]
-pub(super) struct Event {
+pub(in crate::drivers) struct Event {
     pub repository: EventRepository,
     // pub base: Option<EventRefInfo>,
     pub head: Option<EventRefInfo>,
@@ -73,19 +73,19 @@ impl Event {
 // }
 
 #[derive(Debug, Deserialize, Clone)]
-pub(super) struct EventRepository {
+pub(in crate::drivers) struct EventRepository {
     pub default_branch: String,
     pub owner: EventRepositoryOwner,
     pub html_url: String,
 }
 
 #[derive(Debug, Deserialize, Clone)]
-pub(super) struct EventRepositoryOwner {
+pub(in crate::drivers) struct EventRepositoryOwner {
     pub login: String,
 }
 
 #[derive(Debug, Deserialize, Clone)]
-pub(super) struct EventRefInfo {
+pub(in crate::drivers) struct EventRefInfo {
     #[serde(alias = "ref")]
     pub commit_ref: String,
 }
@@ -143,8 +143,8 @@ impl CiDriver for GitlabDriver {
         .to_lowercase())
     }
 
-    fn default_ci_file_path() -> PathBuf {
-        PathBuf::from(".gitlab-ci.yml")
+    fn default_ci_file_path() -> miette::Result<PathBuf> {
+        Ok(PathBuf::from(".gitlab-ci.yml"))
     }
 }
 
@@ -2,6 +2,7 @@ use std::path::PathBuf;
 
 use blue_build_utils::{cmd, string_vec};
 use log::trace;
+use miette::miette;
 
 use super::{opts::GenerateTagsOpts, CiDriver, Driver};
 
@@ -75,8 +76,8 @@ impl CiDriver for LocalDriver {
         Ok(String::from("localhost"))
     }
 
-    fn default_ci_file_path() -> PathBuf {
-        unimplemented!()
+    fn default_ci_file_path() -> miette::Result<PathBuf> {
+        Err(miette!("There is no CI file for local builds"))
     }
 }
 
@@ -0,0 +1,308 @@
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use base64::prelude::*;
+use blue_build_utils::{constants::XDG_RUNTIME_DIR, credentials::Credentials};
+use cached::proc_macro::cached;
+use colored::Colorize;
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, trace};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use oci_distribution::{
+    client::ClientConfig,
+    manifest::{ImageIndexEntry, OciManifest},
+    secrets::RegistryAuth,
+    Client, Reference,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{drivers::types::Platform, logging::Logger, ASYNC_RUNTIME};
+
+use super::{opts::GetMetadataOpts, types::ImageMetadata, InspectDriver};
+
+/// Inspects images by talking to the registry directly,
+/// without needing a container tool installed.
+#[derive(Debug)]
+pub struct OciDriver;
+
+#[derive(Deserialize, Debug, Default)]
+struct ImageConfig {
+    #[serde(default)]
+    config: ContainerConfig,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct ContainerConfig {
+    #[serde(rename = "Labels", default)]
+    labels: Option<HashMap<String, Value>>,
+}
+
+/// A container auth file like `~/.docker/config.json`
+/// or `${XDG_RUNTIME_DIR}/containers/auth.json`.
+#[derive(Deserialize, Debug, Default)]
+struct AuthFile {
+    #[serde(default)]
+    auths: HashMap<String, AuthEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AuthEntry {
+    /// The base64 encoded `<username>:<password>`.
+    auth: Option<String>,
+}
+
+impl InspectDriver for OciDriver {
+    fn get_metadata(opts: &GetMetadataOpts) -> Result<ImageMetadata> {
+        get_metadata_cache(opts)
+    }
+}
+
+#[cached(
+    result = true,
+    key = "String",
+    convert = r#"{ format!("{}-{:?}-{}", &*opts.image, opts.tag.as_ref(), opts.platform)}"#,
+    sync_writes = true
+)]
+fn get_metadata_cache(opts: &GetMetadataOpts) -> Result<ImageMetadata> {
+    trace!("OciDriver::get_metadata({opts:#?})");
+
+    let url = opts.tag.as_ref().map_or_else(
+        || format!("{}", opts.image),
+        |tag| format!("{}:{tag}", opts.image),
+    );
+    let image: Reference = url
+        .parse()
+        .into_diagnostic()
+        .with_context(|| format!("Unable to parse image reference {url}"))?;
+
+    let progress = Logger::multi_progress().add(
+        ProgressBar::new_spinner()
+            .with_style(ProgressStyle::default_spinner())
+            .with_message(format!("Inspecting metadata for {}", url.bold())),
+    );
+    progress.enable_steady_tick(Duration::from_millis(100));
+
+    let result = ASYNC_RUNTIME.block_on(inspect(&image, opts.platform));
+
+    progress.finish_and_clear();
+    Logger::multi_progress().remove(&progress);
+
+    let metadata = result.with_context(|| format!("Failed to inspect image {url}"))?;
+    debug!("Successfully inspected image {url}!");
+    trace!("{metadata:#?}");
+
+    Ok(metadata)
+}
+
+async fn inspect(image: &Reference, platform: Platform) -> Result<ImageMetadata> {
+    let client = Client::new(ClientConfig {
+        platform_resolver: None,
+        ..Default::default()
+    });
+    let auth = registry_auth(image);
+
+    // The digest of the image index is kept when the image is
+    // built for multiple platforms so that it matches the
+    // digest reported by the other inspect drivers.
+    let (manifest, digest) = client.pull_manifest(image, &auth).await.into_diagnostic()?;
+
+    let manifest = match manifest {
+        OciManifest::Image(manifest) => manifest,
+        OciManifest::ImageIndex(index) => {
+            let platform_digest = select_platform(&index.manifests, platform)
+                .ok_or_else(|| miette!("No manifest found for platform {platform}"))?;
+            debug!("Selected manifest {platform_digest} for platform {platform}");
+
+            let platform_image = Reference::with_digest(
+                image.registry().to_string(),
+                image.repository().to_string(),
+                platform_digest.to_string(),
+            );
+            client
+                .pull_image_manifest(&platform_image, &auth)
+                .await
+                .into_diagnostic()?
+                .0
+        }
+    };
+
+    let mut config = Vec::new();
+    client
+        .pull_blob(image, &manifest.config, &mut config)
+        .await
+        .into_diagnostic()?;
+    let config: ImageConfig = serde_json::from_slice(&config).into_diagnostic()?;
+
+    Ok(ImageMetadata {
+        labels: config.config.labels.unwrap_or_default(),
+        digest,
+    })
+}
+
+/// Uses the credentials for the build if they are for the
+/// registry of the image, then the credentials in the
+/// container auth files that podman and docker log in with.
+fn registry_auth(image: &Reference) -> RegistryAuth {
+    Credentials::get()
+        .filter(|credentials| credentials.registry == image.registry())
+        .map(|credentials| {
+            RegistryAuth::Basic(credentials.username.clone(), credentials.password.clone())
+        })
+        .or_else(|| {
+            auth_files()
+                .iter()
+                .find_map(|auth_file| file_auth(auth_file, image))
+        })
+        .unwrap_or(RegistryAuth::Anonymous)
+}
+
+/// The container auth files in the order they're checked.
+fn auth_files() -> Vec<PathBuf> {
+    [
+        env::var(XDG_RUNTIME_DIR)
+            .ok()
+            .map(|dir| Path::new(&dir).join("containers/auth.json")),
+        blue_build_utils::home_dir().map(|home| home.join(".docker/config.json")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Gets the credentials for an image from an auth file.
+///
+/// An entry can be for a whole registry or for a namespace
+/// in it, in which case the most specific entry is used.
+fn file_auth(auth_file: &Path, image: &Reference) -> Option<RegistryAuth> {
+    let contents = std::fs::read_to_string(auth_file).ok()?;
+    let auth_file: AuthFile = serde_json::from_str(&contents)
+        .inspect_err(|e| debug!("Unable to parse {}: {e}", auth_file.display()))
+        .ok()?;
+    let image_path = format!("{}/{}", image.registry(), image.repository());
+
+    let auth = auth_file
+        .auths
+        .iter()
+        .filter(|(key, _)| {
+            let key = auth_key(key);
+            image_path == key || image_path.starts_with(&format!("{key}/"))
+        })
+        .max_by_key(|(key, _)| auth_key(key).len())?
+        .1
+        .auth
+        .as_ref()?;
+    let auth = String::from_utf8(BASE64_STANDARD.decode(auth).ok()?).ok()?;
+    let (username, password) = auth.split_once(':')?;
+
+    Some(RegistryAuth::Basic(username.into(), password.into()))
+}
+
+/// Removes the scheme and API path from an auth file
+/// key, which docker adds for Docker Hub.
+fn auth_key(key: &str) -> &str {
+    let key = key
+        .trim_start_matches("https://")
+        .trim_start_matches("http://");
+    let key = key.strip_suffix("/v1/").unwrap_or(key);
+
+    match key {
+        "index.docker.io" | "registry-1.docker.io" => "docker.io",
+        key => key,
+    }
+}
+
+fn select_platform(manifests: &[ImageIndexEntry], platform: Platform) -> Option<&str> {
+    manifests
+        .iter()
+        .find(|entry| {
+            entry.platform.as_ref().is_some_and(|entry_platform| {
+                entry_platform.os == "linux" && entry_platform.architecture == platform.arch()
+            })
+        })
+        .map(|entry| entry.digest.as_str())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use base64::prelude::*;
+    use oci_distribution::{manifest::ImageIndexEntry, secrets::RegistryAuth, Reference};
+    use rstest::rstest;
+    use tempfile::NamedTempFile;
+
+    use crate::drivers::types::Platform;
+
+    use super::{file_auth, select_platform};
+
+    fn entry(os: &str, architecture: &str, digest: &str) -> ImageIndexEntry {
+        serde_json::from_value(serde_json::json!({
+            "mediaType": "application/vnd.oci.image.manifest.v1+json",
+            "digest": digest,
+            "size": 1024,
+            "platform": { "os": os, "architecture": architecture },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn platform_selection() {
+        let manifests = [
+            entry("linux", "amd64", "sha256:amd64"),
+            entry("windows", "arm64", "sha256:windows"),
+            entry("linux", "arm64", "sha256:arm64"),
+        ];
+
+        assert_eq!(
+            select_platform(&manifests, Platform::LinuxAmd64),
+            Some("sha256:amd64")
+        );
+        assert_eq!(
+            select_platform(&manifests, Platform::LinuxArm64),
+            Some("sha256:arm64")
+        );
+        assert_eq!(
+            select_platform(&manifests[1..2], Platform::LinuxArm64),
+            None
+        );
+    }
+
+    #[rstest]
+    #[case("ghcr.io/octocat/test:latest", Some(("octocat", "registry-password")))]
+    #[case("quay.io/fedora/fedora:40", Some(("fedora", "namespace-password")))]
+    #[case("quay.io/centos/centos:stream9", Some(("quay", "quay-password")))]
+    #[case("fedora:40", Some(("hub", "hub-password")))]
+    #[case("registry.gitlab.com/octocat/test:latest", None)]
+    fn auth_file_credentials(#[case] image: &str, #[case] expected: Option<(&str, &str)>) {
+        let auth =
+            |credentials: &str| serde_json::json!({ "auth": BASE64_STANDARD.encode(credentials) });
+        let mut auth_file = NamedTempFile::new().unwrap();
+        write!(
+            auth_file,
+            "{}",
+            serde_json::json!({
+                "auths": {
+                    "ghcr.io": auth("octocat:registry-password"),
+                    "quay.io": auth("quay:quay-password"),
+                    "quay.io/fedora": auth("fedora:namespace-password"),
+                    "https://index.docker.io/v1/": auth("hub:hub-password"),
+                    "registry.gitlab.com": {},
+                }
+            })
+        )
+        .unwrap();
+
+        let image: Reference = image.parse().unwrap();
+        assert_eq!(
+            file_auth(auth_file.path(), &image),
+            expected.map(|(username, password)| {
+                RegistryAuth::Basic(username.into(), password.into())
+            })
+        );
+    }
+}
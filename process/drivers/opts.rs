@@ -6,6 +6,8 @@ pub use inspect::*;
 #[cfg(feature = "rechunk")]
 pub use rechunk::*;
 pub use run::*;
+#[cfg(feature = "sbom")]
+pub use sbom::*;
 pub use signing::*;
 
 mod build;
@@ -14,6 +16,8 @@ mod inspect;
 #[cfg(feature = "rechunk")]
 mod rechunk;
 mod run;
+#[cfg(feature = "sbom")]
+mod sbom;
 mod signing;
 
 #[derive(Debug, Copy, Clone, Default, ValueEnum)]
@@ -2,7 +2,7 @@ use std::{borrow::Cow, path::Path};
 
 use bon::Builder;
 
-use crate::drivers::types::Platform;
+use crate::drivers::types::{BuildSecret, CacheTarget, Platform};
 
 use super::CompressionType;
 
@@ -23,6 +23,16 @@ pub struct BuildOpts<'scope> {
 
     #[builder(default)]
     pub host_network: bool,
+
+    /// The location to import the build cache from.
+    pub cache_from: Option<&'scope CacheTarget>,
+
+    /// The location to export the build cache to.
+    pub cache_to: Option<&'scope CacheTarget>,
+
+    /// The secrets that modules can mount during the build.
+    #[builder(default)]
+    pub secrets: &'scope [BuildSecret],
 }
 
 #[derive(Debug, Clone, Builder)]
@@ -39,6 +49,20 @@ pub struct PushOpts<'scope> {
     pub compression_type: Option<CompressionType>,
 }
 
+/// Options for creating and pushing a manifest list.
+#[derive(Debug, Clone, Builder)]
+pub struct ManifestPushOpts<'scope> {
+    /// The full image ref, including the tag, to push the manifest list to.
+    #[builder(into)]
+    pub image: Cow<'scope, str>,
+
+    /// The per-platform images that make up the manifest list.
+    ///
+    /// These images must already be pushed to the registry.
+    #[builder(into)]
+    pub images: Vec<Cow<'scope, str>>,
+}
+
 #[derive(Debug, Clone, Builder)]
 #[cfg(feature = "prune")]
 pub struct PruneOpts {
@@ -97,4 +121,14 @@ pub struct BuildTagPushOpts<'scope> {
     /// The platform to build the image on.
     #[builder(default)]
     pub platform: Platform,
+
+    /// The location to import the build cache from.
+    pub cache_from: Option<&'scope CacheTarget>,
+
+    /// The location to export the build cache to.
+    pub cache_to: Option<&'scope CacheTarget>,
+
+    /// The secrets that modules can mount during the build.
+    #[builder(default)]
+    pub secrets: &'scope [BuildSecret],
 }
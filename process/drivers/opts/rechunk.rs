@@ -2,7 +2,7 @@ use std::{borrow::Cow, path::Path};
 
 use bon::Builder;
 
-use crate::drivers::types::Platform;
+use crate::drivers::types::{BuildSecret, Platform};
 
 use super::CompressionType;
 
@@ -23,6 +23,11 @@ pub struct RechunkOpts<'scope> {
     pub base_image: Cow<'scope, str>,
     pub repo: Cow<'scope, str>,
 
+    /// The `<image>@<digest>` references of the images
+    /// that the image is checked against for updates.
+    #[builder(default, into)]
+    pub image_digests: Vec<Cow<'scope, str>>,
+
     /// The list of tags for the image being built.
     #[builder(default, into)]
     pub tags: Vec<Cow<'scope, str>>,
@@ -45,4 +50,8 @@ pub struct RechunkOpts<'scope> {
     #[builder(default)]
     pub compression: CompressionType,
     pub tempdir: Option<&'scope Path>,
+
+    /// The secrets that modules can mount during the build.
+    #[builder(default)]
+    pub secrets: &'scope [BuildSecret],
 }
@@ -0,0 +1,30 @@
+use std::{borrow::Cow, path::Path};
+
+use bon::Builder;
+
+use crate::drivers::types::{Platform, SbomFormat};
+
+/// The image to generate an SBOM for.
+#[derive(Debug, Clone)]
+pub enum SbomSource<'scope> {
+    /// An image in a registry, either by tag or digest.
+    Registry(Cow<'scope, str>),
+
+    /// An OCI archive on disk.
+    Archive(Cow<'scope, Path>),
+}
+
+#[derive(Debug, Clone, Builder)]
+pub struct GenerateSbomOpts<'scope> {
+    pub source: SbomSource<'scope>,
+
+    /// The file to write the SBOM to.
+    #[builder(into)]
+    pub output: Cow<'scope, Path>,
+
+    #[builder(default)]
+    pub format: SbomFormat,
+
+    #[builder(default)]
+    pub platform: Platform,
+}
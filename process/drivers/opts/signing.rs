@@ -78,6 +78,27 @@ pub struct SignOpts<'scope> {
     pub dir: Option<Cow<'scope, Path>>,
 }
 
+#[derive(Debug, Clone, Builder)]
+pub struct AttestOpts<'scope> {
+    /// The image to attest, referenced by digest.
+    #[builder(into)]
+    pub image: Cow<'scope, str>,
+
+    /// The file containing the predicate of the attestation.
+    #[builder(into)]
+    pub predicate: Cow<'scope, Path>,
+
+    /// The URI of the predicate's type.
+    #[builder(into)]
+    pub predicate_type: Cow<'scope, str>,
+
+    /// The directory containing the signing keys.
+    ///
+    /// Keyless signing is used when there is no private key.
+    #[builder(into)]
+    pub dir: Option<Cow<'scope, Path>>,
+}
+
 #[derive(Debug, Clone)]
 pub enum VerifyType<'scope> {
     File(Cow<'scope, Path>),
@@ -94,6 +115,18 @@ pub struct VerifyOpts<'scope> {
     pub verify_type: VerifyType<'scope>,
 }
 
+#[derive(Debug, Clone, Builder)]
+pub struct VerifyAttestationOpts<'scope> {
+    /// The image to verify the attestation of.
+    #[builder(into)]
+    pub image: Cow<'scope, str>,
+
+    /// The URI of the predicate's type.
+    #[builder(into)]
+    pub predicate_type: Cow<'scope, str>,
+    pub verify_type: VerifyType<'scope>,
+}
+
 #[derive(Debug, Clone, Builder)]
 pub struct SignVerifyOpts<'scope> {
     #[builder(into)]
@@ -117,4 +150,15 @@ pub struct SignVerifyOpts<'scope> {
 
     #[builder(default)]
     pub platform: Platform,
+
+    /// The digest of the manifest list pushed to `tag`.
+    ///
+    /// When set, the manifest list is signed along with
+    /// the per-platform image for each of `platforms`.
+    #[builder(into)]
+    pub manifest_digest: Option<Cow<'scope, str>>,
+
+    /// The platforms that make up the manifest list.
+    #[builder(default, into)]
+    pub platforms: Vec<Platform>,
 }
@@ -1,5 +1,6 @@
 use std::{
     collections::HashMap,
+    fs,
     io::Write,
     path::Path,
     process::{Command, ExitStatus, Stdio},
@@ -10,27 +11,37 @@ use blue_build_utils::{cmd, credentials::Credentials};
 use cached::proc_macro::cached;
 use colored::Colorize;
 use indicatif::{ProgressBar, ProgressStyle};
-use log::{debug, error, info, trace};
+use log::{debug, error, info, trace, warn};
 use miette::{bail, miette, IntoDiagnostic, Report, Result};
 use oci_distribution::Reference;
 use semver::Version;
 use serde::Deserialize;
-use tempfile::TempDir;
+use tempfile::{NamedTempFile, TempDir};
+use uuid::Uuid;
 
 use crate::{
     drivers::{
-        opts::{BuildOpts, GetMetadataOpts, PushOpts, RunOpts, RunOptsEnv, RunOptsVolume, TagOpts},
+        functions::registry_cache,
+        opts::{
+            BuildOpts, GetMetadataOpts, ManifestPushOpts, PushOpts, RunOpts, RunOptsEnv,
+            RunOptsVolume, TagOpts,
+        },
         types::{ImageMetadata, Platform},
         BuildDriver, DriverVersion, InspectDriver, RunDriver,
     },
+    dry_run::{is_dry_run, DryRunCommand, DRY_RUN_DIGEST},
     logging::{CommandLogging, Logger},
     signal_handler::{add_cid, remove_cid, ContainerRuntime, ContainerSignalId},
 };
 
+#[cfg(feature = "diff")]
+use super::ImageMountDriver;
 #[cfg(feature = "rechunk")]
+use super::RechunkDriver;
+#[cfg(any(feature = "rechunk", feature = "diff"))]
 use super::{
     types::{ContainerId, MountId},
-    ContainerMountDriver, RechunkDriver,
+    ContainerMountDriver,
 };
 
 #[derive(Deserialize, Debug, Clone)]
@@ -87,10 +98,18 @@ impl TryFrom<Vec<PodmanImageMetadata>> for ImageMetadata {
 }
 
 fn verify_image(repo_digest: &str) -> bool {
-    let mut command = cmd!("podman", "pull", repo_digest);
+    let mut command = cmd!(
+        "podman",
+        "pull",
+        repo_digest,
+        stdout = Stdio::null(),
+        stderr = Stdio::null(),
+    );
     trace!("{command:?}");
 
-    command.output().is_ok_and(|out| out.status.success())
+    command
+        .dry_run_status()
+        .is_ok_and(|status| status.success())
 }
 
 #[derive(Debug, Deserialize)]
@@ -134,6 +153,19 @@ impl BuildDriver for PodmanDriver {
     fn build(opts: &BuildOpts) -> Result<()> {
         trace!("PodmanDriver::build({opts:#?})");
 
+        // Caches are only used when building with layers
+        let (cache_from, cache_to) = if opts.squash {
+            if opts.cache_from.is_some() || opts.cache_to.is_some() {
+                warn!("Build caches are not used when squashing");
+            }
+            (None, None)
+        } else {
+            (
+                registry_cache("podman", opts.cache_from),
+                registry_cache("podman", opts.cache_to),
+            )
+        };
+
         let command = cmd!(
             "podman",
             "build",
@@ -144,6 +176,9 @@ impl BuildDriver for PodmanDriver {
             "--pull=true",
             if opts.host_network => "--net=host",
             format!("--layers={}", !opts.squash),
+            if let Some(repo) = cache_from => format!("--cache-from={repo}"),
+            if let Some(repo) = cache_to => format!("--cache-to={repo}"),
+            for secret in opts.secrets.iter() => format!("--secret={secret}"),
             "-f",
             &*opts.containerfile,
             "-t",
@@ -170,7 +205,7 @@ impl BuildDriver for PodmanDriver {
         let mut command = cmd!("podman", "tag", &*opts.src_image, &*opts.dest_image,);
 
         trace!("{command:?}");
-        let status = command.status().into_diagnostic()?;
+        let status = command.dry_run_status().into_diagnostic()?;
 
         if status.success() {
             info!("Successfully tagged {}!", opts.dest_image);
@@ -206,6 +241,28 @@ impl BuildDriver for PodmanDriver {
         Ok(())
     }
 
+    fn manifest_push(opts: &ManifestPushOpts) -> Result<String> {
+        trace!("PodmanDriver::manifest_push({opts:#?})");
+
+        let manifest = format!("localhost/bluebuild-manifest-{}", Uuid::new_v4());
+
+        let mut command = cmd!("podman", "manifest", "create", &manifest);
+        trace!("{command:?}");
+        if !command.dry_run_status().into_diagnostic()?.success() {
+            bail!("Failed to create manifest list for {}", opts.image);
+        }
+
+        let result = podman_manifest_add_push(&manifest, opts);
+
+        let mut command = cmd!("podman", "manifest", "rm", &manifest);
+        trace!("{command:?}");
+        if !command.dry_run_output().into_diagnostic()?.status.success() {
+            warn!("Failed to remove local manifest list {manifest}");
+        }
+
+        result
+    }
+
     fn login() -> Result<()> {
         trace!("PodmanDriver::login()");
 
@@ -229,6 +286,9 @@ impl BuildDriver for PodmanDriver {
                 .stderr(Stdio::piped());
 
             trace!("{command:?}");
+            if command.print_if_dry_run() {
+                return Ok(());
+            }
             let mut child = command.spawn().into_diagnostic()?;
 
             write!(
@@ -341,7 +401,7 @@ fn get_metadata_cache(opts: &GetMetadataOpts) -> Result<ImageMetadata> {
         .inspect(|metadata| trace!("{metadata:#?}"))
 }
 
-#[cfg(feature = "rechunk")]
+#[cfg(any(feature = "rechunk", feature = "diff"))]
 impl ContainerMountDriver for PodmanDriver {
     fn create_container(image: &Reference) -> Result<ContainerId> {
         let output = {
@@ -444,6 +504,72 @@ impl ContainerMountDriver for PodmanDriver {
     }
 }
 
+#[cfg(feature = "diff")]
+impl ImageMountDriver for PodmanDriver {
+    fn load_archive(archive: &Path, image: &Reference) -> Result<()> {
+        let output = {
+            let c = cmd!(
+                "podman",
+                "pull",
+                "-q",
+                format!("oci-archive:{}", archive.display())
+            );
+            trace!("{c:?}");
+            c
+        }
+        .output()
+        .into_diagnostic()?;
+
+        if !output.status.success() {
+            bail!("Failed to load the archive {}", archive.display());
+        }
+
+        let image_id = String::from_utf8(output.stdout).into_diagnostic()?;
+        let Some(image_id) = image_id.lines().last() else {
+            bail!("No image was loaded from {}", archive.display());
+        };
+
+        let status = {
+            let c = cmd!("podman", "tag", image_id, image.to_string());
+            trace!("{c:?}");
+            c
+        }
+        .status()
+        .into_diagnostic()?;
+
+        if !status.success() {
+            bail!("Failed to tag the image {image_id} as {image}");
+        }
+
+        Ok(())
+    }
+
+    fn image_labels(image: &Reference) -> Result<HashMap<String, serde_json::Value>> {
+        let output = {
+            let c = cmd!(
+                "podman",
+                "image",
+                "inspect",
+                "--format",
+                "{{json .Labels}}",
+                image.to_string()
+            );
+            trace!("{c:?}");
+            c
+        }
+        .output()
+        .into_diagnostic()?;
+
+        if !output.status.success() {
+            bail!("Failed to inspect the image {image}");
+        }
+
+        serde_json::from_slice::<Option<HashMap<String, serde_json::Value>>>(&output.stdout)
+            .into_diagnostic()
+            .map(Option::unwrap_or_default)
+    }
+}
+
 #[cfg(feature = "rechunk")]
 impl RechunkDriver for PodmanDriver {}
 
@@ -493,6 +619,65 @@ impl RunDriver for PodmanDriver {
     }
 }
 
+fn podman_manifest_add_push(manifest: &str, opts: &ManifestPushOpts) -> Result<String> {
+    for image in &opts.images {
+        let mut command = podman_manifest_add(manifest, image);
+        trace!("{command:?}");
+
+        if !command.dry_run_status().into_diagnostic()?.success() {
+            bail!("Failed to add {image} to manifest list for {}", opts.image);
+        }
+    }
+
+    let digest_file = NamedTempFile::new().into_diagnostic()?;
+
+    let command = podman_manifest_push(manifest, &opts.image, digest_file.path());
+    trace!("{command:?}");
+
+    let status = command
+        .build_status(&opts.image, "Pushing Manifest")
+        .into_diagnostic()?;
+
+    if !status.success() {
+        bail!("Failed to push manifest list {}", opts.image);
+    }
+    info!("Successfully pushed manifest list {}!", opts.image);
+
+    if is_dry_run() {
+        return Ok(DRY_RUN_DIGEST.into());
+    }
+
+    Ok(fs::read_to_string(digest_file.path())
+        .into_diagnostic()?
+        .trim()
+        .to_string())
+}
+
+fn podman_manifest_add(manifest: &str, image: &str) -> Command {
+    cmd!(
+        "podman",
+        "manifest",
+        "add",
+        manifest,
+        format!("docker://{image}"),
+    )
+}
+
+fn podman_manifest_push(manifest: &str, image: &str, digest_file: &Path) -> Command {
+    // The per-platform images have already been pushed,
+    // so only the manifest list itself needs to be pushed.
+    cmd!(
+        "podman",
+        "manifest",
+        "push",
+        "--all=false",
+        "--digestfile",
+        digest_file,
+        manifest,
+        format!("docker://{image}"),
+    )
+}
+
 fn podman_run(opts: &RunOpts, cid_file: &Path) -> Command {
     let command = cmd!(
         "podman",
@@ -520,3 +705,46 @@ fn podman_run(opts: &RunOpts, cid_file: &Path) -> Command {
 
     command
 }
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{podman_manifest_add, podman_manifest_push};
+
+    #[test]
+    fn manifest_commands() {
+        let manifest = "localhost/bluebuild-manifest-test";
+
+        let command = podman_manifest_add(manifest, "ghcr.io/octocat/test:latest-amd64");
+        assert_eq!(command.get_program(), "podman");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            [
+                "manifest",
+                "add",
+                manifest,
+                "docker://ghcr.io/octocat/test:latest-amd64",
+            ]
+        );
+
+        let command = podman_manifest_push(
+            manifest,
+            "ghcr.io/octocat/test:latest",
+            Path::new("/tmp/digest"),
+        );
+        assert_eq!(command.get_program(), "podman");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            [
+                "manifest",
+                "push",
+                "--all=false",
+                "--digestfile",
+                "/tmp/digest",
+                manifest,
+                "docker://ghcr.io/octocat/test:latest",
+            ]
+        );
+    }
+}
@@ -1,15 +1,20 @@
-use std::{fs, path::Path};
+use std::{collections::HashMap, fs, path::Path};
 
 use crate::{
     drivers::opts::{PrivateKeyContents, VerifyType},
+    dry_run::{is_dry_run, print_action},
     ASYNC_RUNTIME,
 };
 
 use super::{
     functions::get_private_key,
-    opts::{CheckKeyPairOpts, GenerateKeyPairOpts, SignOpts, VerifyOpts},
-    SigningDriver,
+    opts::{
+        AttestOpts, CheckKeyPairOpts, GenerateKeyPairOpts, SignOpts, VerifyAttestationOpts,
+        VerifyOpts,
+    },
+    SigningDriver, VERIFICATION_FAILED,
 };
+use base64::prelude::*;
 use blue_build_utils::{
     constants::{COSIGN_PRIV_PATH, COSIGN_PUB_PATH},
     credentials::Credentials,
@@ -17,18 +22,29 @@ use blue_build_utils::{
 };
 use log::{debug, trace};
 use miette::{bail, miette, Context, IntoDiagnostic};
+use oci_distribution::{
+    client::{ClientConfig, Config, ImageLayer},
+    errors::{OciDistributionError, OciErrorCode},
+    secrets::RegistryAuth,
+    Client, Reference,
+};
+use serde_json::{json, Value};
 use sigstore::{
     cosign::{
         constraint::PrivateKeySigner,
         verification_constraint::{PublicKeyVerifier, VerificationConstraintVec},
         ClientBuilder, Constraint, CosignCapabilities, SignatureLayer,
     },
-    crypto::{signing_key::SigStoreKeyPair, SigningScheme},
+    crypto::{signing_key::SigStoreKeyPair, CosignVerificationKey, Signature, SigningScheme},
     errors::SigstoreVerifyConstraintsError,
     registry::{Auth, OciReference},
 };
 use zeroize::Zeroizing;
 
+const IN_TOTO_PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+const DSSE_MEDIA_TYPE: &str = "application/vnd.dsse.envelope.v1+json";
+const PREDICATE_TYPE_ANNOTATION: &str = "predicateType";
+
 pub struct SigstoreDriver;
 
 impl SigningDriver for SigstoreDriver {
@@ -107,6 +123,11 @@ impl SigningDriver for SigstoreDriver {
     fn sign(opts: &SignOpts) -> miette::Result<()> {
         trace!("SigstoreDriver::sign({opts:?})");
 
+        if is_dry_run() {
+            print_action(format!("sigstore sign {}", opts.image));
+            return Ok(());
+        }
+
         let path = opts.dir.as_ref().map_or_else(|| Path::new("."), |dir| dir);
         let mut client = ClientBuilder::default().build().into_diagnostic()?;
 
@@ -172,6 +193,11 @@ impl SigningDriver for SigstoreDriver {
     }
 
     fn verify(opts: &VerifyOpts) -> miette::Result<()> {
+        if is_dry_run() {
+            print_action(format!("sigstore verify {}", opts.image));
+            return Ok(());
+        }
+
         let mut client = ClientBuilder::default().build().into_diagnostic()?;
 
         let image_digest: &str = opts.image.as_ref();
@@ -183,7 +209,7 @@ impl SigningDriver for SigstoreDriver {
         let pub_key = fs::read_to_string(match &opts.verify_type {
             VerifyType::File(path) => path,
             VerifyType::Keyless { .. } => {
-                todo!("Keyless currently not supported for sigstore driver")
+                bail!("Keyless verification is currently not supported for sigstore driver");
             }
         })
         .into_diagnostic()
@@ -220,16 +246,281 @@ impl SigningDriver for SigstoreDriver {
                 |SigstoreVerifyConstraintsError {
                      unsatisfied_constraints,
                  }| {
-                    miette!("Failed to verify for constraints: {unsatisfied_constraints:?}")
+                    miette!(
+                        code = VERIFICATION_FAILED,
+                        "Failed to verify for constraints: {unsatisfied_constraints:?}"
+                    )
                 },
             )
     }
 
+    fn attest(opts: &AttestOpts) -> miette::Result<()> {
+        trace!("SigstoreDriver::attest({opts:?})");
+
+        if is_dry_run() {
+            print_action(format!(
+                "sigstore attest --type {} {}",
+                opts.predicate_type, opts.image
+            ));
+            return Ok(());
+        }
+
+        let path = opts.dir.as_ref().map_or_else(|| Path::new("."), |dir| dir);
+        let image: Reference = opts.image.parse().into_diagnostic()?;
+        let (algorithm, digest) = image
+            .digest()
+            .and_then(|digest| digest.split_once(':'))
+            .ok_or_else(|| miette!("The image {image} must be referenced by digest"))?;
+
+        let key: Zeroizing<Vec<u8>> = get_private_key(path)
+            .context("Keyless attestations are currently not supported for sigstore driver")?
+            .contents()?;
+        let signer = SigStoreKeyPair::from_encrypted_pem(&key, b"")
+            .into_diagnostic()?
+            .to_sigstore_signer(&SigningScheme::default())
+            .into_diagnostic()?;
+        debug!("Created signer");
+
+        let predicate: Value =
+            serde_json::from_slice(&fs::read(&opts.predicate).into_diagnostic()?)
+                .into_diagnostic()
+                .with_context(|| {
+                    format!("Failed to parse predicate {}", opts.predicate.display())
+                })?;
+        let payload = serde_json::to_vec(&in_toto_statement(
+            &image,
+            &opts.predicate_type,
+            &predicate,
+        )?)
+        .into_diagnostic()?;
+        let signature = signer
+            .sign(&pae(IN_TOTO_PAYLOAD_TYPE, &payload))
+            .into_diagnostic()?;
+        let envelope = json!({
+            "payloadType": IN_TOTO_PAYLOAD_TYPE,
+            "payload": BASE64_STANDARD.encode(&payload),
+            "signatures": [{ "keyid": "", "sig": BASE64_STANDARD.encode(signature) }],
+        });
+        debug!("Signed attestation");
+
+        let Credentials {
+            registry: _,
+            username,
+            password,
+        } = Credentials::get().ok_or_else(|| miette!("Credentials are required for attesting"))?;
+        let auth = RegistryAuth::Basic(username.clone(), password.clone());
+        let client = Client::new(ClientConfig::default());
+
+        // Cosign stores all attestations for an image
+        // as layers of the `sha256-<digest>.att` tag.
+        let attestation_image = Reference::with_tag(
+            image.registry().to_string(),
+            image.repository().to_string(),
+            format!("{algorithm}-{digest}.att"),
+        );
+        let mut layers = match ASYNC_RUNTIME.block_on(client.pull(
+            &attestation_image,
+            &auth,
+            vec![DSSE_MEDIA_TYPE],
+        )) {
+            Ok(image_data) => image_data.layers,
+            Err(e) if is_manifest_unknown(&e) => {
+                debug!("No attestations exist for {image} yet");
+                Vec::new()
+            }
+            Err(e) => {
+                return Err(e).into_diagnostic().with_context(|| {
+                    format!("Failed to pull the existing attestations {attestation_image}")
+                });
+            }
+        };
+        layers.push(ImageLayer::new(
+            serde_json::to_vec(&envelope).into_diagnostic()?,
+            DSSE_MEDIA_TYPE.to_string(),
+            Some(HashMap::from([(
+                PREDICATE_TYPE_ANNOTATION.to_string(),
+                opts.predicate_type.to_string(),
+            )])),
+        ));
+
+        debug!("Pushing attestation {attestation_image}");
+        retry(2, 5, || {
+            ASYNC_RUNTIME
+                .block_on(client.push(
+                    &attestation_image,
+                    &layers,
+                    Config::oci_v1(b"{}".to_vec(), None),
+                    &auth,
+                    None,
+                ))
+                .into_diagnostic()
+                .with_context(|| {
+                    format!("Failed to push attestation {attestation_image} for image {image}")
+                })
+        })?;
+        debug!("Successfully pushed attestation");
+
+        Ok(())
+    }
+
+    fn verify_attestation(opts: &VerifyAttestationOpts) -> miette::Result<()> {
+        trace!("SigstoreDriver::verify_attestation({opts:?})");
+
+        if is_dry_run() {
+            print_action(format!(
+                "sigstore verify-attestation --type {} {}",
+                opts.predicate_type, opts.image
+            ));
+            return Ok(());
+        }
+
+        let pub_key = fs::read(match &opts.verify_type {
+            VerifyType::File(path) => path,
+            VerifyType::Keyless { .. } => {
+                bail!("Keyless attestations are currently not supported for sigstore driver");
+            }
+        })
+        .into_diagnostic()
+        .with_context(|| format!("Failed to open public key file {COSIGN_PUB_PATH}"))?;
+        let verification_key = CosignVerificationKey::from_pem(&pub_key, &SigningScheme::default())
+            .into_diagnostic()?;
+
+        let image: Reference = opts.image.parse().into_diagnostic()?;
+        let client = Client::new(ClientConfig::default());
+        let auth = Credentials::get().map_or(RegistryAuth::Anonymous, |credentials| {
+            RegistryAuth::Basic(credentials.username.clone(), credentials.password.clone())
+        });
+
+        let digest = match image.digest() {
+            Some(digest) => digest.to_string(),
+            None => ASYNC_RUNTIME
+                .block_on(client.fetch_manifest_digest(&image, &auth))
+                .into_diagnostic()
+                .with_context(|| format!("Failed to get the digest of {image}"))?,
+        };
+        let attestation_image = Reference::with_tag(
+            image.registry().to_string(),
+            image.repository().to_string(),
+            digest.replacen(':', "-", 1) + ".att",
+        );
+        debug!("Pulling attestations {attestation_image}");
+
+        let layers = ASYNC_RUNTIME
+            .block_on(client.pull(&attestation_image, &auth, vec![DSSE_MEDIA_TYPE]))
+            .into_diagnostic()
+            .with_context(|| format!("Failed to pull attestations for {image}"))?
+            .layers;
+
+        let verified = layers
+            .iter()
+            .filter(|layer| {
+                layer
+                    .annotations
+                    .as_ref()
+                    .and_then(|annotations| annotations.get(PREDICATE_TYPE_ANNOTATION))
+                    .is_some_and(|predicate_type| *predicate_type == opts.predicate_type)
+            })
+            .any(|layer| {
+                verify_envelope(&verification_key, &layer.data)
+                    .inspect_err(|e| debug!("Failed to verify attestation: {e:?}"))
+                    .is_ok()
+            });
+
+        if !verified {
+            bail!(
+                code = VERIFICATION_FAILED,
+                "No {} attestation for {image} could be verified",
+                opts.predicate_type
+            );
+        }
+
+        debug!("Verified {} attestation", opts.predicate_type);
+        Ok(())
+    }
+
     fn signing_login() -> miette::Result<()> {
         Ok(())
     }
 }
 
+/// Verifies one of the signatures of a DSSE envelope.
+/// Whether pulling an image failed because it doesn't exist.
+///
+/// Any other error, like failing to authenticate, means the
+/// image might exist and so must not be treated as empty.
+fn is_manifest_unknown(error: &OciDistributionError) -> bool {
+    matches!(
+        error,
+        OciDistributionError::RegistryError { envelope, .. }
+            if envelope
+                .errors
+                .iter()
+                .any(|e| e.code == OciErrorCode::ManifestUnknown)
+    )
+}
+
+fn verify_envelope(key: &CosignVerificationKey, envelope: &[u8]) -> miette::Result<()> {
+    let envelope: Value = serde_json::from_slice(envelope).into_diagnostic()?;
+    let payload_type = envelope["payloadType"]
+        .as_str()
+        .ok_or_else(|| miette!("Envelope is missing a payload type"))?;
+    let payload = BASE64_STANDARD
+        .decode(
+            envelope["payload"]
+                .as_str()
+                .ok_or_else(|| miette!("Envelope is missing a payload"))?,
+        )
+        .into_diagnostic()?;
+    let message = pae(payload_type, &payload);
+
+    envelope["signatures"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|signature| signature["sig"].as_str())
+        .find(|signature| {
+            key.verify_signature(Signature::Base64Encoded(signature.as_bytes()), &message)
+                .is_ok()
+        })
+        .map(|_| ())
+        .ok_or_else(|| miette!("No signature in the envelope matches the public key"))
+}
+
+/// Creates an in-toto statement for the predicate with the image as its subject.
+fn in_toto_statement(
+    image: &Reference,
+    predicate_type: &str,
+    predicate: &Value,
+) -> miette::Result<Value> {
+    let (algorithm, digest) = image
+        .digest()
+        .and_then(|digest| digest.split_once(':'))
+        .ok_or_else(|| miette!("The image {image} must be referenced by digest"))?;
+
+    Ok(json!({
+        "_type": "https://in-toto.io/Statement/v0.1",
+        "predicateType": predicate_type,
+        "subject": [{
+            "name": format!("{}/{}", image.resolve_registry(), image.repository()),
+            "digest": { algorithm: digest },
+        }],
+        "predicate": predicate,
+    }))
+}
+
+/// The DSSE pre-authentication encoding of a payload
+/// which is what gets signed in an envelope.
+fn pae(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut encoded = format!(
+        "DSSEv1 {} {payload_type} {} ",
+        payload_type.len(),
+        payload.len()
+    )
+    .into_bytes();
+    encoded.extend_from_slice(payload);
+    encoded
+}
+
 #[cfg(test)]
 mod test {
     use std::{fs, path::Path};
@@ -243,7 +534,94 @@ mod test {
         SigningDriver,
     };
 
-    use super::SigstoreDriver;
+    use super::{in_toto_statement, is_manifest_unknown, pae, verify_envelope, SigstoreDriver};
+
+    #[test]
+    fn manifest_unknown() {
+        use oci_distribution::errors::{OciDistributionError, OciEnvelope};
+
+        let registry_error = |code: &str| OciDistributionError::RegistryError {
+            envelope: serde_json::from_value::<OciEnvelope>(serde_json::json!({
+                "errors": [{ "code": code, "message": "" }],
+            }))
+            .unwrap(),
+            url: String::from("https://ghcr.io/v2/octocat/test/manifests/sha256-abc.att"),
+        };
+
+        assert!(is_manifest_unknown(&registry_error("MANIFEST_UNKNOWN")));
+        assert!(!is_manifest_unknown(&registry_error("DENIED")));
+        assert!(!is_manifest_unknown(
+            &OciDistributionError::UnauthorizedError {
+                url: String::from("https://ghcr.io/v2/octocat/test/manifests/sha256-abc.att"),
+            }
+        ));
+        assert!(!is_manifest_unknown(
+            &OciDistributionError::IncompatibleLayerMediaTypeError(String::from(
+                "application/vnd.in-toto+json"
+            ))
+        ));
+    }
+
+    #[test]
+    fn pre_authentication_encoding() {
+        assert_eq!(
+            pae("application/vnd.in-toto+json", b"{}"),
+            b"DSSEv1 28 application/vnd.in-toto+json 2 {}"
+        );
+    }
+
+    #[test]
+    fn sign_verify_envelope() {
+        use base64::prelude::*;
+        use sigstore::crypto::{CosignVerificationKey, SigningScheme};
+
+        let signer = SigningScheme::default().create_signer().unwrap();
+        let keypair = signer.to_sigstore_keypair().unwrap();
+        let key = CosignVerificationKey::from_pem(
+            keypair.public_key_to_pem().unwrap().as_bytes(),
+            &SigningScheme::default(),
+        )
+        .unwrap();
+
+        let payload = br#"{"predicateType":"https://slsa.dev/provenance/v1"}"#;
+        let signature = signer
+            .sign(&pae("application/vnd.in-toto+json", payload))
+            .unwrap();
+        let envelope = |payload: &[u8]| {
+            serde_json::to_vec(&serde_json::json!({
+                "payloadType": "application/vnd.in-toto+json",
+                "payload": BASE64_STANDARD.encode(payload),
+                "signatures": [{ "keyid": "", "sig": BASE64_STANDARD.encode(&signature) }],
+            }))
+            .unwrap()
+        };
+
+        verify_envelope(&key, &envelope(payload)).unwrap();
+        assert!(verify_envelope(&key, &envelope(b"{}")).is_err());
+    }
+
+    #[test]
+    fn statement_subject() {
+        let image: oci_distribution::Reference =
+            "ghcr.io/blue-build/test@sha256:ab0f1d6cf3b49a5c3beb8bfb3f3a8a1b0bd4cdc7c11c0e8e3e8b5b4fd35c0f0e"
+                .parse()
+                .unwrap();
+
+        let statement = in_toto_statement(
+            &image,
+            "https://spdx.dev/Document",
+            &serde_json::json!({ "spdxVersion": "SPDX-2.3" }),
+        )
+        .unwrap();
+
+        assert_eq!(statement["predicateType"], "https://spdx.dev/Document");
+        assert_eq!(statement["subject"][0]["name"], "ghcr.io/blue-build/test");
+        assert_eq!(
+            statement["subject"][0]["digest"]["sha256"],
+            "ab0f1d6cf3b49a5c3beb8bfb3f3a8a1b0bd4cdc7c11c0e8e3e8b5b4fd35c0f0e"
+        );
+        assert_eq!(statement["predicate"]["spdxVersion"], "SPDX-2.3");
+    }
 
     #[test]
     fn generate_key_pair() {
@@ -5,36 +5,43 @@ use std::{
 };
 
 use blue_build_utils::{constants::COSIGN_PUB_PATH, retry, string_vec};
-use log::{debug, info, trace};
+use log::{debug, info, trace, warn};
 use miette::{bail, miette, Context, IntoDiagnostic, Result};
 use oci_distribution::Reference;
 use semver::{Version, VersionReq};
 
 use crate::drivers::{functions::get_private_key, types::CiDriverType, Driver};
 
+#[cfg(feature = "oci")]
+use super::oci_driver::OciDriver;
+#[cfg(feature = "rechunk")]
+use super::opts::RechunkOpts;
+#[cfg(feature = "sbom")]
+use super::opts::{GenerateSbomOpts, SbomSource};
 #[cfg(feature = "sigstore")]
 use super::sigstore_driver::SigstoreDriver;
+#[cfg(any(feature = "rechunk", feature = "diff"))]
+use super::types::{ContainerId, MountId};
+#[cfg(feature = "diff")]
+use super::types::{ImageSource, MountedImage};
 use super::{
     buildah_driver::BuildahDriver,
     cosign_driver::CosignDriver,
     docker_driver::DockerDriver,
+    env_driver::EnvDriver,
+    forgejo_driver::ForgejoDriver,
     github_driver::GithubDriver,
     gitlab_driver::GitlabDriver,
     local_driver::LocalDriver,
     opts::{
-        BuildOpts, BuildTagPushOpts, CheckKeyPairOpts, GenerateImageNameOpts, GenerateKeyPairOpts,
-        GenerateTagsOpts, GetMetadataOpts, PushOpts, RunOpts, SignOpts, SignVerifyOpts, TagOpts,
-        VerifyOpts, VerifyType,
+        AttestOpts, BuildOpts, BuildTagPushOpts, CheckKeyPairOpts, GenerateImageNameOpts,
+        GenerateKeyPairOpts, GenerateTagsOpts, GetMetadataOpts, ManifestPushOpts, PushOpts,
+        RunOpts, SignOpts, SignVerifyOpts, TagOpts, VerifyAttestationOpts, VerifyOpts, VerifyType,
     },
     podman_driver::PodmanDriver,
     skopeo_driver::SkopeoDriver,
     types::ImageMetadata,
 };
-#[cfg(feature = "rechunk")]
-use super::{
-    opts::RechunkOpts,
-    types::{ContainerId, MountId},
-};
 
 trait PrivateDriver {}
 
@@ -53,6 +60,8 @@ impl_private_driver!(
     BuildahDriver,
     GithubDriver,
     GitlabDriver,
+    ForgejoDriver,
+    EnvDriver,
     LocalDriver,
     CosignDriver,
     SkopeoDriver,
@@ -62,6 +71,9 @@ impl_private_driver!(
 #[cfg(feature = "sigstore")]
 impl_private_driver!(SigstoreDriver);
 
+#[cfg(feature = "oci")]
+impl_private_driver!(OciDriver);
+
 /// Trait for retrieving version of a driver.
 #[allow(private_bounds)]
 pub trait DriverVersion: PrivateDriver {
@@ -105,6 +117,15 @@ pub trait BuildDriver: PrivateDriver {
     /// Will error if the push fails.
     fn push(opts: &PushOpts) -> Result<()>;
 
+    /// Creates a manifest list from the already pushed
+    /// per-platform images and pushes it to the registry.
+    ///
+    /// Returns the digest of the pushed manifest list.
+    ///
+    /// # Errors
+    /// Will error if the manifest list fails to be created or pushed.
+    fn manifest_push(opts: &ManifestPushOpts) -> Result<String>;
+
     /// Runs the login logic for the driver.
     ///
     /// # Errors
@@ -142,6 +163,9 @@ pub trait BuildDriver: PrivateDriver {
             .containerfile(opts.containerfile.as_ref())
             .platform(opts.platform)
             .squash(opts.squash)
+            .maybe_cache_from(opts.cache_from)
+            .maybe_cache_to(opts.cache_to)
+            .secrets(opts.secrets)
             .build();
 
         info!("Building image {full_image}");
@@ -224,7 +248,7 @@ pub trait RunDriver: PrivateDriver {
 }
 
 #[allow(private_bounds)]
-#[cfg(feature = "rechunk")]
+#[cfg(any(feature = "rechunk", feature = "diff"))]
 pub(super) trait ContainerMountDriver: PrivateDriver {
     /// Creates container
     ///
@@ -260,9 +284,100 @@ pub(super) trait ContainerMountDriver: PrivateDriver {
     ///
     /// # Errors
     /// Will error if the volume remove command fails.
+    #[cfg_attr(not(feature = "rechunk"), allow(dead_code))]
     fn remove_volume(volume_id: &str) -> Result<()>;
 }
 
+/// Mounts the filesystem of images so
+/// that they can be read from the host.
+#[allow(private_bounds)]
+#[cfg(feature = "diff")]
+pub trait ImageMountDriver: ContainerMountDriver {
+    /// Loads an `oci-archive` into local storage as `image`.
+    ///
+    /// # Errors
+    /// Will error if the archive can't be loaded.
+    fn load_archive(archive: &std::path::Path, image: &Reference) -> Result<()>;
+
+    /// Gets the labels of an image in local storage.
+    ///
+    /// # Errors
+    /// Will error if the image can't be inspected.
+    fn image_labels(
+        image: &Reference,
+    ) -> Result<std::collections::HashMap<String, serde_json::Value>>;
+
+    /// Creates a container from the image and mounts its filesystem.
+    ///
+    /// Images that aren't in local storage are pulled when the
+    /// container is created. Archives are loaded into local storage
+    /// under a temporary name that is removed when the image is
+    /// unmounted, or when it fails to mount.
+    ///
+    /// # Errors
+    /// Will error if the image can't be loaded or mounted.
+    fn mount_image(source: &ImageSource) -> Result<MountedImage> {
+        trace!("ImageMountDriver::mount_image({source})");
+
+        let image = match source {
+            ImageSource::Image(image) => image.clone(),
+            ImageSource::Archive(archive) => {
+                let image: Reference = format!("localhost/bluebuild-diff/{}", uuid::Uuid::new_v4())
+                    .parse()
+                    .into_diagnostic()?;
+                Self::load_archive(archive, &image)?;
+                image
+            }
+        };
+        let remove_loaded_image = || {
+            if matches!(source, ImageSource::Archive(_)) {
+                if let Err(e) = Self::remove_image(&image) {
+                    warn!("Failed to remove the temporary image {image}: {e:?}");
+                }
+            }
+        };
+
+        let container = Self::create_container(&image).inspect_err(|_| remove_loaded_image())?;
+        let (labels, mount) = match Self::image_labels(&image)
+            .and_then(|labels| Ok((labels, Self::mount_container(&container)?)))
+        {
+            Ok(mounted) => mounted,
+            Err(e) => {
+                if let Err(e) = Self::remove_container(&container) {
+                    warn!("Failed to remove container {container}: {e:?}");
+                }
+                remove_loaded_image();
+                return Err(e);
+            }
+        };
+        debug!("Mounted {source} at {mount}");
+
+        Ok(MountedImage {
+            source: source.clone(),
+            image,
+            labels,
+            mount: PathBuf::from(mount.to_string()),
+            container,
+        })
+    }
+
+    /// Unmounts the image and removes its container.
+    ///
+    /// # Errors
+    /// Will error if the container can't be unmounted or removed.
+    fn unmount_image(mounted: MountedImage) -> Result<()> {
+        trace!("ImageMountDriver::unmount_image({})", mounted.source);
+
+        Self::unmount_container(&mounted.container)?;
+        Self::remove_container(&mounted.container)?;
+
+        if matches!(mounted.source, ImageSource::Archive(_)) {
+            Self::remove_image(&mounted.image)?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(feature = "rechunk")]
 pub(super) trait OciCopy {
     fn copy_oci_dir(
@@ -299,6 +414,7 @@ pub trait RechunkDriver: RunDriver + BuildDriver + ContainerMountDriver {
                 .platform(opts.platform)
                 .squash(true)
                 .host_network(true)
+                .secrets(opts.secrets)
                 .build(),
         )?;
 
@@ -447,15 +563,24 @@ pub trait RechunkDriver: RunDriver + BuildDriver + ContainerMountDriver {
                 "OUT_REF" => format!("oci:{ostree_cache_id}"),
                 "GIT_DIR" => "/var/git",
                 "LABELS" => format!(
-                    "{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}",
+                    "{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}{}",
                     format_args!("{}={}", blue_build_utils::constants::BUILD_ID_LABEL, Driver::get_build_id()),
                     format_args!("org.opencontainers.image.title={}", &opts.name),
                     format_args!("org.opencontainers.image.description={}", &opts.description),
                     format_args!("org.opencontainers.image.source={}", &opts.repo),
-                    format_args!("org.opencontainers.image.base.digest={}", &opts.base_digest),
+                    format_args!("{}={}", blue_build_utils::constants::BASE_DIGEST_LABEL, &opts.base_digest),
                     format_args!("org.opencontainers.image.base.name={}", &opts.base_image),
                     "org.opencontainers.image.created=<timestamp>",
                     "io.artifacthub.package.readme-url=https://raw.githubusercontent.com/blue-build/cli/main/README.md",
+                    if opts.image_digests.is_empty() {
+                        String::new()
+                    } else {
+                        format!(
+                            "\n{}={}",
+                            blue_build_utils::constants::IMAGE_DIGESTS_LABEL,
+                            opts.image_digests.join(",")
+                        )
+                    },
                 )
             })
             .args(bon::vec!["/sources/rechunk/3_chunk.sh"])
@@ -472,6 +597,115 @@ pub trait RechunkDriver: RunDriver + BuildDriver + ContainerMountDriver {
     }
 }
 
+/// Allows agnostic generation of an SBOM for an image.
+#[cfg(feature = "sbom")]
+pub trait SbomDriver: RunDriver {
+    const SYFT_IMAGE: &str = "docker.io/anchore/syft:v1.18.1";
+
+    /// Generates an SBOM for an image by scanning its rpm
+    /// database and files with `syft`.
+    ///
+    /// # Errors
+    /// Will error if the scan fails.
+    fn generate_sbom(opts: &GenerateSbomOpts) -> Result<()> {
+        use blue_build_utils::traits::CowCollecter;
+
+        trace!("SbomDriver::generate_sbom({opts:?})");
+
+        let output_dir = opts
+            .output
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map_or_else(|| PathBuf::from("."), std::path::Path::to_path_buf);
+        std::fs::create_dir_all(&output_dir).into_diagnostic()?;
+        let output_dir = std::fs::canonicalize(output_dir).into_diagnostic()?;
+        let output_file = opts
+            .output
+            .file_name()
+            .ok_or_else(|| miette!("Invalid SBOM path {}", opts.output.display()))?
+            .to_string_lossy();
+
+        // Registry credentials are given to syft through a docker
+        // config file to keep them out of the container's arguments.
+        let auth_dir = tempfile::TempDir::new().into_diagnostic()?;
+        let mut volumes = crate::run_volumes! {
+            output_dir.display().to_string() => "/out",
+            auth_dir.path().display().to_string() => "/auth",
+        };
+        let mut args = string_vec!["scan"];
+
+        match &opts.source {
+            SbomSource::Registry(image) => {
+                if let Some(credentials) = blue_build_utils::credentials::Credentials::get() {
+                    use base64::prelude::*;
+
+                    let auth = BASE64_STANDARD
+                        .encode(format!("{}:{}", credentials.username, credentials.password));
+                    std::fs::write(
+                        auth_dir.path().join("config.json"),
+                        serde_json::json!({
+                            "auths": { &credentials.registry: { "auth": auth } }
+                        })
+                        .to_string(),
+                    )
+                    .into_diagnostic()?;
+                }
+                args.extend(string_vec![
+                    format!("registry:{image}"),
+                    format!("--platform={}", opts.platform),
+                ]);
+            }
+            SbomSource::Archive(archive) => {
+                // The archive was never written in dry-run mode
+                let archive = if crate::dry_run::is_dry_run() {
+                    std::path::absolute(archive)
+                } else {
+                    std::fs::canonicalize(archive)
+                }
+                .into_diagnostic()?;
+                let archive_name = archive
+                    .file_name()
+                    .ok_or_else(|| miette!("Invalid archive path {}", archive.display()))?
+                    .to_string_lossy()
+                    .into_owned();
+                volumes.extend(crate::run_volumes! {
+                    archive.display().to_string() => format!("/archive/{archive_name}"),
+                });
+                args.push(format!("oci-archive:/archive/{archive_name}"));
+            }
+        }
+
+        args.push(format!(
+            "--output={}=/out/{output_file}",
+            opts.format.syft_output()
+        ));
+
+        let status = Self::run(
+            &RunOpts::builder()
+                .image(Self::SYFT_IMAGE)
+                .remove(true)
+                .volumes(volumes)
+                .env_vars(crate::run_envs! {
+                    "DOCKER_CONFIG" => "/auth",
+                })
+                .args(args.collect_cow_vec())
+                .build(),
+        )?;
+
+        if !status.success() {
+            bail!("Failed to generate SBOM {}", opts.output.display());
+        }
+
+        info!("Generated SBOM {}", opts.output.display());
+        Ok(())
+    }
+}
+
+/// The diagnostic code of the errors from [`SigningDriver::verify`] and
+/// [`SigningDriver::verify_attestation`] when the image was checked and
+/// didn't pass, as opposed to when it couldn't be checked at all.
+pub const VERIFICATION_FAILED: &str = "bluebuild::verification_failed";
+
 /// Allows agnostic management of signature keys.
 #[allow(private_bounds)]
 pub trait SigningDriver: PrivateDriver {
@@ -501,9 +735,64 @@ pub trait SigningDriver: PrivateDriver {
     /// information about the `issuer` and `identity`.
     ///
     /// # Errors
-    /// Will error if the image fails to be verified.
+    /// Will error with the [`VERIFICATION_FAILED`] code if the image
+    /// fails to be verified, or any other error if it couldn't be checked.
     fn verify(opts: &VerifyOpts) -> Result<()>;
 
+    /// Attaches a signed attestation of a predicate,
+    /// like an SBOM, to the image digest.
+    ///
+    /// # Errors
+    /// Will error if the attestation fails to be signed or pushed.
+    fn attest(opts: &AttestOpts) -> Result<()>;
+
+    /// Verifies that the image has an attestation with
+    /// the predicate type signed by the key or identity.
+    ///
+    /// # Errors
+    /// Will error with the [`VERIFICATION_FAILED`] code if no matching
+    /// attestation could be verified, or any other error if it couldn't be checked.
+    fn verify_attestation(opts: &VerifyAttestationOpts) -> Result<()>;
+
+    /// Attests the image digest and verifies the
+    /// attestation with the matching public key
+    /// or keyless identity.
+    ///
+    /// # Errors
+    /// Will error if the attestation fails to be pushed or verified.
+    fn attest_and_verify(opts: &AttestOpts) -> Result<()> {
+        trace!("attest_and_verify({opts:?})");
+
+        let path = opts
+            .dir
+            .as_ref()
+            .map_or_else(|| PathBuf::from("."), |d| d.to_path_buf());
+
+        let verify_type = match (Driver::get_ci_driver(), get_private_key(&path)) {
+            // Cosign public/private key pair
+            (_, Ok(_)) => VerifyType::File(path.join(COSIGN_PUB_PATH).into()),
+            // CI keyless
+            (CiDriverType::Github | CiDriverType::Gitlab | CiDriverType::Forgejo, _) => {
+                VerifyType::Keyless {
+                    issuer: Driver::oidc_provider()?.into(),
+                    identity: Driver::keyless_cert_identity()?.into(),
+                }
+            }
+            _ => {
+                bail!("Failed to get information for attesting the image");
+            }
+        };
+
+        Self::attest(opts)?;
+        Self::verify_attestation(
+            &VerifyAttestationOpts::builder()
+                .image(&*opts.image)
+                .predicate_type(&*opts.predicate_type)
+                .verify_type(verify_type)
+                .build(),
+        )
+    }
+
     /// Sign an image given the image name and tag.
     ///
     /// # Errors
@@ -517,56 +806,90 @@ pub trait SigningDriver: PrivateDriver {
             .map_or_else(|| PathBuf::from("."), |d| d.to_path_buf());
 
         let image_name: &str = opts.image.as_ref();
-        let inspect_opts = GetMetadataOpts::builder()
-            .image(image_name)
-            .platform(opts.platform);
-
-        let inspect_opts = if let Some(ref tag) = opts.tag {
-            inspect_opts.tag(&**tag).build()
-        } else {
-            inspect_opts.build()
-        };
-
-        let image_digest = Driver::get_metadata(&inspect_opts)?.digest;
         let image_name_tag = opts
             .tag
             .as_ref()
             .map_or_else(|| image_name.to_owned(), |t| format!("{image_name}:{t}"));
-        let image_digest = format!("{image_name}@{image_digest}");
 
-        let (sign_opts, verify_opts) = match (Driver::get_ci_driver(), get_private_key(&path)) {
-            // Cosign public/private key pair
-            (_, Ok(priv_key)) => (
-                SignOpts::builder()
-                    .image(&image_digest)
-                    .dir(&path)
-                    .key(priv_key.to_string())
-                    .build(),
-                VerifyOpts::builder()
-                    .image(&image_name_tag)
-                    .verify_type(VerifyType::File(path.join(COSIGN_PUB_PATH).into()))
-                    .build(),
-            ),
-            // Gitlab keyless
-            (CiDriverType::Github | CiDriverType::Gitlab, _) => (
-                SignOpts::builder().dir(&path).image(&image_digest).build(),
-                VerifyOpts::builder()
-                    .image(&image_name_tag)
-                    .verify_type(VerifyType::Keyless {
-                        issuer: Driver::oidc_provider()?.into(),
-                        identity: Driver::keyless_cert_identity()?.into(),
-                    })
-                    .build(),
-            ),
-            _ => bail!("Failed to get information for signing the image"),
+        // A manifest list gets signed along with every
+        // per-platform image that it points to.
+        let images = if let Some(ref manifest_digest) = opts.manifest_digest {
+            let tag = opts
+                .tag
+                .as_deref()
+                .ok_or_else(|| miette!("A tag is required to sign a manifest list"))?;
+
+            std::iter::once(Ok((
+                format!("{image_name}@{manifest_digest}"),
+                image_name_tag,
+            )))
+            .chain(opts.platforms.iter().map(|platform| {
+                let arch_tag = platform.arch_tag(tag);
+                let image_digest = Driver::get_pushed_digest(
+                    &GetMetadataOpts::builder()
+                        .image(image_name)
+                        .tag(&arch_tag)
+                        .platform(*platform)
+                        .build(),
+                )?;
+                Ok((
+                    format!("{image_name}@{image_digest}"),
+                    format!("{image_name}:{arch_tag}"),
+                ))
+            }))
+            .collect::<Result<Vec<_>>>()?
+        } else {
+            let inspect_opts = GetMetadataOpts::builder()
+                .image(image_name)
+                .platform(opts.platform);
+
+            let inspect_opts = if let Some(ref tag) = opts.tag {
+                inspect_opts.tag(&**tag).build()
+            } else {
+                inspect_opts.build()
+            };
+
+            let image_digest = Driver::get_pushed_digest(&inspect_opts)?;
+            vec![(format!("{image_name}@{image_digest}"), image_name_tag)]
         };
 
         let retry_count = if opts.retry_push { opts.retry_count } else { 0 };
 
-        retry(retry_count, 5, || {
-            Self::sign(&sign_opts)?;
-            Self::verify(&verify_opts)
-        })?;
+        for (image_digest, image_name_tag) in &images {
+            let (sign_opts, verify_opts) = match (Driver::get_ci_driver(), get_private_key(&path)) {
+                // Cosign public/private key pair
+                (_, Ok(priv_key)) => (
+                    SignOpts::builder()
+                        .image(image_digest)
+                        .dir(&path)
+                        .key(priv_key.to_string())
+                        .build(),
+                    VerifyOpts::builder()
+                        .image(image_name_tag)
+                        .verify_type(VerifyType::File(path.join(COSIGN_PUB_PATH).into()))
+                        .build(),
+                ),
+                // CI keyless
+                (CiDriverType::Github | CiDriverType::Gitlab | CiDriverType::Forgejo, _) => (
+                    SignOpts::builder().dir(&path).image(image_digest).build(),
+                    VerifyOpts::builder()
+                        .image(image_name_tag)
+                        .verify_type(VerifyType::Keyless {
+                            issuer: Driver::oidc_provider()?.into(),
+                            identity: Driver::keyless_cert_identity()?.into(),
+                        })
+                        .build(),
+                ),
+                _ => {
+                    bail!("Failed to get information for signing the image");
+                }
+            };
+
+            retry(retry_count, 5, || {
+                Self::sign(&sign_opts)?;
+                Self::verify(&verify_opts)
+            })?;
+        }
 
         Ok(())
     }
@@ -680,5 +1003,9 @@ pub trait CiDriver: PrivateDriver {
     /// Will error if the environment variables aren't set.
     fn get_registry() -> Result<String>;
 
-    fn default_ci_file_path() -> PathBuf;
+    /// Get the path of the CI file that `init` generates for the CI system.
+    ///
+    /// # Errors
+    /// Will error if a CI file can't be generated for the CI system.
+    fn default_ci_file_path() -> Result<PathBuf>;
 }
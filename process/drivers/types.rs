@@ -1,14 +1,16 @@
-use std::{collections::HashMap, env};
+use std::{collections::HashMap, env, path::PathBuf, str::FromStr};
 
-use blue_build_utils::constants::{GITHUB_ACTIONS, GITLAB_CI, IMAGE_VERSION_LABEL};
+use blue_build_utils::constants::{
+    FORGEJO_ACTIONS, GITEA_ACTIONS, GITHUB_ACTIONS, GITLAB_CI, IMAGE_VERSION_LABEL,
+};
 use clap::ValueEnum;
 use log::trace;
 use serde::Deserialize;
 use serde_json::Value;
 
 use crate::drivers::{
-    buildah_driver::BuildahDriver, docker_driver::DockerDriver, podman_driver::PodmanDriver,
-    DriverVersion,
+    buildah_driver::BuildahDriver, docker_driver::DockerDriver, env_driver::EnvDriver,
+    podman_driver::PodmanDriver, DriverVersion,
 };
 
 pub(super) trait DetermineDriver<T> {
@@ -20,6 +22,8 @@ pub enum InspectDriverType {
     Skopeo,
     Podman,
     Docker,
+    #[cfg(feature = "oci")]
+    Oci,
 }
 
 impl DetermineDriver<InspectDriverType> for Option<InspectDriverType> {
@@ -33,6 +37,9 @@ impl DetermineDriver<InspectDriverType> for Option<InspectDriverType> {
                 (Ok(_skopeo), _, _) => InspectDriverType::Skopeo,
                 (_, Ok(_docker), _) => InspectDriverType::Docker,
                 (_, _, Ok(_podman)) => InspectDriverType::Podman,
+                #[cfg(feature = "oci")]
+                _ => InspectDriverType::Oci,
+                #[cfg(not(feature = "oci"))]
                 _ => panic!(
                     "{}{}",
                     "Could not determine inspection strategy. ",
@@ -154,16 +161,31 @@ pub enum CiDriverType {
     Local,
     Gitlab,
     Github,
+    Forgejo,
+    Env,
 }
 
 impl DetermineDriver<CiDriverType> for Option<CiDriverType> {
     fn determine_driver(&mut self) -> CiDriverType {
         trace!("CiDriverType::determine_driver()");
 
+        // Forgejo and Gitea runners also set `GITHUB_ACTIONS`
+        // so they need to be checked for first. Explicitly
+        // configuring the env driver takes precedence over both.
         *self.get_or_insert(
-            match (env::var(GITLAB_CI).ok(), env::var(GITHUB_ACTIONS).ok()) {
-                (Some(_gitlab_ci), None) => CiDriverType::Gitlab,
-                (None, Some(_github_actions)) => CiDriverType::Github,
+            match (
+                EnvDriver::is_explicit(),
+                env::var(GITLAB_CI).ok(),
+                env::var(GITHUB_ACTIONS).ok(),
+                env::var(FORGEJO_ACTIONS)
+                    .or_else(|_| env::var(GITEA_ACTIONS))
+                    .ok(),
+            ) {
+                (true, _, _, _) => CiDriverType::Env,
+                (false, None, _, Some(_forgejo_actions)) => CiDriverType::Forgejo,
+                (false, Some(_gitlab_ci), None, None) => CiDriverType::Gitlab,
+                (false, None, Some(_github_actions), None) => CiDriverType::Github,
+                _ if EnvDriver::is_configured() => CiDriverType::Env,
                 _ => CiDriverType::Local,
             },
         )
@@ -196,6 +218,15 @@ impl Platform {
             Self::LinuxArm64 => "arm64",
         }
     }
+
+    /// Suffixes the tag with the architecture of the platform.
+    ///
+    /// This is the tag used for each per-platform image
+    /// that makes up a multi-platform manifest list.
+    #[must_use]
+    pub fn arch_tag(&self, tag: &str) -> String {
+        format!("{tag}-{}", self.arch())
+    }
 }
 
 impl std::fmt::Display for Platform {
@@ -216,6 +247,134 @@ impl std::fmt::Display for Platform {
     }
 }
 
+/// A location to import the build cache from or export it to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheTarget {
+    /// An image repository in a registry,
+    /// e.g. `ghcr.io/octocat/my-image-cache`.
+    Registry(String),
+
+    /// A local OCI image layout directory.
+    Local(PathBuf),
+}
+
+impl CacheTarget {
+    /// The default cache location for an image.
+    ///
+    /// When pushing, the cache is stored next to the image in the
+    /// `<image>-cache` repository. Otherwise it is stored in
+    /// `~/.cache/bluebuild/build-cache/<image>`.
+    #[must_use]
+    pub fn default_for(image: &str, push: bool) -> Self {
+        match blue_build_utils::cache_dir() {
+            Some(cache_dir) if !push => Self::Local(
+                cache_dir
+                    .join("build-cache")
+                    .join(image.replace(['/', ':'], "_")),
+            ),
+            _ => Self::Registry(format!("{image}-cache")),
+        }
+    }
+
+    /// Suffixes the target with the architecture of the platform
+    /// so that each platform of a multi-platform build has its own cache.
+    #[must_use]
+    pub fn for_platform(&self, platform: Platform) -> Self {
+        match self {
+            Self::Registry(repo) => Self::Registry(format!("{repo}-{}", platform.arch())),
+            Self::Local(dir) => {
+                let mut dir = dir.clone().into_os_string();
+                dir.push(format!("-{}", platform.arch()));
+                Self::Local(dir.into())
+            }
+        }
+    }
+}
+
+impl FromStr for CacheTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            _ if s.is_empty() => Err(String::from("The cache target cannot be empty")),
+            Some(("oci" | "local", dir)) => Ok(Self::Local(PathBuf::from(dir))),
+            Some(("registry", repo)) => Ok(Self::Registry(repo.to_string())),
+            _ if s.starts_with(['/', '.']) => Ok(Self::Local(PathBuf::from(s))),
+            _ => Ok(Self::Registry(s.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for CacheTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Registry(repo) => write!(f, "{repo}"),
+            Self::Local(dir) => write!(f, "oci:{}", dir.display()),
+        }
+    }
+}
+
+/// A secret passed to a build with the `--secret` flag.
+///
+/// Modules mount the secret with `--mount=type=secret,id=<id>`
+/// so that it isn't stored in a layer of the image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildSecret {
+    /// A secret read from an environment variable.
+    Env { id: String, var: String },
+
+    /// A secret read from a file.
+    File { id: String, path: PathBuf },
+}
+
+impl std::fmt::Display for BuildSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Env { id, var } => write!(f, "id={id},env={var}"),
+            Self::File { id, path } => write!(f, "id={id},src={}", path.display()),
+        }
+    }
+}
+
+/// The format of a generated SBOM.
+#[cfg(feature = "sbom")]
+#[derive(Debug, Default, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum SbomFormat {
+    #[default]
+    Spdx,
+    Cyclonedx,
+}
+
+#[cfg(feature = "sbom")]
+impl SbomFormat {
+    /// The name of the output format for `syft`.
+    #[must_use]
+    pub const fn syft_output(self) -> &'static str {
+        match self {
+            Self::Spdx => "spdx-json",
+            Self::Cyclonedx => "cyclonedx-json",
+        }
+    }
+
+    /// The predicate type used when attesting the SBOM.
+    #[must_use]
+    pub const fn predicate_type(self) -> &'static str {
+        match self {
+            Self::Spdx => "https://spdx.dev/Document",
+            Self::Cyclonedx => "https://cyclonedx.org/bom",
+        }
+    }
+
+    /// The file extension of the SBOM.
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Spdx => "spdx.json",
+            Self::Cyclonedx => "cdx.json",
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct ImageMetadata {
@@ -236,47 +395,101 @@ impl ImageMetadata {
     }
 }
 
-#[cfg(feature = "rechunk")]
+#[cfg(any(feature = "rechunk", feature = "diff"))]
+#[derive(Debug)]
 pub struct ContainerId(pub(super) String);
 
-#[cfg(feature = "rechunk")]
+#[cfg(any(feature = "rechunk", feature = "diff"))]
 impl std::fmt::Display for ContainerId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", &self.0)
     }
 }
 
-#[cfg(feature = "rechunk")]
+#[cfg(any(feature = "rechunk", feature = "diff"))]
 impl AsRef<std::ffi::OsStr> for ContainerId {
     fn as_ref(&self) -> &std::ffi::OsStr {
         self.0.as_ref()
     }
 }
 
-#[cfg(feature = "rechunk")]
+#[cfg(any(feature = "rechunk", feature = "diff"))]
+#[derive(Debug)]
 pub struct MountId(pub(super) String);
 
-#[cfg(feature = "rechunk")]
+#[cfg(any(feature = "rechunk", feature = "diff"))]
 impl std::fmt::Display for MountId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", &self.0)
     }
 }
 
-#[cfg(feature = "rechunk")]
+#[cfg(any(feature = "rechunk", feature = "diff"))]
 impl AsRef<std::ffi::OsStr> for MountId {
     fn as_ref(&self) -> &std::ffi::OsStr {
         self.0.as_ref()
     }
 }
 
-#[cfg(feature = "rechunk")]
+#[cfg(any(feature = "rechunk", feature = "diff"))]
 impl<'a> From<&'a MountId> for std::borrow::Cow<'a, str> {
     fn from(value: &'a MountId) -> Self {
         Self::Borrowed(&value.0)
     }
 }
 
+/// An image in local storage or a registry, or an
+/// `oci-archive` to load into local storage.
+#[cfg(feature = "diff")]
+#[derive(Debug, Clone)]
+pub enum ImageSource {
+    Image(oci_distribution::Reference),
+    Archive(PathBuf),
+}
+
+#[cfg(feature = "diff")]
+impl FromStr for ImageSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.strip_prefix("oci-archive:").map_or_else(
+            || {
+                s.parse()
+                    .map(Self::Image)
+                    .map_err(|e| format!("Unable to parse image reference {s}: {e}"))
+            },
+            |archive| Ok(Self::Archive(PathBuf::from(archive))),
+        )
+    }
+}
+
+#[cfg(feature = "diff")]
+impl std::fmt::Display for ImageSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Image(image) => write!(f, "{image}"),
+            Self::Archive(archive) => write!(f, "oci-archive:{}", archive.display()),
+        }
+    }
+}
+
+/// An image with its filesystem mounted on the host.
+#[cfg(feature = "diff")]
+#[derive(Debug)]
+pub struct MountedImage {
+    pub source: ImageSource,
+
+    /// The reference of the image in local storage.
+    pub image: oci_distribution::Reference,
+
+    pub labels: HashMap<String, Value>,
+
+    /// The directory the image's filesystem is mounted at.
+    pub mount: PathBuf,
+
+    pub(super) container: ContainerId,
+}
+
 #[cfg(feature = "rechunk")]
 pub struct OciDir(String);
 
@@ -306,3 +519,63 @@ impl TryFrom<std::path::PathBuf> for OciDir {
         Ok(Self(format!("oci:{}", value.display())))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use rstest::rstest;
+
+    use super::{BuildSecret, CacheTarget, Platform};
+
+    #[rstest]
+    #[case("ghcr.io/octocat/cache", CacheTarget::Registry("ghcr.io/octocat/cache".into()))]
+    #[case("localhost:5000/cache", CacheTarget::Registry("localhost:5000/cache".into()))]
+    #[case("registry:ghcr.io/octocat/cache", CacheTarget::Registry("ghcr.io/octocat/cache".into()))]
+    #[case("oci:cache", CacheTarget::Local(PathBuf::from("cache")))]
+    #[case("local:/tmp/cache", CacheTarget::Local(PathBuf::from("/tmp/cache")))]
+    #[case("./cache", CacheTarget::Local(PathBuf::from("./cache")))]
+    fn parse_cache_target(#[case] target: &str, #[case] expected: CacheTarget) {
+        assert_eq!(target.parse::<CacheTarget>().unwrap(), expected);
+    }
+
+    #[test]
+    fn cache_target_for_platform() {
+        assert_eq!(
+            CacheTarget::Registry("ghcr.io/octocat/image-cache".into())
+                .for_platform(Platform::LinuxArm64),
+            CacheTarget::Registry("ghcr.io/octocat/image-cache-arm64".into())
+        );
+        assert_eq!(
+            CacheTarget::Local(PathBuf::from("/tmp/cache")).for_platform(Platform::LinuxAmd64),
+            CacheTarget::Local(PathBuf::from("/tmp/cache-amd64"))
+        );
+    }
+
+    #[rstest]
+    #[case(Platform::LinuxAmd64, "latest-amd64")]
+    #[case(Platform::LinuxArm64, "latest-arm64")]
+    fn arch_tag(#[case] platform: Platform, #[case] expected: &str) {
+        assert_eq!(platform.arch_tag("latest"), expected);
+    }
+
+    #[test]
+    fn build_secret_args() {
+        assert_eq!(
+            BuildSecret::Env {
+                id: "license-key".into(),
+                var: "LICENSE_KEY".into(),
+            }
+            .to_string(),
+            "id=license-key,env=LICENSE_KEY"
+        );
+        assert_eq!(
+            BuildSecret::File {
+                id: "repo-token".into(),
+                path: PathBuf::from("/tmp/token"),
+            }
+            .to_string(),
+            "id=repo-token,src=/tmp/token"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Support for printing the external commands that would
+//! be run instead of running them.
+//!
+//! Only commands that change the state of the system or a registry
+//! go through dry-run. Commands that read state, like inspecting an
+//! image, still run so that tags and digests can be resolved.
+//!
+//! Everything is printed to stderr so that it doesn't
+//! get mixed into the output of a command.
+
+use std::{
+    io::Result,
+    os::unix::process::ExitStatusExt,
+    process::{Command, ExitStatus, Output},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use private::Private;
+
+/// Env vars, and `NAME=value` args, with a name containing
+/// any of these have their values redacted.
+const SECRET_ENV_VARS: &[&str] = &["PASSWORD", "TOKEN", "SECRET", "PRIVATE_KEY"];
+
+/// Args whose value is redacted.
+const SECRET_ARGS: &[&str] = &["-p", "--password", "--registry-password", "--token"];
+
+const REDACTED: &str = "***";
+
+/// The digest given to images that would have been pushed.
+pub const DRY_RUN_DIGEST: &str =
+    "sha256:0000000000000000000000000000000000000000000000000000000000000000";
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+mod private {
+    pub trait Private {}
+}
+
+impl Private for Command {}
+
+/// Sets whether commands are printed instead of run.
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+/// Whether commands are printed instead of run.
+#[must_use]
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Prints a note about an action that doesn't use
+/// an external command, like signing with `sigstore`.
+pub fn print_action<S: AsRef<str>>(action: S) {
+    eprintln!("# {}", action.as_ref());
+}
+
+pub trait DryRunCommand: Private {
+    /// Prints the command when in dry-run mode,
+    /// otherwise it's run with [`Command::status`].
+    ///
+    /// # Errors
+    /// Will error if the command failed to run.
+    fn dry_run_status(&mut self) -> Result<ExitStatus>;
+
+    /// Prints the command when in dry-run mode,
+    /// otherwise it's run with [`Command::output`].
+    /// The output of a printed command is empty.
+    ///
+    /// # Errors
+    /// Will error if the command failed to run.
+    fn dry_run_output(&mut self) -> Result<Output>;
+
+    /// Prints the command if in dry-run mode
+    /// and returns whether it was printed.
+    fn print_if_dry_run(&self) -> bool;
+
+    /// The command line with secrets redacted.
+    fn redacted(&self) -> String;
+}
+
+impl DryRunCommand for Command {
+    fn dry_run_status(&mut self) -> Result<ExitStatus> {
+        if self.print_if_dry_run() {
+            return Ok(ExitStatus::from_raw(0));
+        }
+        self.status()
+    }
+
+    fn dry_run_output(&mut self) -> Result<Output> {
+        if self.print_if_dry_run() {
+            return Ok(Output {
+                status: ExitStatus::from_raw(0),
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            });
+        }
+        self.output()
+    }
+
+    fn print_if_dry_run(&self) -> bool {
+        let dry_run = is_dry_run();
+        if dry_run {
+            eprintln!("{}", self.redacted());
+        }
+        dry_run
+    }
+
+    fn redacted(&self) -> String {
+        let envs = self.get_envs().filter_map(|(key, value)| {
+            let key = key.to_string_lossy();
+            value.map(|value| {
+                if is_secret_var(&key) {
+                    format!("{key}={REDACTED}")
+                } else {
+                    format!("{key}={}", quote(&value.to_string_lossy()))
+                }
+            })
+        });
+
+        let mut redact_next = false;
+        let args = self.get_args().map(|arg| {
+            let arg = arg.to_string_lossy();
+
+            if redact_next {
+                redact_next = false;
+                return String::from(REDACTED);
+            }
+            redact_next = SECRET_ARGS.contains(&&*arg);
+
+            match arg.split_once('=') {
+                Some((flag, _)) if SECRET_ARGS.contains(&flag) => format!("{flag}={REDACTED}"),
+                Some((name, _)) if is_secret_var(name) => format!("{name}={REDACTED}"),
+                _ => quote(&arg),
+            }
+        });
+
+        envs.chain(std::iter::once(quote(
+            &self.get_program().to_string_lossy(),
+        )))
+        .chain(args)
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+}
+
+fn is_secret_var(name: &str) -> bool {
+    SECRET_ENV_VARS.iter().any(|secret| name.contains(secret))
+}
+
+/// Quotes an argument so that it can be
+/// copied into a shell.
+fn quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./:=@%+,".contains(c))
+    {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use blue_build_utils::cmd;
+
+    use super::DryRunCommand;
+
+    #[test]
+    fn redacted_command() {
+        let command = cmd!(
+            "podman",
+            "login",
+            "-u",
+            "octocat",
+            "-p",
+            "hunter2",
+            "--password=hunter2",
+            "--label",
+            "org.opencontainers.image.description=A test image",
+            "ENROLLMENT_PASSWORD=universalblue",
+            "ghcr.io",
+            "COSIGN_PASSWORD" => "hunter2",
+            "BUILDAH_FORMAT" => "docker",
+        );
+
+        assert_eq!(
+            command.redacted(),
+            "BUILDAH_FORMAT=docker COSIGN_PASSWORD=*** podman login -u octocat -p *** \
+            --password=*** --label 'org.opencontainers.image.description=A test image' \
+            ENROLLMENT_PASSWORD=*** ghcr.io"
+        );
+    }
+}
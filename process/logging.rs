@@ -3,6 +3,7 @@ use std::{
     env,
     fs::OpenOptions,
     io::{BufRead, BufReader, Result, Write as IoWrite},
+    os::unix::process::ExitStatusExt,
     path::{Path, PathBuf},
     process::{Command, ExitStatus, Stdio},
     sync::Mutex,
@@ -35,7 +36,10 @@ use once_cell::sync::Lazy;
 use private::Private;
 use rand::Rng;
 
-use crate::signal_handler::{add_pid, remove_pid};
+use crate::{
+    dry_run::DryRunCommand,
+    signal_handler::{add_pid, remove_pid},
+};
 
 mod private {
     pub trait Private {}
@@ -213,6 +217,10 @@ impl CommandLogging for Command {
         U: AsRef<str>,
     {
         fn inner(mut command: Command, image_ref: &str, message: &str) -> Result<ExitStatus> {
+            if command.print_if_dry_run() {
+                return Ok(ExitStatus::from_raw(0));
+            }
+
             let ansi_color = gen_random_ansi_color();
             let name = color_str(image_ref, ansi_color);
             let short_name = color_str(shorten_name(image_ref), ansi_color);
@@ -288,6 +296,10 @@ impl CommandLogging for Command {
             header: &str,
             message: Cow<'static, str>,
         ) -> Result<ExitStatus> {
+            if command.print_if_dry_run() {
+                return Ok(ExitStatus::from_raw(0));
+            }
+
             let ansi_color = gen_random_ansi_color();
             let header = color_str(header, ansi_color);
             let (reader, writer) = os_pipe::pipe()?;
@@ -2,16 +2,17 @@
 //! by this tool. It contains drivers for running, building, inspecting, and signing
 //! images that interface with tools like docker or podman.
 
-#[cfg(any(feature = "sigstore", feature = "validate"))]
+#[cfg(any(feature = "sigstore", feature = "validate", feature = "oci"))]
 use once_cell::sync::Lazy;
-#[cfg(any(feature = "sigstore", feature = "validate"))]
+#[cfg(any(feature = "sigstore", feature = "validate", feature = "oci"))]
 use tokio::runtime::Runtime;
 
 pub mod drivers;
+pub mod dry_run;
 pub mod logging;
 pub mod signal_handler;
 
-#[cfg(any(feature = "sigstore", feature = "validate"))]
+#[cfg(any(feature = "sigstore", feature = "validate", feature = "oci"))]
 pub static ASYNC_RUNTIME: Lazy<Runtime> = Lazy::new(|| {
     tokio::runtime::Builder::new_multi_thread()
         .enable_all()
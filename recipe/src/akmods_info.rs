@@ -1,3 +1,4 @@
+use blue_build_utils::constants::AKMODS_IMAGE_REPO;
 use bon::Builder;
 
 #[derive(Debug, Clone, Builder, PartialEq, Eq, Hash)]
@@ -8,3 +9,17 @@ pub struct AkmodsInfo {
     #[builder(into)]
     pub stage_name: String,
 }
+
+impl AkmodsInfo {
+    /// The references of the images holding the RPMs.
+    #[must_use]
+    pub fn image_refs(&self) -> Vec<String> {
+        let (base, kernel, nvidia) = &self.images;
+
+        [Some(base), Some(kernel), nvidia.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(|image| format!("{AKMODS_IMAGE_REPO}/{image}"))
+            .collect()
+    }
+}
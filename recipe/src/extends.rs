@@ -0,0 +1,276 @@
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use colored::Colorize;
+use indexmap::IndexMap;
+use log::{debug, trace};
+use miette::{bail, Context, IntoDiagnostic, Result};
+use serde_yaml::{Mapping, Value};
+
+use crate::base_recipe_path;
+
+const EXTENDS_KEY: &str = "extends";
+const MODULES_KEY: &str = "modules";
+const STAGES_KEY: &str = "stages";
+const VARS_KEY: &str = "vars";
+const ANCHOR_KEY: &str = "anchor";
+
+/// Merges the recipes that a recipe `extends` into its contents.
+///
+/// Any `anchor` entries are removed from the module list of
+/// the final recipe. The contents are returned untouched if the
+/// recipe doesn't extend another recipe and has no anchors.
+pub fn resolve_extends<'a>(file_path: &Path, file: &'a str) -> Result<Cow<'a, str>> {
+    let value = serde_yaml::from_str::<Value>(file)
+        .map_err(blue_build_utils::serde_yaml_err(file))
+        .into_diagnostic()?;
+
+    if value.get(EXTENDS_KEY).is_none() && !has_anchors(&value) {
+        return Ok(Cow::Borrowed(file));
+    }
+
+    let mut value = merge_extends(value, Some(vec![canonical_path(file_path)]))?;
+
+    if let Some(Value::Sequence(modules)) = value.get_mut(MODULES_KEY) {
+        modules.retain(|module| anchor_name(module).is_none());
+    }
+
+    Ok(Cow::Owned(serde_yaml::to_string(&value).into_diagnostic()?))
+}
+
+fn merge_extends(value: Value, traversed_files: Option<Vec<PathBuf>>) -> Result<Value> {
+    let Value::Mapping(mut recipe) = value else {
+        bail!("A recipe must be a map of properties");
+    };
+
+    let Some(extends) = recipe.remove(EXTENDS_KEY) else {
+        return Ok(Value::Mapping(recipe));
+    };
+    let Some(extends) = extends.as_str() else {
+        bail!("The `{EXTENDS_KEY}:` property must be a path to a recipe file");
+    };
+
+    let mut traversed_files = traversed_files.unwrap_or_default();
+    let file_path = base_recipe_path().join(extends);
+    let canonical_file_path = canonical_path(&file_path);
+
+    if traversed_files.contains(&canonical_file_path) {
+        bail!(
+            "{} File {} has already been parsed:\n{traversed_files:?}",
+            "Circular dependency detected!".bright_red(),
+            file_path.display().to_string().bold(),
+        );
+    }
+
+    traversed_files.push(canonical_file_path);
+
+    debug!("Extending recipe {}", file_path.display());
+    let base_file = fs::read_to_string(&file_path)
+        .into_diagnostic()
+        .with_context(|| format!("Failed to open {}", file_path.display()))?;
+    let base = serde_yaml::from_str::<Value>(&base_file)
+        .map_err(blue_build_utils::serde_yaml_err(&base_file))
+        .into_diagnostic()?;
+
+    let Value::Mapping(base) = merge_extends(base, Some(traversed_files))? else {
+        unreachable!("Merged recipes are always maps");
+    };
+
+    Ok(Value::Mapping(merge(base, recipe)?))
+}
+
+/// Merges a recipe into the recipe it extends.
+///
+/// - Properties set in the recipe override the base recipe.
+/// - `vars` are merged with the recipe's values taking precedence.
+/// - `stages` replace the base stage with the same name or are appended.
+/// - `modules` are appended to the end of the base modules. Modules
+///   listed after an `- anchor: <name>` entry are inserted at the
+///   matching anchor in the base modules instead.
+fn merge(mut base: Mapping, recipe: Mapping) -> Result<Mapping> {
+    trace!("merge({base:?}, {recipe:?})");
+
+    for (key, value) in recipe {
+        let merged = match (key.as_str(), base.remove(&key), value) {
+            (Some(VARS_KEY), Some(Value::Mapping(mut base_vars)), Value::Mapping(vars)) => {
+                base_vars.extend(vars);
+                Value::Mapping(base_vars)
+            }
+            (Some(STAGES_KEY), Some(Value::Sequence(base_stages)), Value::Sequence(stages)) => {
+                Value::Sequence(merge_stages(base_stages, stages))
+            }
+            (Some(MODULES_KEY), Some(Value::Sequence(base_modules)), Value::Sequence(modules)) => {
+                Value::Sequence(merge_modules(base_modules, modules)?)
+            }
+            (_, _, value) => value,
+        };
+        base.insert(key, merged);
+    }
+
+    Ok(base)
+}
+
+fn merge_stages(mut base_stages: Vec<Value>, stages: Vec<Value>) -> Vec<Value> {
+    for stage in stages {
+        let existing = stage.get("name").and_then(|name| {
+            base_stages
+                .iter()
+                .position(|base_stage| base_stage.get("name") == Some(name))
+        });
+
+        match existing {
+            Some(index) => base_stages[index] = stage,
+            None => base_stages.push(stage),
+        }
+    }
+    base_stages
+}
+
+fn merge_modules(mut base_modules: Vec<Value>, modules: Vec<Value>) -> Result<Vec<Value>> {
+    let mut appended = Vec::new();
+    let mut inserts: IndexMap<String, Vec<Value>> = IndexMap::new();
+    let mut current_anchor: Option<String> = None;
+
+    for module in modules {
+        if let Some(anchor) = anchor_name(&module) {
+            inserts.entry(anchor.to_string()).or_default();
+            current_anchor = Some(anchor.to_string());
+            continue;
+        }
+
+        match current_anchor {
+            Some(ref anchor) => inserts.entry(anchor.clone()).or_default().push(module),
+            None => appended.push(module),
+        }
+    }
+
+    for (anchor, modules) in inserts {
+        let Some(index) = base_modules
+            .iter()
+            .position(|module| anchor_name(module) == Some(&anchor))
+        else {
+            bail!(
+                "Anchor {} does not exist in the extended recipe",
+                anchor.bold()
+            );
+        };
+
+        // Inserting before the anchor keeps it available
+        // for any recipes further down the chain.
+        base_modules.splice(index..index, modules);
+    }
+
+    base_modules.extend(appended);
+    Ok(base_modules)
+}
+
+fn anchor_name(module: &Value) -> Option<&str> {
+    match module {
+        Value::Mapping(module) if module.len() == 1 => module.get(ANCHOR_KEY)?.as_str(),
+        _ => None,
+    }
+}
+
+fn has_anchors(recipe: &Value) -> bool {
+    recipe
+        .get(MODULES_KEY)
+        .and_then(Value::as_sequence)
+        .is_some_and(|modules| modules.iter().any(|module| anchor_name(module).is_some()))
+}
+
+fn canonical_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod test {
+    use serde_yaml::{Mapping, Value};
+
+    use super::merge;
+
+    fn mapping(yaml: &str) -> Mapping {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    const BASE: &str = r"
+name: base
+image-version: 40
+vars:
+  registry: ghcr.io/blue-build
+  editor: vim
+stages:
+  - name: one
+    from: alpine
+  - name: two
+    from: alpine
+modules:
+  - type: rpm-ostree
+  - anchor: packages
+  - type: signing
+";
+
+    #[test]
+    fn merge_recipe() {
+        let recipe = mapping(
+            r"
+name: gaming
+vars:
+  editor: micro
+stages:
+  - name: two
+    from: fedora
+  - name: three
+    from: fedora
+modules:
+  - type: default-flatpaks
+  - anchor: packages
+  - type: script
+  - type: files
+",
+        );
+
+        let merged = Value::Mapping(merge(mapping(BASE), recipe).unwrap());
+        let expected: Value = serde_yaml::from_str(
+            r"
+name: gaming
+image-version: 40
+vars:
+  registry: ghcr.io/blue-build
+  editor: micro
+stages:
+  - name: one
+    from: alpine
+  - name: two
+    from: fedora
+  - name: three
+    from: fedora
+modules:
+  - type: rpm-ostree
+  - type: script
+  - type: files
+  - anchor: packages
+  - type: signing
+  - type: default-flatpaks
+",
+        )
+        .unwrap();
+
+        assert_eq!(merged, expected);
+    }
+
+    #[test]
+    fn merge_missing_anchor() {
+        let recipe = mapping(
+            r"
+modules:
+  - anchor: missing
+  - type: script
+",
+        );
+
+        merge(mapping(BASE), recipe).unwrap_err();
+    }
+}
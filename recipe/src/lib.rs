@@ -1,9 +1,13 @@
 pub mod akmods_info;
+mod extends;
+pub mod lockfile;
 pub mod module;
 pub mod module_ext;
 pub mod recipe;
+pub mod secret;
 pub mod stage;
 pub mod stages_ext;
+pub mod vars;
 
 use std::path::{Path, PathBuf};
 
@@ -11,11 +15,14 @@ use blue_build_utils::constants::{CONFIG_PATH, RECIPE_PATH};
 use log::warn;
 
 pub use akmods_info::*;
+pub use lockfile::*;
 pub use module::*;
 pub use module_ext::*;
 pub use recipe::*;
+pub use secret::*;
 pub use stage::*;
 pub use stages_ext::*;
+pub use vars::*;
 
 pub trait FromFileList {
     const LIST_KEY: &str;
@@ -0,0 +1,179 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use log::{debug, trace};
+use miette::{Context, IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
+
+const LOCKFILE_HEADER: &str = "# This file is generated by `bluebuild lock`.\n\
+    # Run `bluebuild lock --update` to update the pinned digests.\n";
+
+/// The digests that the images pulled
+/// when building recipes are pinned to.
+///
+/// This is written to `bluebuild.lock` by `bluebuild lock`
+/// so that an image can be rebuilt from the same images later.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Lockfile {
+    /// The build scripts image that was
+    /// resolved when the recipes were locked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_scripts_image: Option<String>,
+
+    /// The pinned images keyed by the
+    /// reference used in the `Containerfile`.
+    #[serde(default)]
+    pub images: BTreeMap<String, LockedImage>,
+}
+
+/// An image pinned to a digest.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct LockedImage {
+    pub digest: String,
+
+    /// The major version of the OS for base images.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os_version: Option<u64>,
+}
+
+impl Lockfile {
+    /// Reads the lockfile, returning an empty
+    /// lockfile if one doesn't exist.
+    ///
+    /// # Errors
+    /// Will error if the file can't be read or deserialized.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        trace!("Lockfile::load({})", path.display());
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        debug!("Using lockfile {}", path.display());
+        let file = fs::read_to_string(path)
+            .into_diagnostic()
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        serde_yaml::from_str(&file)
+            .map_err(blue_build_utils::serde_yaml_err(&file))
+            .into_diagnostic()
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Writes the lockfile.
+    ///
+    /// # Errors
+    /// Will error if the lockfile can't be serialized or written.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        trace!("Lockfile::write({})", path.display());
+
+        let contents = serde_yaml::to_string(self).into_diagnostic()?;
+        fs::write(path, format!("{LOCKFILE_HEADER}{contents}"))
+            .into_diagnostic()
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    #[must_use]
+    pub fn get(&self, image: &str) -> Option<&LockedImage> {
+        self.images.get(image)
+    }
+
+    /// The reference to use for an image in the `Containerfile`.
+    ///
+    /// Pinned images are referenced by their digest,
+    /// any other image is returned as is.
+    #[must_use]
+    pub fn image_ref<S: AsRef<str>>(&self, image: S) -> String {
+        let image = image.as_ref();
+
+        self.get(image).map_or_else(
+            || image.to_string(),
+            |locked| format!("{}@{}", strip_tag(image), locked.digest),
+        )
+    }
+}
+
+/// Removes the tag from an image reference.
+fn strip_tag(image: &str) -> &str {
+    let name_start = image.rfind('/').map_or(0, |i| i + 1);
+
+    image[name_start..]
+        .rfind(':')
+        .map_or(image, |i| &image[..name_start + i])
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::{LockedImage, Lockfile};
+
+    const DIGEST: &str = "sha256:1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c8d9e0f1a2b";
+
+    fn lockfile() -> Lockfile {
+        Lockfile {
+            build_scripts_image: Some(String::from("ghcr.io/blue-build/cli/build-scripts:v0.9.0")),
+            images: BTreeMap::from([
+                (
+                    String::from("ghcr.io/ublue-os/silverblue-main:40"),
+                    LockedImage {
+                        digest: DIGEST.to_string(),
+                        os_version: Some(40),
+                    },
+                ),
+                (
+                    String::from("localhost:5000/cosign"),
+                    LockedImage {
+                        digest: DIGEST.to_string(),
+                        os_version: None,
+                    },
+                ),
+            ]),
+        }
+    }
+
+    #[test]
+    fn image_refs() {
+        let lockfile = lockfile();
+
+        assert_eq!(
+            lockfile.image_ref("ghcr.io/ublue-os/silverblue-main:40"),
+            format!("ghcr.io/ublue-os/silverblue-main@{DIGEST}")
+        );
+        assert_eq!(
+            lockfile.image_ref("localhost:5000/cosign"),
+            format!("localhost:5000/cosign@{DIGEST}")
+        );
+        assert_eq!(
+            lockfile.image_ref("ghcr.io/blue-build/modules:latest"),
+            "ghcr.io/blue-build/modules:latest"
+        );
+    }
+
+    #[test]
+    fn serialization() {
+        let lockfile = lockfile();
+        let serialized = serde_yaml::to_string(&lockfile).unwrap();
+
+        assert_eq!(
+            serialized,
+            format!(
+                "build-scripts-image: ghcr.io/blue-build/cli/build-scripts:v0.9.0
+images:
+  ghcr.io/ublue-os/silverblue-main:40:
+    digest: {DIGEST}
+    os-version: 40
+  localhost:5000/cosign:
+    digest: {DIGEST}
+"
+            )
+        );
+        assert_eq!(
+            serde_yaml::from_str::<Lockfile>(&serialized).unwrap(),
+            lockfile
+        );
+    }
+}
@@ -9,7 +9,7 @@ use miette::{bail, Result};
 use serde::{Deserialize, Serialize};
 use serde_yaml::Value;
 
-use crate::{base_recipe_path, AkmodsInfo, ModuleExt};
+use crate::{base_recipe_path, AkmodsInfo, ModuleExt, Vars};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Builder, Default)]
 pub struct ModuleRequiredFields<'a> {
@@ -25,6 +25,28 @@ pub struct ModuleRequiredFields<'a> {
     #[serde(rename = "no-cache", default, skip_serializing_if = "is_false")]
     pub no_cache: bool,
 
+    /// A condition that must be true for the module to be run.
+    ///
+    /// This is a jinja expression that is evaluated when templating
+    /// against `os_version`, `platform`, `arch`, `vars`, and `env`.
+    #[builder(into)]
+    #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
+    pub condition: Option<Cow<'a, str>>,
+
+    /// Whether the module's condition was false.
+    ///
+    /// Skipped modules aren't run, but are kept so
+    /// that they show up in the full recipe.
+    #[builder(default)]
+    #[serde(skip_deserializing, default, skip_serializing_if = "is_false")]
+    pub skipped: bool,
+
+    /// The ids of the recipe's `secrets:` to
+    /// mount when running the module.
+    #[builder(default, into)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<Cow<'a, str>>,
+
     #[serde(flatten)]
     #[builder(default, into)]
     pub config: IndexMap<String, Value>,
@@ -35,7 +57,38 @@ const fn is_false(b: &bool) -> bool {
     !*b
 }
 
+/// The fields of a module that are passed to `run_module.sh`.
+///
+/// This leaves out `if:`, `secrets:`, and whether the module was
+/// skipped since they're only used when templating, while they're
+/// still shown in the full recipe.
+#[derive(Serialize, Debug)]
+pub struct ModuleRunConfig<'b> {
+    #[serde(rename = "type")]
+    module_type: &'b str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<&'b str>,
+
+    #[serde(rename = "no-cache", skip_serializing_if = "is_false")]
+    no_cache: bool,
+
+    #[serde(flatten)]
+    config: &'b IndexMap<String, Value>,
+}
+
 impl<'a> ModuleRequiredFields<'a> {
+    /// The fields of the module to pass to `run_module.sh`.
+    #[must_use]
+    pub fn run_config(&self) -> ModuleRunConfig<'_> {
+        ModuleRunConfig {
+            module_type: &self.module_type,
+            source: self.source.as_deref(),
+            no_cache: self.no_cache,
+            config: &self.config,
+        }
+    }
+
     #[must_use]
     pub fn get_module_type_list(&'a self, typ: &str, list_key: &str) -> Option<Vec<String>> {
         if self.module_type == typ {
@@ -194,6 +247,7 @@ impl Module<'_> {
     /// property alongside `from-file:`.
     pub fn get_modules(
         modules: &[Self],
+        vars: &Vars,
         traversed_files: Option<Vec<PathBuf>>,
     ) -> Result<Vec<Self>> {
         let mut found_modules = vec![];
@@ -223,7 +277,8 @@ impl Module<'_> {
                         traversed_files.push(file_name.clone());
 
                         Self::get_modules(
-                            &ModuleExt::try_from(&file_name)?.modules,
+                            &ModuleExt::try_from_file(&file_name, vars)?.modules,
+                            vars,
                             Some(traversed_files),
                         )?
                     }
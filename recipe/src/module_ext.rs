@@ -9,7 +9,7 @@ use log::trace;
 use miette::{Context, IntoDiagnostic, Report, Result};
 use serde::{Deserialize, Serialize};
 
-use crate::{base_recipe_path, AkmodsInfo, FromFileList, Module};
+use crate::{base_recipe_path, AkmodsInfo, FromFileList, Module, Vars};
 
 #[derive(Default, Serialize, Clone, Deserialize, Debug, Builder)]
 pub struct ModuleExt<'a> {
@@ -41,11 +41,24 @@ impl TryFrom<&Path> for ModuleExt<'_> {
     type Error = Report;
 
     fn try_from(file_name: &Path) -> Result<Self> {
+        Self::try_from_file(file_name, &Vars::default())
+    }
+}
+
+impl ModuleExt<'_> {
+    /// Reads a module file relative to the recipe directory,
+    /// interpolating any variables used in it.
+    ///
+    /// # Errors
+    /// Will error if the file can't be read or deserialized,
+    /// or an undefined variable is used.
+    pub fn try_from_file(file_name: &Path, vars: &Vars) -> Result<Self> {
         let file_path = base_recipe_path().join(file_name);
 
         let file = fs::read_to_string(&file_path)
             .into_diagnostic()
             .with_context(|| format!("Failed to open {}", file_path.display()))?;
+        let file = vars.interpolate_file(&file)?;
 
         serde_yaml::from_str::<Self>(&file).map_or_else(
             |_| -> Result<Self> {
@@ -57,9 +70,7 @@ impl TryFrom<&Path> for ModuleExt<'_> {
             Ok,
         )
     }
-}
 
-impl ModuleExt<'_> {
     #[must_use]
     pub fn get_akmods_info_list(&self, os_version: &u64) -> Vec<AkmodsInfo> {
         trace!("get_akmods_image_list({self:#?}, {os_version})");
@@ -72,7 +83,7 @@ impl ModuleExt<'_> {
                 module
                     .required_fields
                     .as_ref()
-                    .is_some_and(|rf| rf.module_type == "akmods")
+                    .is_some_and(|rf| rf.module_type == "akmods" && !rf.skipped)
             })
             .filter_map(|module| {
                 Some(
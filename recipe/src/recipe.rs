@@ -1,12 +1,23 @@
-use std::{borrow::Cow, fs, path::Path};
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashSet},
+    fs,
+    path::Path,
+};
 
+use blue_build_utils::constants::{
+    BLUE_BUILD_IMAGE_REF, BLUE_BUILD_INSTALLER_TAG, COSIGN_BIN_IMAGE_REF, MODULES_IMAGE_REF,
+};
 use bon::Builder;
-use log::{debug, trace};
-use miette::{Context, IntoDiagnostic, Result};
+use log::{debug, info, trace};
+use miette::{bail, Context, IntoDiagnostic, Result};
 use oci_distribution::Reference;
 use serde::{Deserialize, Serialize};
 
-use crate::{Module, ModuleExt, StagesExt};
+use crate::{
+    extends, AkmodsInfo, Module, ModuleExt, ModuleRequiredFields, Secret, StagesExt, VarOverride,
+    Vars,
+};
 
 /// The build recipe.
 ///
@@ -16,6 +27,20 @@ use crate::{Module, ModuleExt, StagesExt};
 /// and tagging the image appropriately.
 #[derive(Default, Serialize, Clone, Deserialize, Debug, Builder)]
 pub struct Recipe<'a> {
+    /// The path of another recipe, relative to the recipes directory,
+    /// that this recipe extends.
+    ///
+    /// Properties set in this recipe override those of the extended recipe.
+    /// Modules are appended to the extended recipe's modules, or inserted
+    /// at a named `- anchor: <name>` entry when listed after one. Stages
+    /// replace a stage of the same name or are appended.
+    ///
+    /// This is merged away when parsing so it will
+    /// always be `None` on a parsed recipe.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(into)]
+    pub extends: Option<Cow<'a, str>>,
+
     /// The name of the user's image.
     ///
     /// This will be set on the `org.opencontainers.image.title` label.
@@ -54,6 +79,24 @@ pub struct Recipe<'a> {
     #[builder(into)]
     pub alt_tags: Option<Vec<String>>,
 
+    /// Secrets that modules can use during the build
+    /// without them being stored in the image.
+    ///
+    /// Each secret is read from an environment variable,
+    /// a file, or the credential helper of a registry.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[builder(default, into)]
+    pub secrets: Vec<Secret<'a>>,
+
+    /// Variables that can be used throughout the recipe
+    /// and any `from-file` with the `${{ name }}` syntax.
+    ///
+    /// After parsing, this holds the resolved values
+    /// including any overrides.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(into)]
+    pub vars: Option<Vars>,
+
     /// The stages extension of the recipe.
     ///
     /// This hold the list of stages that can
@@ -69,48 +112,202 @@ pub struct Recipe<'a> {
     pub modules_ext: ModuleExt<'a>,
 }
 
-impl<'a> Recipe<'a> {
+impl Recipe<'_> {
     /// Parse a recipe file
     ///
     /// # Errors
     /// Errors when a yaml file cannot be deserialized,
     /// or a linked module yaml file does not exist.
     pub fn parse<P: AsRef<Path>>(path: P) -> Result<Self> {
-        trace!("Recipe::parse({})", path.as_ref().display());
-
-        let file_path = if Path::new(path.as_ref()).is_absolute() {
-            path.as_ref().to_path_buf()
-        } else {
-            std::env::current_dir()
-                .into_diagnostic()?
-                .join(path.as_ref())
-        };
+        Self::parse_with_vars(path, &[])
+    }
 
-        let file = fs::read_to_string(&file_path)
-            .into_diagnostic()
-            .with_context(|| format!("Failed to read {}", file_path.display()))?;
+    /// Parse a recipe file, overriding the
+    /// recipe's variables with `overrides`.
+    ///
+    /// # Errors
+    /// Errors when a yaml file cannot be deserialized,
+    /// a linked module yaml file does not exist, or
+    /// an undefined variable is used.
+    pub fn parse_with_vars<P: AsRef<Path>>(path: P, overrides: &[VarOverride]) -> Result<Self> {
+        trace!(
+            "Recipe::parse_with_vars({}, {overrides:?})",
+            path.as_ref().display()
+        );
 
-        debug!("Recipe contents: {file}");
+        let (file, vars) = Self::resolve_file(path, overrides)?;
 
         let mut recipe = serde_yaml::from_str::<Recipe>(&file)
             .map_err(blue_build_utils::serde_yaml_err(&file))
             .into_diagnostic()?;
 
-        recipe.modules_ext.modules = Module::get_modules(&recipe.modules_ext.modules, None)?;
+        recipe.modules_ext.modules = Module::get_modules(&recipe.modules_ext.modules, &vars, None)?;
 
         #[cfg(feature = "stages")]
         if let Some(ref mut stages_ext) = recipe.stages_ext {
-            stages_ext.stages = crate::Stage::get_stages(&stages_ext.stages, None)?;
+            stages_ext.stages = crate::Stage::get_stages(&stages_ext.stages, &vars, None)?;
         }
 
+        recipe.vars = (!vars.is_empty()).then_some(vars);
+
         #[cfg(not(feature = "stages"))]
         {
             recipe.stages_ext = None;
         }
 
+        recipe.check_secrets()?;
+
         Ok(recipe)
     }
 
+    /// Checks that secret ids are unique and that
+    /// modules only use secrets declared by the recipe.
+    fn check_secrets(&self) -> Result<()> {
+        let mut ids = HashSet::new();
+        for secret in &self.secrets {
+            if !ids.insert(&*secret.id) {
+                bail!("The secret {} is declared more than once", secret.id);
+            }
+        }
+
+        for module in self.all_modules() {
+            if let Some(id) = module.secrets.iter().find(|id| !ids.contains(&***id)) {
+                bail!(
+                    "The {} module uses the secret {id} which isn't declared in the recipe's `secrets:`",
+                    module.module_type
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether any of the recipe's modules have an `if:` condition.
+    #[must_use]
+    pub fn has_module_conditions(&self) -> bool {
+        self.all_modules().any(|module| module.condition.is_some())
+    }
+
+    /// Marks the modules whose `if:` condition
+    /// is false as skipped so they aren't run.
+    ///
+    /// # Errors
+    /// Will error if a condition fails to be evaluated.
+    pub fn skip_modules<F>(&mut self, mut is_true: F) -> Result<()>
+    where
+        F: FnMut(&str) -> Result<bool>,
+    {
+        let stage_modules = self
+            .stages_ext
+            .iter_mut()
+            .flat_map(|stages_ext| &mut stages_ext.stages)
+            .filter_map(|stage| stage.required_fields.as_mut())
+            .flat_map(|stage| &mut stage.modules_ext.modules);
+
+        for module in self
+            .modules_ext
+            .modules
+            .iter_mut()
+            .chain(stage_modules)
+            .filter_map(|module| module.required_fields.as_mut())
+        {
+            if let Some(condition) = module.condition.as_deref() {
+                module.skipped = !is_true(condition)?;
+
+                if module.skipped {
+                    info!(
+                        "Skipping the {} module since `{condition}` is false",
+                        module.module_type
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The modules of the recipe and all of its stages.
+    pub fn all_modules(&self) -> impl Iterator<Item = &ModuleRequiredFields<'_>> {
+        std::iter::once(&self.modules_ext)
+            .chain(
+                self.stages_ext
+                    .iter()
+                    .flat_map(|stages_ext| &stages_ext.stages)
+                    .filter_map(|stage| stage.required_fields.as_ref())
+                    .map(|stage| &stage.modules_ext),
+            )
+            .flat_map(|modules_ext| &modules_ext.modules)
+            .filter_map(|module| module.required_fields.as_ref())
+    }
+
+    /// Reads a recipe file and resolves it into its final form.
+    ///
+    /// Any recipes it `extends` are merged in and its variables
+    /// are interpolated. The resolved contents are returned
+    /// along with the variables that were used.
+    ///
+    /// # Errors
+    /// Errors when a recipe file cannot be read or deserialized,
+    /// the `extends` chain is circular, or an undefined variable is used.
+    pub fn resolve_file<P: AsRef<Path>>(
+        path: P,
+        overrides: &[VarOverride],
+    ) -> Result<(String, Vars)> {
+        trace!(
+            "Recipe::resolve_file({}, {overrides:?})",
+            path.as_ref().display()
+        );
+
+        let (file, vars) = Self::resolve_source(path, overrides)?;
+        let file = vars.interpolate_file(&file)?.into_owned();
+
+        Ok((file, vars))
+    }
+
+    /// Reads a recipe file and merges in any recipes it `extends`
+    /// without interpolating its variables.
+    ///
+    /// The contents are the same as the file on disk if the recipe
+    /// doesn't extend another recipe, which allows locations in the
+    /// contents to be reported against the user's file.
+    ///
+    /// # Errors
+    /// Errors when a recipe file cannot be read or deserialized,
+    /// the `extends` chain is circular, or the `vars:` section is invalid.
+    pub fn resolve_source<P: AsRef<Path>>(
+        path: P,
+        overrides: &[VarOverride],
+    ) -> Result<(String, Vars)> {
+        trace!(
+            "Recipe::resolve_source({}, {overrides:?})",
+            path.as_ref().display()
+        );
+
+        let file_path = if Path::new(path.as_ref()).is_absolute() {
+            path.as_ref().to_path_buf()
+        } else {
+            std::env::current_dir()
+                .into_diagnostic()?
+                .join(path.as_ref())
+        };
+
+        let file = fs::read_to_string(&file_path)
+            .into_diagnostic()
+            .with_context(|| format!("Failed to read {}", file_path.display()))?;
+
+        debug!("Recipe contents: {file}");
+
+        let file = extends::resolve_extends(&file_path, &file)?.into_owned();
+
+        let vars = Vars::resolve(
+            serde_yaml::from_str::<serde_yaml::Value>(&file)
+                .map_err(blue_build_utils::serde_yaml_err(&file))
+                .into_diagnostic()?
+                .get("vars"),
+            overrides,
+        )?;
+
+        Ok((file, vars))
+    }
+
     /// Get a `Reference` object of the `base_image`.
     ///
     /// # Errors
@@ -122,4 +319,221 @@ impl<'a> Recipe<'a> {
             .into_diagnostic()
             .with_context(|| format!("Unable to parse base image {base_image}"))
     }
+
+    /// The image that the `bluebuild` binary
+    /// installed in the image is copied from.
+    #[must_use]
+    pub fn blue_build_image(&self) -> String {
+        format!(
+            "{BLUE_BUILD_IMAGE_REF}:{}",
+            self.blue_build_tag
+                .as_deref()
+                .unwrap_or(BLUE_BUILD_INSTALLER_TAG)
+        )
+    }
+
+    /// The references of the images other than the base image that
+    /// a published image is checked against for updates.
+    ///
+    /// This is the modules image and the akmods images.
+    #[must_use]
+    pub fn update_image_refs(&self, os_version: u64) -> Vec<String> {
+        std::iter::once(MODULES_IMAGE_REF.to_string())
+            .chain(
+                self.modules_ext
+                    .get_akmods_info_list(&os_version)
+                    .iter()
+                    .flat_map(AkmodsInfo::image_refs),
+            )
+            .collect()
+    }
+
+    /// The references of every image pulled when building the
+    /// recipe, other than the base image and build scripts.
+    ///
+    /// Stages are referenced by name and so are left out.
+    #[must_use]
+    pub fn image_refs(&self, os_version: u64) -> BTreeSet<String> {
+        let stages = self
+            .stages_ext
+            .iter()
+            .flat_map(|stages_ext| &stages_ext.stages)
+            .filter_map(|stage| stage.required_fields.as_ref())
+            .collect::<Vec<_>>();
+        let stage_names = stages
+            .iter()
+            .map(|stage| &*stage.name)
+            .collect::<HashSet<_>>();
+        let is_image = |image: &&str| *image != "scratch" && !stage_names.contains(image);
+
+        let modules = std::iter::once(&self.modules_ext)
+            .chain(stages.iter().map(|stage| &stage.modules_ext))
+            .flat_map(|modules_ext| &modules_ext.modules)
+            .filter_map(|module| module.required_fields.as_ref());
+
+        self.update_image_refs(os_version)
+            .into_iter()
+            .chain([COSIGN_BIN_IMAGE_REF.to_string(), self.blue_build_image()])
+            .chain(
+                stages
+                    .iter()
+                    .map(|stage| &*stage.from)
+                    .filter(is_image)
+                    .map(ToString::to_string),
+            )
+            .chain(modules.flat_map(|module| {
+                module
+                    .get_non_local_source()
+                    .into_iter()
+                    .chain(module.get_copy_args().and_then(|(from, _, _)| from))
+                    .filter(is_image)
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+            }))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use crate::SecretSource;
+
+    use super::Recipe;
+
+    const RECIPE: &str = r"
+name: test
+description: A test image
+base-image: ghcr.io/ublue-os/silverblue-main
+image-version: 40
+stages:
+  - name: builder
+    from: docker.io/library/rust:1
+    modules:
+      - type: script
+        source: ghcr.io/example/modules:v1
+  - name: empty
+    from: scratch
+modules:
+  - type: akmods
+  - type: script
+  - type: files
+    source: local
+";
+
+    #[test]
+    fn image_refs() {
+        let recipe: Recipe = serde_yaml::from_str(RECIPE).unwrap();
+
+        assert_eq!(
+            recipe.image_refs(40).into_iter().collect::<Vec<_>>(),
+            [
+                "docker.io/library/rust:1",
+                "gcr.io/projectsigstore/cosign",
+                "ghcr.io/blue-build/cli:latest-installer",
+                "ghcr.io/blue-build/modules:latest",
+                "ghcr.io/example/modules:v1",
+                "ghcr.io/ublue-os/akmods-extra:main-40",
+                "ghcr.io/ublue-os/akmods:main-40",
+            ]
+        );
+    }
+
+    #[test]
+    fn secrets() {
+        let recipe: Recipe = serde_yaml::from_str(&format!(
+            "{RECIPE}
+secrets:
+  - id: license-key
+    env: LICENSE_KEY
+  - id: repo-token
+    file: ./token
+  - id: registry-token
+    credential-helper: ghcr.io
+"
+        ))
+        .unwrap();
+
+        assert_eq!(
+            recipe
+                .secrets
+                .iter()
+                .map(|secret| (&*secret.id, &secret.source))
+                .collect::<Vec<_>>(),
+            [
+                ("license-key", &SecretSource::Env("LICENSE_KEY".into())),
+                ("repo-token", &SecretSource::File(PathBuf::from("./token"))),
+                (
+                    "registry-token",
+                    &SecretSource::CredentialHelper("ghcr.io".into())
+                ),
+            ]
+        );
+        assert!(recipe.check_secrets().is_ok());
+
+        let recipe: Recipe = serde_yaml::from_str(&format!(
+            "{RECIPE}  - type: script
+    secrets: [license-key]
+"
+        ))
+        .unwrap();
+        assert!(recipe.check_secrets().is_err());
+    }
+
+    #[test]
+    fn skip_modules() {
+        let mut recipe: Recipe = serde_yaml::from_str(&format!(
+            "{RECIPE}  - type: rpm-ostree
+    if: os_version >= 41
+"
+        ))
+        .unwrap();
+        assert!(recipe.has_module_conditions());
+
+        recipe
+            .skip_modules(|condition| Ok(condition != "os_version >= 41"))
+            .unwrap();
+        assert_eq!(
+            recipe
+                .all_modules()
+                .filter(|module| module.skipped)
+                .map(|module| &*module.module_type)
+                .collect::<Vec<_>>(),
+            ["rpm-ostree"]
+        );
+        assert_eq!(recipe.modules_ext.get_akmods_info_list(&40).len(), 1);
+    }
+
+    #[test]
+    fn module_run_config() {
+        let mut recipe: Recipe = serde_yaml::from_str(&format!(
+            "{RECIPE}  - type: rpm-ostree
+    if: os_version >= 41
+    secrets: [repo-token]
+    install: [micro]
+"
+        ))
+        .unwrap();
+        recipe.skip_modules(|_| Ok(false)).unwrap();
+
+        let module = recipe
+            .all_modules()
+            .find(|module| module.module_type == "rpm-ostree")
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(module.run_config()).unwrap(),
+            serde_json::json!({ "type": "rpm-ostree", "install": ["micro"] })
+        );
+        assert_eq!(
+            serde_json::to_value(module).unwrap(),
+            serde_json::json!({
+                "type": "rpm-ostree",
+                "if": "os_version >= 41",
+                "skipped": true,
+                "secrets": ["repo-token"],
+                "install": ["micro"],
+            })
+        );
+    }
 }
@@ -0,0 +1,36 @@
+use std::{borrow::Cow, path::PathBuf};
+
+use bon::Builder;
+use serde::{Deserialize, Serialize};
+
+/// A secret that modules can use during the build
+/// without it being stored in the image.
+///
+/// Modules that list the secret's `id` in their `secrets:`
+/// can read it from `/run/secrets/<id>`.
+#[derive(Serialize, Deserialize, Debug, Clone, Builder, PartialEq, Eq)]
+pub struct Secret<'a> {
+    /// The id modules use to reference the secret.
+    #[builder(into)]
+    pub id: Cow<'a, str>,
+
+    /// Where the value of the secret is read from.
+    #[serde(flatten)]
+    pub source: SecretSource<'a>,
+}
+
+/// Where the value of a secret is read from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SecretSource<'a> {
+    /// The name of an environment variable.
+    Env(Cow<'a, str>),
+
+    /// The path of a file, relative to
+    /// where `bluebuild` is run.
+    File(PathBuf),
+
+    /// The registry whose password or token is read
+    /// from the docker or podman credential helper.
+    CredentialHelper(Cow<'a, str>),
+}
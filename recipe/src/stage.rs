@@ -6,7 +6,7 @@ use colored::Colorize;
 use miette::{bail, Result};
 use serde::{Deserialize, Serialize};
 
-use crate::{base_recipe_path, Module, ModuleExt, StagesExt};
+use crate::{base_recipe_path, Module, ModuleExt, StagesExt, Vars};
 
 /// Contains the required fields for a stage.
 #[derive(Serialize, Deserialize, Debug, Clone, Builder)]
@@ -93,7 +93,11 @@ impl Stage<'_> {
     /// Will error if the stage cannot be
     /// deserialized or the user uses another
     /// property alongside `from-file:`.
-    pub fn get_stages(stages: &[Self], traversed_files: Option<Vec<PathBuf>>) -> Result<Vec<Self>> {
+    pub fn get_stages(
+        stages: &[Self],
+        vars: &Vars,
+        traversed_files: Option<Vec<PathBuf>>,
+    ) -> Result<Vec<Self>> {
         let mut found_stages = vec![];
         let traversed_files = traversed_files.unwrap_or_default();
 
@@ -119,7 +123,11 @@ impl Stage<'_> {
                         let mut tf = traversed_files.clone();
                         tf.push(file_name.clone());
 
-                        Self::get_stages(&StagesExt::try_from(&file_name)?.stages, Some(tf))?
+                        Self::get_stages(
+                            &StagesExt::try_from_file(&file_name, vars)?.stages,
+                            vars,
+                            Some(tf),
+                        )?
                     }
                     _ => {
                         let from_example = Stage::builder().from_file("path/to/stage.yml").build();
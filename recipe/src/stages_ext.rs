@@ -7,7 +7,7 @@ use bon::Builder;
 use miette::{Context, IntoDiagnostic, Report, Result};
 use serde::{Deserialize, Serialize};
 
-use crate::{base_recipe_path, FromFileList, Module, Stage};
+use crate::{base_recipe_path, FromFileList, Module, Stage, Vars};
 
 #[derive(Default, Serialize, Clone, Deserialize, Debug, Builder)]
 pub struct StagesExt<'a> {
@@ -51,11 +51,24 @@ impl TryFrom<&Path> for StagesExt<'_> {
     type Error = Report;
 
     fn try_from(file_name: &Path) -> Result<Self> {
+        Self::try_from_file(file_name, &Vars::default())
+    }
+}
+
+impl StagesExt<'_> {
+    /// Reads a stage file relative to the recipe directory,
+    /// interpolating any variables used in it.
+    ///
+    /// # Errors
+    /// Will error if the file can't be read or deserialized,
+    /// or an undefined variable is used.
+    pub fn try_from_file(file_name: &Path, vars: &Vars) -> Result<Self> {
         let file_path = base_recipe_path().join(file_name);
 
         let file = fs::read_to_string(&file_path)
             .into_diagnostic()
             .with_context(|| format!("Failed to open {}", file_path.display()))?;
+        let file = vars.interpolate_file(&file)?;
 
         serde_yaml::from_str::<Self>(&file).map_or_else(
             |_| -> Result<Self> {
@@ -63,7 +76,8 @@ impl TryFrom<&Path> for StagesExt<'_> {
                     .map_err(blue_build_utils::serde_yaml_err(&file))
                     .into_diagnostic()?;
                 if let Some(ref mut rf) = stage.required_fields {
-                    rf.modules_ext.modules = Module::get_modules(&rf.modules_ext.modules, None)?;
+                    rf.modules_ext.modules =
+                        Module::get_modules(&rf.modules_ext.modules, vars, None)?;
                 }
                 Ok(Self::builder().stages(vec![stage]).build())
             },
@@ -73,7 +87,7 @@ impl TryFrom<&Path> for StagesExt<'_> {
                 for stage in &mut stages {
                     if let Some(ref mut rf) = stage.required_fields {
                         rf.modules_ext.modules =
-                            Module::get_modules(&rf.modules_ext.modules, None)?;
+                            Module::get_modules(&rf.modules_ext.modules, vars, None)?;
                     }
                 }
                 stages_ext.stages = stages;
@@ -0,0 +1,322 @@
+use std::{borrow::Cow, str::FromStr};
+
+use blue_build_utils::constants::BB_VAR_PREFIX;
+use colored::Colorize;
+use indexmap::IndexMap;
+use log::trace;
+use miette::{bail, miette, IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+#[cfg(not(test))]
+use blue_build_utils::get_env_var;
+#[cfg(test)]
+use blue_build_utils::test_utils::get_env_var;
+
+const VAR_START: &str = "${{";
+const VAR_END: &str = "}}";
+const VARS_KEY: &str = "vars";
+
+/// A `key=value` pair used to override a variable
+/// in the `vars:` section of a recipe.
+///
+/// The value is parsed as yaml which allows
+/// passing numbers and lists as well as strings.
+#[derive(Debug, Clone)]
+pub struct VarOverride {
+    pub key: String,
+    pub value: Value,
+}
+
+impl FromStr for VarOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Variable '{s}' must be in the format KEY=VALUE"))?;
+
+        if key.is_empty() {
+            return Err(format!("Variable '{s}' is missing a name"));
+        }
+
+        Ok(Self {
+            key: key.to_string(),
+            value: parse_value(value),
+        })
+    }
+}
+
+/// The variables that can be interpolated into
+/// a recipe using the `${{ name }}` syntax.
+#[derive(Default, Serialize, Clone, Deserialize, Debug)]
+#[serde(transparent)]
+pub struct Vars(IndexMap<String, Value>);
+
+impl Vars {
+    /// Resolves the final set of variables for a recipe.
+    ///
+    /// Each variable declared in the recipe can be overridden
+    /// by an env var with the `BB_VAR_` prefix followed by the
+    /// uppercased name (e.g. `BB_VAR_FEDORA_VERSION`). Any
+    /// `overrides` given on the CLI take precedence over both.
+    ///
+    /// # Errors
+    /// Will error if the `vars:` section is not a map of names to values.
+    pub fn resolve(recipe_vars: Option<&Value>, overrides: &[VarOverride]) -> Result<Self> {
+        trace!("Vars::resolve({recipe_vars:?}, {overrides:?})");
+
+        let mut vars = match recipe_vars {
+            None | Some(Value::Null) => IndexMap::new(),
+            Some(Value::Mapping(map)) => map
+                .iter()
+                .map(|(key, value)| {
+                    let Some(key) = key.as_str() else {
+                        bail!("Variable names must be strings, found {key:?}");
+                    };
+                    Ok((key.to_string(), value.clone()))
+                })
+                .collect::<Result<_>>()?,
+            Some(_) => {
+                bail!("The `{VARS_KEY}:` section must be a map of names to values");
+            }
+        };
+
+        for (key, value) in &mut vars {
+            if let Ok(env_value) = get_env_var(env_var_name(key).as_str()) {
+                trace!("Overriding variable {key} from the environment");
+                *value = parse_value(&env_value);
+            }
+        }
+
+        for VarOverride { key, value } in overrides {
+            vars.insert(key.clone(), value.clone());
+        }
+
+        Ok(Self(vars))
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Interpolates variables into the contents of a yaml file.
+    ///
+    /// The contents are returned untouched if there is nothing to
+    /// interpolate, otherwise the interpolated yaml is re-serialized.
+    /// A top-level `vars:` key is left as is.
+    ///
+    /// # Errors
+    /// Will error if the file isn't valid yaml or references
+    /// a variable that doesn't exist.
+    pub fn interpolate_file<'a>(&self, file: &'a str) -> Result<Cow<'a, str>> {
+        if !file.contains(VAR_START) {
+            return Ok(Cow::Borrowed(file));
+        }
+
+        let mut value = serde_yaml::from_str::<Value>(file)
+            .map_err(blue_build_utils::serde_yaml_err(file))
+            .into_diagnostic()?;
+        self.interpolate_document(&mut value)?;
+
+        Ok(Cow::Owned(serde_yaml::to_string(&value).into_diagnostic()?))
+    }
+
+    /// Interpolates variables into a parsed yaml file.
+    ///
+    /// This only changes the values of the document and keeps
+    /// the paths of any existing keys intact. A top-level
+    /// `vars:` key is left as is.
+    ///
+    /// # Errors
+    /// Will error if the document references a variable that doesn't exist.
+    pub fn interpolate_document(&self, value: &mut Value) -> Result<()> {
+        if let Value::Mapping(map) = value {
+            for (key, value) in map.iter_mut() {
+                if key.as_str() != Some(VARS_KEY) {
+                    self.interpolate(value)?;
+                }
+            }
+            Ok(())
+        } else {
+            self.interpolate(value)
+        }
+    }
+
+    /// Interpolates all variables found in the string values of `value`.
+    ///
+    /// A string that consists of only a single variable is replaced by the
+    /// variable's value as is, which allows lists and maps to be used.
+    ///
+    /// # Errors
+    /// Will error if a variable doesn't exist or a list
+    /// or map is interpolated into the middle of a string.
+    pub fn interpolate(&self, value: &mut Value) -> Result<()> {
+        match value {
+            Value::String(string) => {
+                if let Some(new_value) = self.interpolate_str(string)? {
+                    *value = new_value;
+                }
+            }
+            Value::Sequence(sequence) => {
+                for value in sequence {
+                    self.interpolate(value)?;
+                }
+            }
+            Value::Mapping(map) => {
+                for (_, value) in map.iter_mut() {
+                    self.interpolate(value)?;
+                }
+            }
+            Value::Tagged(tagged) => self.interpolate(&mut tagged.value)?,
+            Value::Null | Value::Bool(_) | Value::Number(_) => {}
+        }
+        Ok(())
+    }
+
+    fn interpolate_str(&self, string: &str) -> Result<Option<Value>> {
+        if !string.contains(VAR_START) {
+            return Ok(None);
+        }
+
+        let trimmed = string.trim();
+        if trimmed.starts_with(VAR_START)
+            && trimmed.ends_with(VAR_END)
+            && trimmed.matches(VAR_START).count() == 1
+        {
+            let name = trimmed[VAR_START.len()..trimmed.len() - VAR_END.len()].trim();
+            return Ok(Some(self.get(name)?.clone()));
+        }
+
+        let mut interpolated = String::with_capacity(string.len());
+        let mut rest = string;
+
+        while let Some(start) = rest.find(VAR_START) {
+            interpolated.push_str(&rest[..start]);
+
+            let after_start = &rest[start + VAR_START.len()..];
+            let Some(end) = after_start.find(VAR_END) else {
+                bail!("Missing closing `{VAR_END}` for variable in '{string}'");
+            };
+            let name = after_start[..end].trim();
+
+            match self.get(name)? {
+                Value::String(value) => interpolated.push_str(value),
+                Value::Number(value) => interpolated.push_str(&value.to_string()),
+                Value::Bool(value) => interpolated.push_str(&value.to_string()),
+                _ => {
+                    bail!(
+                        "Variable {} must be a string, number, or boolean to be used within '{string}'",
+                        name.bold(),
+                    );
+                }
+            }
+
+            rest = &after_start[end + VAR_END.len()..];
+        }
+        interpolated.push_str(rest);
+
+        Ok(Some(Value::String(interpolated)))
+    }
+
+    fn get(&self, name: &str) -> Result<&Value> {
+        self.0.get(name).ok_or_else(|| {
+            miette!(
+                "Variable {} is not defined. Available variables: {:?}",
+                name.bold(),
+                self.0.keys().collect::<Vec<_>>(),
+            )
+        })
+    }
+}
+
+fn env_var_name(key: &str) -> String {
+    format!("{BB_VAR_PREFIX}{}", key.to_uppercase().replace('-', "_"))
+}
+
+fn parse_value(value: &str) -> Value {
+    match serde_yaml::from_str::<Value>(value) {
+        Ok(Value::Null) | Err(_) => Value::String(value.to_string()),
+        Ok(value) => value,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use blue_build_utils::test_utils::set_env_var;
+    use rstest::rstest;
+    use serde_yaml::Value;
+
+    use super::{VarOverride, Vars};
+
+    const RECIPE_VARS: &str = r"
+fedora-version: 40
+registry: ghcr.io/blue-build
+packages:
+  - micro
+  - htop
+";
+
+    fn vars(overrides: &[VarOverride]) -> Vars {
+        let recipe_vars: Value = serde_yaml::from_str(RECIPE_VARS).unwrap();
+        Vars::resolve(Some(&recipe_vars), overrides).unwrap()
+    }
+
+    #[rstest]
+    #[case("${{ fedora-version }}", "40")]
+    #[case("${{fedora-version}}", "40")]
+    #[case("${{ registry }}/base", "ghcr.io/blue-build/base")]
+    #[case(
+        "${{ registry }}/base:${{ fedora-version }}",
+        "ghcr.io/blue-build/base:40"
+    )]
+    #[case("${{ packages }}", "[micro, htop]")]
+    #[case("no vars here", "no vars here")]
+    fn interpolate(#[case] input: &str, #[case] expected: &str) {
+        let mut value = Value::String(input.to_string());
+        vars(&[]).interpolate(&mut value).unwrap();
+
+        assert_eq!(value, serde_yaml::from_str::<Value>(expected).unwrap());
+    }
+
+    #[rstest]
+    #[case("${{ missing }}")]
+    #[case("${{ fedora-version ")]
+    #[case("install ${{ packages }}")]
+    fn interpolate_err(#[case] input: &str) {
+        let mut value = Value::String(input.to_string());
+        vars(&[]).interpolate(&mut value).unwrap_err();
+    }
+
+    #[test]
+    fn overrides() {
+        set_env_var("BB_VAR_REGISTRY", "quay.io/blue-build");
+
+        let vars = vars(&[
+            "fedora-version=41".parse().unwrap(),
+            "packages=[fish]".parse().unwrap(),
+        ]);
+
+        let mut value: Value = serde_yaml::from_str(
+            "[\"${{ registry }}:${{ fedora-version }}\", \"${{ packages }}\"]",
+        )
+        .unwrap();
+        vars.interpolate(&mut value).unwrap();
+
+        assert_eq!(
+            value,
+            serde_yaml::from_str::<Value>("[\"quay.io/blue-build:41\", [fish]]").unwrap()
+        );
+    }
+
+    #[test]
+    fn interpolate_file_skips_vars() {
+        let file = "vars:\n  name: \"${{ name }}\"\nname: ${{ registry }}\n";
+        let file = vars(&[]).interpolate_file(file).unwrap();
+
+        let value: Value = serde_yaml::from_str(&file).unwrap();
+        assert_eq!(value["name"], Value::from("ghcr.io/blue-build"));
+        assert_eq!(value["vars"]["name"], Value::from("${{ name }}"));
+    }
+}
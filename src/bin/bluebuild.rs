@@ -31,6 +31,12 @@ fn main() {
         #[cfg(feature = "switch")]
         CommandArgs::Switch(mut command) => command.run(),
 
+        #[cfg(feature = "switch")]
+        CommandArgs::Status(mut command) => command.run(),
+
+        #[cfg(feature = "switch")]
+        CommandArgs::Rollback(mut command) => command.run(),
+
         #[cfg(feature = "login")]
         CommandArgs::Login(mut command) => command.run(),
 
@@ -43,9 +49,27 @@ fn main() {
         #[cfg(feature = "iso")]
         CommandArgs::GenerateIso(mut command) => command.run(),
 
+        #[cfg(feature = "disk")]
+        CommandArgs::GenerateDisk(mut command) => command.run(),
+
         #[cfg(feature = "validate")]
         CommandArgs::Validate(mut command) => command.run(),
 
+        #[cfg(feature = "lsp")]
+        CommandArgs::Lsp(mut command) => command.run(),
+
+        #[cfg(feature = "verify")]
+        CommandArgs::Verify(mut command) => command.run(),
+
+        #[cfg(feature = "diff")]
+        CommandArgs::Diff(mut command) => command.run(),
+
+        #[cfg(feature = "check-updates")]
+        CommandArgs::CheckUpdates(mut command) => command.run(),
+
+        #[cfg(feature = "lock")]
+        CommandArgs::Lock(mut command) => command.run(),
+
         #[cfg(feature = "prune")]
         CommandArgs::Prune(mut command) => command.run(),
 
@@ -0,0 +1,166 @@
+use std::{collections::HashMap, fmt::Debug, path::Path, process::Command};
+
+use blue_build_utils::constants::{BUILD_ID_LABEL, IMAGE_TITLE_LABEL, IMAGE_VERSION_LABEL};
+use log::{debug, trace};
+use miette::Result;
+use serde::Deserialize;
+
+use crate::{bootc_status::BootcStatus, rpm_ostree_status::RpmOstreeStatus};
+
+/// The status of the deployments managed by
+/// the tool that updates the host.
+pub trait BootStatus: Debug {
+    /// The name of the tool managing the deployments.
+    fn name(&self) -> &'static str;
+
+    /// Whether the commands need to be run with `sudo`.
+    fn requires_sudo(&self) -> bool;
+
+    /// Checks if there is a transaction in progress.
+    fn transaction_in_progress(&self) -> bool;
+
+    /// Get the booted deployment.
+    fn booted_deployment(&self) -> Option<Deployment>;
+
+    /// Get the deployment staged for the next boot.
+    fn staged_deployment(&self) -> Option<Deployment>;
+
+    /// Get the deployment that would be booted
+    /// after a rollback.
+    fn rollback_deployment(&self) -> Option<Deployment>;
+
+    /// Creates the command to upgrade the host
+    /// to the latest version of the booted image.
+    fn upgrade_command(&self, reboot: bool) -> Command;
+
+    /// Creates the command to switch the host
+    /// to an image stored in an `oci-archive`.
+    fn switch_archive_command(&self, archive_path: &Path, reboot: bool) -> Command;
+
+    /// The reference of a signed image in a registry
+    /// as it's shown for the deployment.
+    fn signed_image_ref(&self, image: &str) -> String;
+
+    /// Creates the command to switch the host to a signed
+    /// image in a registry, verifying it with the host's
+    /// signature policy.
+    fn switch_signed_image_command(&self, image: &str, reboot: bool) -> Command;
+
+    /// Creates the command to boot into
+    /// the rollback deployment.
+    fn rollback_command(&self, reboot: bool) -> Command;
+
+    /// Get the booted image's reference.
+    fn booted_image(&self) -> Option<String> {
+        self.booted_deployment().map(|deployment| deployment.image)
+    }
+
+    /// Get the staged image's reference.
+    fn staged_image(&self) -> Option<String> {
+        self.staged_deployment().map(|deployment| deployment.image)
+    }
+
+    /// Checks if the booted deployment is
+    /// the `oci-archive` at `archive_path`.
+    fn is_booted_on_archive(&self, archive_path: &Path) -> bool {
+        self.booted_image()
+            .is_some_and(|deployment| image_is_archive(&deployment, archive_path))
+    }
+
+    /// Checks if the deployment staged for the next boot
+    /// is the `oci-archive` at `archive_path`.
+    fn is_staged_on_archive(&self, archive_path: &Path) -> bool {
+        self.staged_image()
+            .is_some_and(|deployment| image_is_archive(&deployment, archive_path))
+    }
+}
+
+/// The key of the commit metadata that stores
+/// the config of the image a deployment was created from.
+pub const IMAGE_CONFIG_META: &str = "ostree.container.image-config";
+
+#[derive(Debug, Default, Deserialize)]
+struct ImageConfig {
+    #[serde(default)]
+    config: ImageConfigDetails,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ImageConfigDetails {
+    #[serde(default)]
+    labels: Option<HashMap<String, String>>,
+}
+
+/// Reads the labels from the JSON config of an image.
+///
+/// Returns no labels if the config can't be parsed.
+#[must_use]
+pub fn image_config_labels(config: &str) -> HashMap<String, String> {
+    serde_json::from_str::<ImageConfig>(config)
+        .inspect_err(|e| debug!("Unable to parse image config: {e}"))
+        .ok()
+        .and_then(|config| config.config.labels)
+        .unwrap_or_default()
+}
+
+/// A deployment of an image on the host.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Deployment {
+    /// The image reference used to deploy.
+    pub image: String,
+
+    /// The digest of the image.
+    pub digest: Option<String>,
+
+    /// The version of the image.
+    pub version: Option<String>,
+
+    /// The ID of the `bluebuild` build that created the image.
+    pub build_id: Option<String>,
+
+    /// The name of the recipe the image was built from.
+    pub recipe: Option<String>,
+}
+
+impl Deployment {
+    /// Fills in the details of the deployment from the labels
+    /// that are written to images built by `bluebuild`.
+    #[must_use]
+    pub fn with_labels(mut self, labels: &HashMap<String, String>) -> Self {
+        let label = |key: &str| labels.get(key).cloned();
+
+        self.version = self.version.or_else(|| label(IMAGE_VERSION_LABEL));
+        self.build_id = label(BUILD_ID_LABEL);
+        self.recipe = label(IMAGE_TITLE_LABEL);
+        self
+    }
+}
+
+/// Gets the status of the deployments on the host.
+///
+/// `bootc` is used if it manages the booted deployment,
+/// otherwise `rpm-ostree` is used.
+///
+/// # Errors
+/// Will error if neither tool is able to get the status.
+pub fn get_boot_status() -> Result<Box<dyn BootStatus>> {
+    trace!("get_boot_status()");
+
+    if blue_build_utils::check_command_exists("bootc").is_ok() {
+        match BootcStatus::try_new() {
+            Ok(status) if status.is_compatible() => return Ok(Box::new(status)),
+            Ok(_) => debug!("The booted deployment isn't compatible with bootc"),
+            Err(e) => debug!("Unable to get bootc status: {e:?}"),
+        }
+    }
+
+    Ok(Box::new(RpmOstreeStatus::try_new()?))
+}
+
+fn image_is_archive(image: &str, archive_path: &Path) -> bool {
+    image
+        .split(':')
+        .next_back()
+        .is_some_and(|boot_ref| Path::new(boot_ref) == archive_path)
+}
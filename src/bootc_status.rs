@@ -0,0 +1,400 @@
+use std::{borrow::Cow, collections::HashMap, path::Path, process::Command};
+
+use blue_build_utils::{cmd, constants::OCI_ARCHIVE};
+use log::{debug, trace};
+use miette::{bail, IntoDiagnostic, Result};
+use serde::Deserialize;
+
+use crate::boot_status::{self, BootStatus, Deployment, IMAGE_CONFIG_META};
+
+const OSTREE_REPO: &str = "/ostree/repo";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BootcStatus<'a> {
+    status: BootcHostStatus<'a>,
+
+    /// The labels of the images of each deployment
+    /// keyed by the checksum of the deployment's commit.
+    #[serde(skip)]
+    labels: HashMap<String, HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct BootcHostStatus<'a> {
+    staged: Option<BootcBootEntry<'a>>,
+    booted: Option<BootcBootEntry<'a>>,
+    rollback: Option<BootcBootEntry<'a>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct BootcBootEntry<'a> {
+    image: Option<BootcImageStatus<'a>>,
+
+    #[serde(default)]
+    incompatible: bool,
+
+    ostree: Option<BootcOstreeStatus<'a>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct BootcOstreeStatus<'a> {
+    checksum: Cow<'a, str>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BootcImageStatus<'a> {
+    image: BootcImageReference<'a>,
+    version: Option<Cow<'a, str>>,
+    image_digest: Option<Cow<'a, str>>,
+}
+
+impl BootcBootEntry<'_> {
+    fn checksum(&self) -> Option<&str> {
+        self.ostree.as_ref().map(|ostree| &*ostree.checksum)
+    }
+
+    fn deployment(&self, labels: &HashMap<String, HashMap<String, String>>) -> Option<Deployment> {
+        let image = self.image.as_ref()?;
+
+        let deployment = Deployment {
+            image: image.image.reference(),
+            digest: image.image_digest.as_ref().map(ToString::to_string),
+            version: image.version.as_ref().map(ToString::to_string),
+            ..Default::default()
+        };
+
+        Some(
+            match self.checksum().and_then(|checksum| labels.get(checksum)) {
+                Some(labels) => deployment.with_labels(labels),
+                None => deployment,
+            },
+        )
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct BootcImageReference<'a> {
+    image: Cow<'a, str>,
+    transport: Cow<'a, str>,
+}
+
+impl BootcImageReference<'_> {
+    fn reference(&self) -> String {
+        match &*self.transport {
+            "registry" => format!("docker://{}", self.image),
+            transport => format!("{transport}:{}", self.image),
+        }
+    }
+}
+
+impl BootcStatus<'_> {
+    /// Creates a status struct for `bootc`.
+    ///
+    /// # Errors
+    /// Errors if the command fails or deserialization fails.
+    pub fn try_new() -> Result<Self> {
+        blue_build_utils::check_command_exists("bootc")?;
+
+        let mut command = bootc_command();
+        cmd!(command, "status", "--json");
+
+        trace!("{command:?}");
+        let output = command.output().into_diagnostic()?;
+
+        if !output.status.success() {
+            bail!("Failed to get `bootc` status!");
+        }
+
+        trace!("{}", String::from_utf8_lossy(&output.stdout));
+
+        let mut status: Self = serde_json::from_slice(&output.stdout).into_diagnostic()?;
+        status.labels = status
+            .entries()
+            .filter_map(BootcBootEntry::checksum)
+            .filter_map(|checksum| Some((checksum.to_string(), commit_labels(checksum)?)))
+            .collect();
+
+        Ok(status)
+    }
+
+    fn entries(&self) -> impl Iterator<Item = &BootcBootEntry<'_>> {
+        [
+            self.status.staged.as_ref(),
+            self.status.booted.as_ref(),
+            self.status.rollback.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// Checks if the booted deployment can be managed by `bootc`.
+    ///
+    /// Deployments with local modifications like layered
+    /// packages have to be managed with `rpm-ostree`.
+    #[must_use]
+    pub fn is_compatible(&self) -> bool {
+        self.status
+            .booted
+            .as_ref()
+            .is_some_and(|booted| booted.image.is_some() && !booted.incompatible)
+    }
+}
+
+impl BootStatus for BootcStatus<'_> {
+    fn name(&self) -> &'static str {
+        "bootc"
+    }
+
+    fn requires_sudo(&self) -> bool {
+        !nix::unistd::Uid::effective().is_root()
+    }
+
+    /// `bootc` doesn't report its transactions
+    /// and locks the sysroot while updating instead.
+    fn transaction_in_progress(&self) -> bool {
+        false
+    }
+
+    fn booted_deployment(&self) -> Option<Deployment> {
+        self.status.booted.as_ref()?.deployment(&self.labels)
+    }
+
+    fn staged_deployment(&self) -> Option<Deployment> {
+        self.status.staged.as_ref()?.deployment(&self.labels)
+    }
+
+    fn rollback_deployment(&self) -> Option<Deployment> {
+        self.status.rollback.as_ref()?.deployment(&self.labels)
+    }
+
+    fn upgrade_command(&self, reboot: bool) -> Command {
+        let mut command = bootc_command();
+        cmd!(command, "upgrade", if reboot => "--apply");
+        command
+    }
+
+    fn switch_archive_command(&self, archive_path: &Path, reboot: bool) -> Command {
+        let mut command = bootc_command();
+        cmd!(
+            command,
+            "switch",
+            "--transport",
+            OCI_ARCHIVE,
+            archive_path,
+            if reboot => "--apply",
+        );
+        command
+    }
+
+    fn signed_image_ref(&self, image: &str) -> String {
+        format!("docker://{image}")
+    }
+
+    fn switch_signed_image_command(&self, image: &str, reboot: bool) -> Command {
+        let mut command = bootc_command();
+        cmd!(
+            command,
+            "switch",
+            "--enforce-container-sigpolicy",
+            image,
+            if reboot => "--apply",
+        );
+        command
+    }
+
+    fn rollback_command(&self, reboot: bool) -> Command {
+        let mut command = bootc_command();
+        cmd!(command, "rollback", if reboot => "--apply");
+        command
+    }
+}
+
+/// Reads the labels of the image a commit was created from.
+///
+/// `bootc` doesn't include the labels in its status, so they're read
+/// from the image config stored in the commit's metadata instead.
+fn commit_labels(checksum: &str) -> Option<HashMap<String, String>> {
+    let mut command = cmd!(
+        "ostree",
+        "show",
+        format!("--repo={OSTREE_REPO}"),
+        format!("--print-metadata-key={IMAGE_CONFIG_META}"),
+        checksum,
+    );
+
+    trace!("{command:?}");
+    let output = command
+        .output()
+        .inspect_err(|e| debug!("Unable to run ostree: {e}"))
+        .ok()?;
+
+    if !output.status.success() {
+        debug!(
+            "Failed to read the image config of {checksum}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return None;
+    }
+
+    printed_config_labels(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Reads the labels from an image config printed
+/// as a `GVariant` string by `ostree show`.
+fn printed_config_labels(printed: &str) -> Option<HashMap<String, String>> {
+    Some(boot_status::image_config_labels(&parse_gvariant_string(
+        printed,
+    )?))
+}
+
+/// Parses a string in the `GVariant` text format, which
+/// is quoted with `'` or `"` and uses C-style escapes.
+fn parse_gvariant_string(printed: &str) -> Option<String> {
+    let printed = printed.trim();
+    let quote = printed.chars().next().filter(|c| matches!(c, '\'' | '"'))?;
+    let inner = printed.strip_prefix(quote)?.strip_suffix(quote)?;
+
+    let mut string = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            string.push(c);
+            continue;
+        }
+
+        string.push(match chars.next()? {
+            'a' => '\u{07}',
+            'b' => '\u{08}',
+            'f' => '\u{0C}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'v' => '\u{0B}',
+            escape @ ('u' | 'U') => {
+                let digits = if escape == 'u' { 4 } else { 8 };
+                let hex = chars.by_ref().take(digits).collect::<String>();
+                char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+            }
+            c => c,
+        });
+    }
+
+    Some(string)
+}
+
+/// `bootc` needs to be run as root.
+fn bootc_command() -> Command {
+    if nix::unistd::Uid::effective().is_root() {
+        cmd!("bootc")
+    } else {
+        cmd!("sudo", "bootc")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use blue_build_utils::constants::{ARCHIVE_SUFFIX, LOCAL_BUILD};
+    use rstest::rstest;
+
+    use crate::boot_status::BootStatus;
+
+    use super::{parse_gvariant_string, printed_config_labels, BootcStatus};
+
+    fn status_fixture(fixture: &str) -> BootcStatus<'_> {
+        serde_json::from_str(fixture).unwrap()
+    }
+
+    #[test]
+    fn staged_status() {
+        let status = status_fixture(include_str!("../test-files/boot-status/bootc-staged.json"));
+
+        assert!(status.is_compatible());
+        assert!(!status.transaction_in_progress());
+        assert_eq!(
+            status.booted_image().as_deref(),
+            Some("docker://ghcr.io/blue-build/cli/test:latest")
+        );
+        assert_eq!(
+            status.staged_image(),
+            Some(format!(
+                "oci-archive:{LOCAL_BUILD}/cli_test.{ARCHIVE_SUFFIX}"
+            ))
+        );
+        assert!(status.is_staged_on_archive(
+            &Path::new(LOCAL_BUILD).join(format!("cli_test.{ARCHIVE_SUFFIX}"))
+        ));
+        assert!(!status.is_booted_on_archive(
+            &Path::new(LOCAL_BUILD).join(format!("cli_test.{ARCHIVE_SUFFIX}"))
+        ));
+    }
+
+    #[test]
+    fn rollback_deployment() {
+        let status = status_fixture(include_str!("../test-files/boot-status/bootc-staged.json"));
+        let rollback = status
+            .rollback_deployment()
+            .expect("Contains rollback deployment");
+
+        assert_eq!(
+            rollback.image,
+            "docker://ghcr.io/blue-build/cli/test:latest"
+        );
+        assert_eq!(rollback.version.as_deref(), Some("stream9.20241015.0"));
+        assert_eq!(rollback.build_id, None);
+    }
+
+    #[test]
+    fn deployment_labels() {
+        let mut status =
+            status_fixture(include_str!("../test-files/boot-status/bootc-staged.json"));
+        status.labels.insert(
+            "3e9a1f7c5b2d8e4a6c0f9b3d7e1a5c8f2b6d0e4a9c3f7b1d5e8a2c6f0b4d9e3a".into(),
+            printed_config_labels(include_str!(
+                "../test-files/boot-status/bootc-image-config.txt"
+            ))
+            .expect("Contains image config"),
+        );
+
+        let booted = status
+            .booted_deployment()
+            .expect("Contains booted deployment");
+        assert_eq!(booted.recipe.as_deref(), Some("cli/test"));
+        assert_eq!(
+            booted.build_id.as_deref(),
+            Some("0c8d7e6f-1a2b-4c3d-8e9f-a0b1c2d3e4f5")
+        );
+        assert_eq!(booted.version.as_deref(), Some("stream9.20241016.0"));
+
+        let staged = status
+            .staged_deployment()
+            .expect("Contains staged deployment");
+        assert_eq!(staged.recipe, None);
+    }
+
+    #[rstest]
+    #[case("'{\"a\":\"b\"}'", Some("{\"a\":\"b\"}"))]
+    #[case("\"it's\"\n", Some("it's"))]
+    #[case(r#""say \"hi\"""#, Some("say \"hi\""))]
+    #[case(r"'tab\tnew\nline'", Some("tab\tnew\nline"))]
+    #[case(r"'\u00e9'", Some("\u{e9}"))]
+    #[case("not quoted", None)]
+    fn gvariant_strings(#[case] printed: &str, #[case] expected: Option<&str>) {
+        assert_eq!(parse_gvariant_string(printed).as_deref(), expected);
+    }
+
+    #[test]
+    fn incompatible_status() {
+        let status = status_fixture(include_str!(
+            "../test-files/boot-status/bootc-incompatible.json"
+        ));
+
+        assert!(!status.is_compatible());
+        assert_eq!(status.booted_image(), None);
+        assert_eq!(status.rollback_deployment(), None);
+        assert_eq!(status.staged_image(), None);
+    }
+}
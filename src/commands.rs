@@ -1,28 +1,52 @@
-use std::path::PathBuf;
+use std::{
+    fs::File,
+    io::{self, Write},
+    os::fd::{AsFd, AsRawFd, OwnedFd},
+    path::PathBuf,
+    sync::OnceLock,
+};
 
 use log::error;
 
-use clap::{command, crate_authors, Parser, Subcommand};
+use clap::{crate_authors, Parser, Subcommand, ValueEnum};
 use clap_verbosity_flag::{InfoLevel, Verbosity};
+use miette::IntoDiagnostic;
+use serde::Serialize;
 
 use crate::shadow;
 
 pub mod bug_report;
 pub mod build;
+#[cfg(feature = "check-updates")]
+pub mod check_updates;
 pub mod completions;
+#[cfg(feature = "diff")]
+pub mod diff;
 pub mod generate;
+#[cfg(feature = "disk")]
+pub mod generate_disk;
 #[cfg(feature = "iso")]
 pub mod generate_iso;
 #[cfg(feature = "init")]
 pub mod init;
+#[cfg(feature = "lock")]
+pub mod lock;
 #[cfg(feature = "login")]
 pub mod login;
+#[cfg(feature = "lsp")]
+pub mod lsp;
 #[cfg(feature = "prune")]
 pub mod prune;
 #[cfg(feature = "switch")]
+pub mod rollback;
+#[cfg(feature = "switch")]
+pub mod status;
+#[cfg(feature = "switch")]
 pub mod switch;
 #[cfg(feature = "validate")]
 pub mod validate;
+#[cfg(feature = "verify")]
+pub mod verify;
 
 pub trait BlueBuildCommand {
     /// Runs the command and returns a result
@@ -42,6 +66,70 @@ pub trait BlueBuildCommand {
     }
 }
 
+/// The format that a command prints its results in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable output.
+    #[default]
+    Text,
+
+    /// A single JSON document on stdout
+    /// for other programs to consume.
+    Json,
+}
+
+/// The original stdout once it's been redirected
+/// with [`OutputFormat::redirect_stdout`].
+static JSON_STDOUT: OnceLock<OwnedFd> = OnceLock::new();
+
+impl OutputFormat {
+    #[must_use]
+    pub const fn is_json(self) -> bool {
+        matches!(self, Self::Json)
+    }
+
+    /// Sends everything written to stdout to stderr when
+    /// JSON output is selected, which includes the output of
+    /// the external commands that are run. This keeps stdout
+    /// clear for the document printed by [`Self::print_json`].
+    ///
+    /// # Errors
+    /// Will error if stdout can't be redirected.
+    pub fn redirect_stdout(self) -> miette::Result<()> {
+        if !self.is_json() || JSON_STDOUT.get().is_some() {
+            return Ok(());
+        }
+
+        let stdout = io::stdout();
+        stdout.lock().flush().into_diagnostic()?;
+        let json_stdout = stdout.as_fd().try_clone_to_owned().into_diagnostic()?;
+
+        nix::unistd::dup2(io::stderr().as_raw_fd(), stdout.as_raw_fd()).into_diagnostic()?;
+        _ = JSON_STDOUT.set(json_stdout);
+        Ok(())
+    }
+
+    /// Prints the value as a JSON document on stdout.
+    ///
+    /// Logs are written to stderr so that they
+    /// don't mix with the document.
+    ///
+    /// # Errors
+    /// Will error if the value can't be serialized.
+    pub fn print_json<T: Serialize>(value: &T) -> miette::Result<()> {
+        let json = serde_json::to_string_pretty(value).into_diagnostic()?;
+
+        match JSON_STDOUT.get() {
+            Some(json_stdout) => {
+                let mut file = File::from(json_stdout.try_clone().into_diagnostic()?);
+                writeln!(file, "{json}").into_diagnostic()?;
+            }
+            None => println!("{json}"),
+        }
+        Ok(())
+    }
+}
+
 #[derive(Parser, Debug)]
 #[clap(
     name = "BlueBuild",
@@ -76,15 +164,19 @@ pub enum CommandArgs {
     #[cfg(feature = "iso")]
     GenerateIso(generate_iso::GenerateIsoCommand),
 
+    /// Generate a VM disk image for an image or recipe.
+    #[cfg(feature = "disk")]
+    GenerateDisk(generate_disk::GenerateDiskCommand),
+
     /// Switch your current OS onto the image
     /// being built.
     ///
     /// This will create a tarball of your image at
-    /// `/etc/bluebuild/` and invoke `rpm-ostree` to
-    /// rebase/upgrade onto the image using `oci-archive`.
+    /// `/etc/bluebuild/` and invoke `bootc` or `rpm-ostree`
+    /// to rebase/upgrade onto the image using `oci-archive`.
     ///
-    /// NOTE: This can only be used if you have `rpm-ostree`
-    /// installed. This image will not be signed.
+    /// NOTE: This can only be used if you have `bootc` or
+    /// `rpm-ostree` installed. This image will not be signed.
     #[cfg(feature = "switch")]
     #[command(
         visible_alias("update"),
@@ -93,6 +185,16 @@ pub enum CommandArgs {
     )]
     Switch(switch::SwitchCommand),
 
+    /// Show the booted, staged, and rollback
+    /// deployments of your current OS.
+    #[cfg(feature = "switch")]
+    Status(status::StatusCommand),
+
+    /// Boot into the previous deployment
+    /// of your current OS.
+    #[cfg(feature = "switch")]
+    Rollback(rollback::RollbackCommand),
+
     /// Login to all services used for building.
     #[cfg(feature = "login")]
     Login(login::LoginCommand),
@@ -110,6 +212,37 @@ pub enum CommandArgs {
     #[cfg(feature = "validate")]
     Validate(Box<validate::ValidateCommand>),
 
+    /// Start a language server for recipe files
+    /// that communicates over stdio.
+    #[cfg(feature = "lsp")]
+    Lsp(lsp::LspCommand),
+
+    /// Verify the signature of a published image.
+    #[cfg(feature = "verify")]
+    Verify(verify::VerifyCommand),
+
+    /// Compare the packages, files, and labels of two images.
+    ///
+    /// Each image can be a reference to an image in local
+    /// storage or a registry, or an `oci-archive:<path>`.
+    #[cfg(feature = "diff")]
+    Diff(diff::DiffCommand),
+
+    /// Check whether the images a published image was
+    /// built from have been updated since it was built.
+    ///
+    /// Exits with `1` when the image needs to be rebuilt.
+    #[cfg(feature = "check-updates")]
+    CheckUpdates(check_updates::CheckUpdatesCommand),
+
+    /// Pin the images used by your recipes to their
+    /// current digests in a `bluebuild.lock` file.
+    ///
+    /// `generate` and `build` use the pinned digests
+    /// so that images can be rebuilt reproducibly.
+    #[cfg(feature = "lock")]
+    Lock(lock::LockCommand),
+
     /// Clean up cache and images for build drivers.
     #[cfg(feature = "prune")]
     Prune(prune::PruneCommand),
@@ -12,9 +12,10 @@ use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
 use log::{debug, error, trace};
 use miette::{IntoDiagnostic, Result};
 use requestty::question::{completions, Completions};
+use serde::Serialize;
 use std::time::Duration;
 
-use super::BlueBuildCommand;
+use super::{BlueBuildCommand, OutputFormat};
 
 use crate::shadow;
 
@@ -29,6 +30,35 @@ pub struct BugReportCommand {
     /// Path to the recipe file
     #[arg(short, long)]
     recipe_path: Option<String>,
+
+    /// The format to print the report in.
+    ///
+    /// The JSON output contains the information that
+    /// would be in the issue, without prompting for
+    /// anything or opening the browser.
+    #[arg(short, long, default_value = "text")]
+    #[builder(default)]
+    output: OutputFormat,
+}
+
+/// The information in a bug report that
+/// is printed with `--output json`.
+#[derive(Debug, Serialize)]
+struct BugReport {
+    bb_version: &'static str,
+    git_commit_hash: &'static str,
+    pkg_branch_tag: String,
+    build_time: &'static str,
+    build_rust_channel: &'static str,
+    rust_version: &'static str,
+    rust_channel: &'static str,
+    os_name: String,
+    os_version: String,
+    shell_name: String,
+    shell_version: String,
+    terminal_name: String,
+    terminal_version: String,
+    recipe: Option<Recipe<'static>>,
 }
 
 impl BlueBuildCommand for BugReportCommand {
@@ -36,6 +66,11 @@ impl BlueBuildCommand for BugReportCommand {
         debug!("Generating bug report for hash: {}\n", shadow::COMMIT_HASH);
         debug!("Shadow Versioning:\n{}", shadow::VERSION.trim());
 
+        if self.output.is_json() {
+            self.output.redirect_stdout()?;
+            return self.print_json();
+        }
+
         self.create_bugreport()
     }
 }
@@ -62,7 +97,7 @@ impl BugReportCommand {
             os_version: os_info.version().clone(),
         };
 
-        let issue_body = match generate_github_issue(&environment, &recipe) {
+        let issue_body = match generate_github_issue(&environment, recipe.as_ref()) {
             Ok(body) => body,
             Err(e) => {
                 println!("{}: {e}", "Failed to generate bug report".bright_red());
@@ -113,6 +148,30 @@ impl BugReportCommand {
         Ok(())
     }
 
+    fn print_json(&self) -> Result<()> {
+        let os_info = os_info::get();
+        let shell_info = get_shell_info();
+        let terminal_info = get_terminal_info();
+
+        OutputFormat::print_json(&BugReport {
+            bb_version: shadow::PKG_VERSION,
+            git_commit_hash: shadow::COMMIT_HASH,
+            pkg_branch_tag: get_pkg_branch_tag(),
+            build_time: shadow::BUILD_TIME,
+            build_rust_channel: shadow::BUILD_RUST_CHANNEL,
+            rust_version: shadow::RUST_VERSION,
+            rust_channel: shadow::RUST_CHANNEL,
+            os_name: os_info.os_type().to_string(),
+            os_version: os_info.version().to_string(),
+            shell_name: shell_info.name,
+            shell_version: shell_info.version,
+            terminal_name: terminal_info.name,
+            terminal_version: terminal_info.version,
+            // Prompting for the recipe would get in the way of other programs
+            recipe: self.recipe_path.as_ref().map(Recipe::parse).transpose()?,
+        })
+    }
+
     fn get_recipe(&self) -> Option<Recipe> {
         let recipe_path = self.recipe_path.clone().unwrap_or_else(|| {
             get_config_file("recipe", "Enter path to recipe file").unwrap_or_else(|_| {
@@ -276,8 +335,8 @@ fn get_pkg_branch_tag() -> String {
     format!("{} ({})", shadow::BRANCH, shadow::LAST_TAG)
 }
 
-fn generate_github_issue(environment: &Environment, recipe: &Option<Recipe>) -> Result<String> {
-    let recipe = serde_yaml::to_string(recipe).into_diagnostic()?;
+fn generate_github_issue(environment: &Environment, recipe: Option<&Recipe>) -> Result<String> {
+    let recipe = serde_yaml::to_string(&recipe).into_diagnostic()?;
 
     let github_template = GithubIssueTemplate::builder()
         .bb_version(shadow::PKG_VERSION)
@@ -335,7 +394,7 @@ mod tests {
         };
 
         let recipe = Recipe::default();
-        let body = generate_github_issue(&environment, &Some(recipe)).unwrap();
+        let body = generate_github_issue(&environment, Some(&recipe)).unwrap();
         let link = make_github_issue_link(&body);
 
         assert!(link.contains(clap::crate_version!()));
@@ -1,36 +1,55 @@
-use std::path::{Path, PathBuf};
+use std::{
+    env,
+    io::Write,
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
 use blue_build_process_management::{
     drivers::{
         opts::{
-            BuildTagPushOpts, CheckKeyPairOpts, CompressionType, GenerateImageNameOpts,
-            GenerateTagsOpts, SignVerifyOpts,
+            AttestOpts, BuildTagPushOpts, CheckKeyPairOpts, CompressionType, GenerateImageNameOpts,
+            GenerateTagsOpts, GetMetadataOpts, ManifestPushOpts, SignVerifyOpts,
         },
-        types::Platform,
+        types::{BuildSecret, CacheTarget, Platform},
         BuildDriver, CiDriver, Driver, DriverArgs, SigningDriver,
     },
+    dry_run::{is_dry_run, print_action, set_dry_run},
     logging::{color_str, gen_random_ansi_color},
 };
-use blue_build_recipe::Recipe;
+use blue_build_recipe::{Recipe, SecretSource, VarOverride};
 use blue_build_utils::{
     constants::{
-        ARCHIVE_SUFFIX, BB_REGISTRY_NAMESPACE, CONFIG_PATH, CONTAINER_FILE, RECIPE_FILE,
-        RECIPE_PATH,
+        ARCHIVE_SUFFIX, BB_REGISTRY_NAMESPACE, BB_TEMPLATE_DIR, CONFIG_PATH, CONTAINER_FILE,
+        RECIPE_FILE, RECIPE_PATH, SLSA_PROVENANCE_PREDICATE_TYPE,
     },
     cowstr,
-    credentials::{Credentials, CredentialsArgs},
-    string,
+    credentials::{credential_helper_secret, Credentials, CredentialsArgs},
+    retry, string,
     traits::CowCollecter,
 };
 use bon::Builder;
 use clap::Args;
 use log::{info, trace, warn};
-use miette::{bail, IntoDiagnostic, Result};
-use tempfile::TempDir;
+use miette::{bail, miette, Context, IntoDiagnostic, Result};
+use tempfile::{NamedTempFile, TempDir};
+
+#[cfg(feature = "sbom")]
+use blue_build_process_management::drivers::types::SbomFormat;
+#[cfg(feature = "validate")]
+use blue_build_utils::constants::BB_SCHEMA_DIR;
 
 use crate::commands::generate::GenerateCommand;
 
-use super::BlueBuildCommand;
+use super::{BlueBuildCommand, OutputFormat};
+
+use self::{
+    provenance::Provenance,
+    report::{BuildReport, ImageReport, PlatformReport},
+};
+
+mod provenance;
+mod report;
 
 #[allow(clippy::struct_excessive_bools)]
 #[derive(Debug, Args, Builder)]
@@ -56,15 +75,20 @@ pub struct BuildCommand {
     #[builder(default)]
     push: bool,
 
-    /// Build for a specific platform.
+    /// Build for specific platforms.
+    ///
+    /// Multiple platforms can be given as a comma separated
+    /// list. Each platform is built separately and, when
+    /// pushing, joined into a single manifest list so that
+    /// every tag resolves to all of the architectures.
     ///
     /// NOTE: Building for a different architecture
     /// than your hardware will require installing
     /// qemu. Build times will be much greater when
     /// building for a non-native architecture.
-    #[arg(long, default_value = "native")]
-    #[builder(default)]
-    platform: Platform,
+    #[arg(long, default_value = "native", value_delimiter = ',')]
+    #[builder(default, into)]
+    platform: Vec<Platform>,
 
     /// The compression format the images
     /// will be pushed in.
@@ -120,11 +144,89 @@ pub struct BuildCommand {
     #[cfg(feature = "rechunk")]
     rechunk: bool,
 
+    /// Import the build cache from a registry repository
+    /// or a local OCI directory.
+    ///
+    /// Targets prefixed with `oci:` or that start with `/` or `.`
+    /// are local directories, anything else is a registry
+    /// repository. If no target is given, the `<image>-cache` repository
+    /// is used when pushing and `~/.cache/bluebuild/build-cache/<image>`
+    /// otherwise.
+    ///
+    /// NOTE: The podman and buildah drivers only
+    /// support registry caches.
+    #[arg(long, num_args = 0..=1, require_equals = true, value_name = "TARGET")]
+    #[allow(clippy::option_option)]
+    cache_from: Option<Option<CacheTarget>>,
+
+    /// Export the build cache to a registry repository
+    /// or a local OCI directory.
+    ///
+    /// Takes the same targets as `--cache-from`.
+    #[arg(long, num_args = 0..=1, require_equals = true, value_name = "TARGET")]
+    #[allow(clippy::option_option)]
+    cache_to: Option<Option<CacheTarget>>,
+
+    /// Generate an SBOM of the packages and files in the image.
+    ///
+    /// When archiving, the SBOM is written next to the archive.
+    /// When pushing, the SBOM is attached to the pushed image
+    /// as a signed attestation, so it can't be used with `--no-sign`.
+    #[cfg(feature = "sbom")]
+    #[arg(long, value_name = "FORMAT")]
+    sbom: Option<SbomFormat>,
+
     /// The location to temporarily store files
     /// while building. If unset, it will use `/tmp`.
     #[arg(long)]
     tempdir: Option<PathBuf>,
 
+    /// Override a variable in the recipe's `vars:` section.
+    ///
+    /// Can be used multiple times. Values are parsed
+    /// as yaml (e.g. `--var packages=[micro,htop]`).
+    #[arg(long = "var", value_name = "KEY=VALUE")]
+    #[builder(default, into)]
+    vars: Vec<VarOverride>,
+
+    /// A directory of templates that replace parts
+    /// of the built-in `Containerfile` template.
+    ///
+    /// A `Containerfile.j2`, `stages.j2`, or `modules/<module-type>.j2`
+    /// in the directory is rendered in place of the built-in template
+    /// for that part, which it can include with `{{ default }}`.
+    #[arg(long, env = BB_TEMPLATE_DIR)]
+    #[builder(into)]
+    template_dir: Option<PathBuf>,
+
+    /// A directory containing the json schemas to validate against.
+    ///
+    /// Schemas are otherwise taken from the cache in
+    /// `~/.cache/bluebuild/schemas` or retrieved from
+    /// <https://schema.blue-build.org>, falling back to
+    /// the ones embedded in the binary when offline.
+    #[cfg(feature = "validate")]
+    #[arg(long, env = BB_SCHEMA_DIR)]
+    #[builder(into)]
+    schema_dir: Option<PathBuf>,
+
+    /// The format to print the results of the build in.
+    ///
+    /// The JSON output contains the image references, tags,
+    /// digests, signatures, archives, build ID, and timings.
+    #[arg(short, long, default_value = "text")]
+    #[builder(default)]
+    output: OutputFormat,
+
+    /// Print the commands that would be run instead of running them.
+    ///
+    /// Tags and image names are still resolved and the
+    /// Containerfiles are rendered into the temporary
+    /// directory, which is kept for inspection.
+    #[arg(long)]
+    #[builder(default)]
+    dry_run: bool,
+
     #[clap(flatten)]
     #[builder(default)]
     credentials: CredentialsArgs,
@@ -138,20 +240,18 @@ impl BlueBuildCommand for BuildCommand {
     /// Runs the command and returns a result.
     fn try_run(&mut self) -> Result<()> {
         trace!("BuildCommand::try_run()");
+        self.output.redirect_stdout()?;
 
-        #[cfg(feature = "rechunk")]
-        if !nix::unistd::Uid::effective().is_root() && self.rechunk {
-            bail!("You must be root to use the rechunk feature!");
+        if self.dry_run {
+            set_dry_run(true);
         }
 
+        self.check_args()?;
+
         Driver::init(self.drivers);
 
         Credentials::init(self.credentials.clone());
 
-        if self.push && self.archive.is_some() {
-            bail!("You cannot use '--archive' and '--push' at the same time");
-        }
-
         if self.push {
             blue_build_utils::check_command_exists("cosign")?;
             Driver::check_signing_files(&CheckKeyPairOpts::builder().dir(Path::new(".")).build())?;
@@ -159,11 +259,7 @@ impl BlueBuildCommand for BuildCommand {
             Driver::signing_login()?;
         }
 
-        let tempdir = if let Some(ref dir) = self.tempdir {
-            TempDir::new_in(dir).into_diagnostic()?
-        } else {
-            TempDir::new().into_diagnostic()?
-        };
+        let tempdir = self.tempdir()?;
 
         #[cfg(feature = "multi-recipe")]
         {
@@ -184,21 +280,23 @@ impl BlueBuildCommand for BuildCommand {
                 recipes.into_iter().filter(|recipe| same.insert(recipe.clone())).collect()
             });
 
+            let platforms = self.platforms();
             recipe_paths.par_iter().try_for_each(|recipe| {
-                GenerateCommand::builder()
-                    .output(tempdir.path().join(if recipe_paths.len() > 1 {
-                        blue_build_utils::generate_containerfile_path(recipe)?
-                    } else {
-                        PathBuf::from(CONTAINER_FILE)
-                    }))
-                    .platform(self.platform)
-                    .recipe(recipe)
-                    .drivers(self.drivers)
-                    .build()
+                platforms.par_iter().try_for_each(|&platform| {
+                    self.generate_command(
+                        recipe,
+                        tempdir.path().join(self.containerfile_path(
+                            recipe,
+                            recipe_paths.len(),
+                            platform,
+                        )?),
+                        platform,
+                    )
                     .try_run()
+                })
             })?;
 
-            self.start(&recipe_paths, tempdir.path())
+            self.start(&recipe_paths, tempdir.path())?;
         }
 
         #[cfg(not(feature = "multi-recipe"))]
@@ -214,107 +312,384 @@ impl BlueBuildCommand for BuildCommand {
                 }
             });
 
-            GenerateCommand::builder()
-                .output(tempdir.path().join(CONTAINER_FILE))
-                .recipe(&recipe_path)
-                .drivers(self.drivers)
-                .build()
+            for platform in self.platforms() {
+                self.generate_command(
+                    &recipe_path,
+                    tempdir
+                        .path()
+                        .join(self.containerfile_path(&recipe_path, 1, platform)?),
+                    platform,
+                )
                 .try_run()?;
+            }
+
+            self.start(&recipe_path, tempdir.path())?;
+        }
 
-            self.start(&recipe_path, tempdir.path())
+        if is_dry_run() {
+            let tempdir = tempdir.into_path();
+            print_action(format!(
+                "The rendered Containerfiles were kept in {}",
+                tempdir.display()
+            ));
         }
+        Ok(())
     }
 }
 
 impl BuildCommand {
+    /// Checks for combinations of args that can't be used together.
+    fn check_args(&self) -> Result<()> {
+        #[cfg(feature = "rechunk")]
+        if self.rechunk && is_dry_run() {
+            bail!("The rechunk feature does not support '--dry-run'");
+        }
+
+        #[cfg(feature = "rechunk")]
+        if !nix::unistd::Uid::effective().is_root() && self.rechunk {
+            bail!("You must be root to use the rechunk feature!");
+        }
+
+        #[cfg(feature = "rechunk")]
+        if self.rechunk && self.platforms().len() > 1 {
+            bail!("The rechunk feature does not support building for multiple platforms");
+        }
+
+        if self.push && self.archive.is_some() {
+            bail!("You cannot use '--archive' and '--push' at the same time");
+        }
+
+        #[cfg(feature = "sbom")]
+        if self.sbom.is_some() && !self.push && self.archive.is_none() {
+            bail!("Generating an SBOM requires either '--push' or '--archive'");
+        }
+
+        #[cfg(feature = "sbom")]
+        if self.sbom.is_some() && self.push && self.no_sign {
+            bail!("An SBOM for a pushed image is attached as a signed attestation, so '--sbom' can't be used with '--no-sign'");
+        }
+
+        Ok(())
+    }
+
+    fn generate_command(
+        &self,
+        recipe: &Path,
+        output: PathBuf,
+        platform: Platform,
+    ) -> GenerateCommand {
+        let generate = GenerateCommand::builder()
+            .output_file(output)
+            .platform(platform)
+            .recipe(recipe)
+            .vars(self.vars.clone())
+            .maybe_template_dir(self.template_dir.clone())
+            .drivers(self.drivers);
+
+        #[cfg(feature = "validate")]
+        let generate = generate.maybe_schema_dir(self.schema_dir.clone());
+
+        generate.build()
+    }
+
     #[cfg(feature = "multi-recipe")]
     fn start(&self, recipe_paths: &[PathBuf], temp_dir: &Path) -> Result<()> {
         use rayon::prelude::*;
 
         trace!("BuildCommand::build_image()");
 
-        let images = recipe_paths
+        let start = Instant::now();
+        let reports = recipe_paths
             .par_iter()
-            .try_fold(Vec::new, |mut images, recipe_path| -> Result<Vec<String>> {
-                let containerfile = temp_dir.join(if recipe_paths.len() > 1 {
-                    blue_build_utils::generate_containerfile_path(recipe_path)?
-                } else {
-                    PathBuf::from(CONTAINER_FILE)
-                });
-                images.extend(self.build(recipe_path, &containerfile)?);
-                Ok(images)
-            })
-            .try_reduce(Vec::new, |mut init, image_names| {
-                let color = gen_random_ansi_color();
-                init.extend(image_names.iter().map(|image| color_str(image, color)));
-                Ok(init)
-            })?;
+            .map(|recipe_path| self.build(recipe_path, recipe_paths.len(), temp_dir))
+            .collect::<Result<Vec<_>>>()?;
 
         info!(
             "Finished building:\n{}",
-            images
+            reports
                 .iter()
-                .map(|image| format!("\t- {image}"))
+                .flat_map(|report| {
+                    let color = gen_random_ansi_color();
+                    report
+                        .refs
+                        .iter()
+                        .map(move |image| format!("\t- {}", color_str(image, color)))
+                })
                 .collect::<Vec<_>>()
                 .join("\n")
         );
-        Ok(())
+        self.print_report(reports, start)
     }
 
     #[cfg(not(feature = "multi-recipe"))]
     fn start(&self, recipe_path: &Path, temp_dir: &Path) -> Result<()> {
         trace!("BuildCommand::start()");
 
-        let images = self.build(recipe_path, &temp_dir.join(CONTAINER_FILE))?;
+        let start = Instant::now();
+        let report = self.build(recipe_path, 1, temp_dir)?;
         let color = gen_random_ansi_color();
 
         info!(
             "Finished building:\n{}",
-            images
+            report
+                .refs
                 .iter()
                 .map(|image| format!("\t- {}", color_str(image, color)))
                 .collect::<Vec<_>>()
                 .join("\n")
         );
-        Ok(())
+        self.print_report(vec![report], start)
     }
 
-    fn build(&self, recipe_path: &Path, containerfile: &Path) -> Result<Vec<String>> {
-        let recipe = Recipe::parse(recipe_path)?;
+    fn print_report(&self, images: Vec<ImageReport>, start: Instant) -> Result<()> {
+        if !self.output.is_json() {
+            return Ok(());
+        }
+
+        OutputFormat::print_json(&BuildReport {
+            build_id: Driver::get_build_id().to_string(),
+            duration_secs: start.elapsed().as_secs_f64(),
+            images,
+        })
+    }
+
+    fn build(
+        &self,
+        recipe_path: &Path,
+        recipe_count: usize,
+        temp_dir: &Path,
+    ) -> Result<ImageReport> {
+        let start = Instant::now();
+        let recipe = Recipe::parse_with_vars(recipe_path, &self.vars)?;
+        let platforms = self.platforms();
         let tags = Driver::generate_tags(
             &GenerateTagsOpts::builder()
                 .oci_ref(&recipe.base_image_ref()?)
                 .maybe_alt_tags(recipe.alt_tags.as_ref().map(CowCollecter::collect_cow_vec))
-                .platform(self.platform)
+                .platform(platforms[0])
                 .build(),
         )?;
         let image_name = self.image_name(&recipe)?;
+        let mut report = ImageReport {
+            recipe: recipe_path.to_path_buf(),
+            image: image_name.clone(),
+            tags: tags.clone(),
+            refs: Vec::new(),
+            digest: None,
+            platforms: platforms
+                .iter()
+                .map(|&platform| PlatformReport {
+                    platform: platform.to_string(),
+                    digest: None,
+                    archive: self
+                        .archive
+                        .as_ref()
+                        .map(|dir| self.archive_path(dir, &recipe, platform, ARCHIVE_SUFFIX)),
+                })
+                .collect(),
+            signatures: Vec::new(),
+            duration_secs: 0.0,
+        };
+
+        if let [platform] = *platforms {
+            let containerfile =
+                temp_dir.join(self.containerfile_path(recipe_path, recipe_count, platform)?);
+            report.refs =
+                self.build_platform(&recipe, &image_name, &containerfile, &tags, platform)?;
+
+            if self.push {
+                let image_digest =
+                    image_digest(&image_name, tags.first().map(String::as_str), platform)?;
+
+                if !self.no_sign {
+                    Driver::sign_and_verify(
+                        &SignVerifyOpts::builder()
+                            .image(&image_name)
+                            .retry_push(self.retry_push)
+                            .retry_count(self.retry_count)
+                            .maybe_tag(tags.first())
+                            .platform(platform)
+                            .build(),
+                    )?;
+
+                    self.attest_provenance(
+                        &image_digest,
+                        &Provenance::resolve(recipe_path, &recipe, &[platform])?,
+                    )?;
+                }
+
+                report.digest = digest_of(&image_digest);
+                report.platforms[0].digest.clone_from(&report.digest);
+            }
+
+            return Ok(self.finish_report(report, start));
+        }
+
+        let first_tag = tags
+            .first()
+            .ok_or_else(|| miette!("At least one tag is required to build multiple platforms"))?;
+
+        let platform_images = platforms
+            .iter()
+            .map(|&platform| {
+                let containerfile =
+                    temp_dir.join(self.containerfile_path(recipe_path, recipe_count, platform)?);
+                let arch_tags = if self.archive.is_some() {
+                    vec![]
+                } else {
+                    vec![platform.arch_tag(first_tag)]
+                };
+                self.build_platform(&recipe, &image_name, &containerfile, &arch_tags, platform)
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        if self.push {
+            self.push_manifest(recipe_path, &recipe, &platform_images, &mut report)?;
+        } else {
+            report.refs = platform_images;
+        }
+
+        Ok(self.finish_report(report, start))
+    }
+
+    /// Pushes a manifest list for every tag that joins the images built
+    /// for each platform, then signs and attests the pushed images.
+    ///
+    /// The pushed references and digests are added to the report.
+    fn push_manifest(
+        &self,
+        recipe_path: &Path,
+        recipe: &Recipe,
+        platform_images: &[String],
+        report: &mut ImageReport,
+    ) -> Result<()> {
+        let image_name = report.image.as_str();
+        let platforms = self.platforms();
+        let first_tag = report
+            .tags
+            .first()
+            .ok_or_else(|| miette!("At least one tag is required to build multiple platforms"))?;
+
+        let retry_count = if self.retry_push { self.retry_count } else { 0 };
+        let mut manifest_digest = None;
+        let images = report
+            .tags
+            .iter()
+            .map(|tag| -> Result<String> {
+                let image = format!("{image_name}:{tag}");
+                manifest_digest = Some(retry(retry_count, 5, || {
+                    Driver::manifest_push(
+                        &ManifestPushOpts::builder()
+                            .image(&image)
+                            .images(platform_images.collect_cow_vec())
+                            .build(),
+                    )
+                })?);
+                Ok(image)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let platform_digests = platforms
+            .iter()
+            .map(|&platform| {
+                image_digest(image_name, Some(&platform.arch_tag(first_tag)), platform)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if !self.no_sign {
+            Driver::sign_and_verify(
+                &SignVerifyOpts::builder()
+                    .image(image_name)
+                    .retry_push(self.retry_push)
+                    .retry_count(self.retry_count)
+                    .tag(first_tag)
+                    .maybe_manifest_digest(manifest_digest.as_deref())
+                    .platforms(platforms.clone())
+                    .build(),
+            )?;
+
+            // The manifest list records every platform while each
+            // platform image only records its own dependencies.
+            if let Some(ref manifest_digest) = manifest_digest {
+                self.attest_provenance(
+                    &format!("{image_name}@{manifest_digest}"),
+                    &Provenance::resolve(recipe_path, recipe, &platforms)?,
+                )?;
+            }
+            for (&platform, image_digest) in platforms.iter().zip(&platform_digests) {
+                self.attest_provenance(
+                    image_digest,
+                    &Provenance::resolve(recipe_path, recipe, &[platform])?,
+                )?;
+            }
+        }
+
+        for (platform, image_digest) in report.platforms.iter_mut().zip(&platform_digests) {
+            platform.digest = digest_of(image_digest);
+        }
+        report.refs = images;
+        report.digest = manifest_digest;
+        Ok(())
+    }
+
+    /// Records how long the build took and adds the
+    /// signature references when the images were signed.
+    fn finish_report(&self, mut report: ImageReport, start: Instant) -> ImageReport {
+        report.duration_secs = start.elapsed().as_secs_f64();
+
+        if self.push && !self.no_sign {
+            report.with_signatures()
+        } else {
+            report
+        }
+    }
+
+    fn build_platform(
+        &self,
+        recipe: &Recipe,
+        image_name: &str,
+        containerfile: &Path,
+        tags: &[String],
+        platform: Platform,
+    ) -> Result<Vec<String>> {
+        let cache_from = self.cache_target(self.cache_from.as_ref(), image_name, platform);
+        let cache_to = self.cache_target(self.cache_to.as_ref(), image_name, platform);
+        let (secrets, _secret_files) = self.build_secrets(recipe)?;
 
         let build_fn = || -> Result<Vec<String>> {
             Driver::build_tag_push(&self.archive.as_ref().map_or_else(
                 || {
                     BuildTagPushOpts::builder()
-                        .image(&image_name)
+                        .image(image_name)
                         .containerfile(containerfile)
-                        .platform(self.platform)
+                        .platform(platform)
                         .tags(tags.collect_cow_vec())
                         .push(self.push)
                         .retry_push(self.retry_push)
                         .retry_count(self.retry_count)
                         .compression(self.compression_format)
                         .squash(self.squash)
+                        .maybe_cache_from(cache_from.as_ref())
+                        .maybe_cache_to(cache_to.as_ref())
+                        .secrets(&secrets)
                         .build()
                 },
                 |archive_dir| {
                     BuildTagPushOpts::builder()
                         .containerfile(containerfile)
-                        .platform(self.platform)
-                        .archive_path(format!(
-                            "{}/{}.{ARCHIVE_SUFFIX}",
-                            archive_dir.to_string_lossy().trim_end_matches('/'),
-                            recipe.name.to_lowercase().replace('/', "_"),
+                        .platform(platform)
+                        .archive_path(self.archive_path(
+                            archive_dir,
+                            recipe,
+                            platform,
+                            ARCHIVE_SUFFIX,
                         ))
                         .squash(self.squash)
+                        .maybe_cache_from(cache_from.as_ref())
+                        .maybe_cache_to(cache_to.as_ref())
+                        .secrets(&secrets)
                         .build()
                 },
             ))
@@ -322,43 +697,47 @@ impl BuildCommand {
 
         #[cfg(feature = "rechunk")]
         let images = if self.rechunk {
-            use blue_build_process_management::drivers::{
-                opts::{GetMetadataOpts, RechunkOpts},
-                InspectDriver, RechunkDriver,
+            use blue_build_process_management::drivers::{opts::RechunkOpts, RechunkDriver};
+            use blue_build_recipe::Lockfile;
+            use blue_build_utils::constants::LOCKFILE_PATH;
+
+            use crate::commands::generate::{current_digest, resolve_image_digests};
+
+            let lockfile = Lockfile::load(LOCKFILE_PATH)?;
+            let base_image = format!("{}:{}", recipe.base_image, recipe.image_version);
+            let os_version = match lockfile
+                .get(&base_image)
+                .and_then(|locked| locked.os_version)
+            {
+                Some(os_version) => os_version,
+                None => Driver::get_os_version()
+                    .oci_ref(&recipe.base_image_ref()?)
+                    .platform(platform)
+                    .call()?,
             };
 
             Driver::rechunk(
                 &RechunkOpts::builder()
-                    .image(&image_name)
+                    .image(image_name)
                     .containerfile(containerfile)
-                    .platform(self.platform)
+                    .platform(platform)
                     .tags(tags.collect_cow_vec())
                     .push(self.push)
-                    .version(format!(
-                        "{version}.<date>",
-                        version = Driver::get_os_version()
-                            .oci_ref(&recipe.base_image_ref()?)
-                            .platform(self.platform)
-                            .call()?,
-                    ))
+                    .version(format!("{os_version}.<date>"))
                     .retry_push(self.retry_push)
                     .retry_count(self.retry_count)
                     .compression(self.compression_format)
-                    .base_digest(
-                        Driver::get_metadata(
-                            &GetMetadataOpts::builder()
-                                .image(&*recipe.base_image)
-                                .tag(&*recipe.image_version)
-                                .platform(self.platform)
-                                .build(),
-                        )?
-                        .digest,
+                    .base_digest(current_digest(&base_image, &lockfile, platform)?)
+                    .image_digests(
+                        resolve_image_digests(recipe, &lockfile, os_version, platform)?
+                            .collect_cow_vec(),
                     )
                     .repo(Driver::get_repo_url()?)
                     .name(&*recipe.name)
                     .description(&*recipe.description)
-                    .base_image(format!("{}:{}", &recipe.base_image, &recipe.image_version))
+                    .base_image(base_image.as_str())
                     .maybe_tempdir(self.tempdir.as_deref())
+                    .secrets(&secrets)
                     .build(),
             )?
         } else {
@@ -368,21 +747,261 @@ impl BuildCommand {
         #[cfg(not(feature = "rechunk"))]
         let images = build_fn()?;
 
-        if self.push && !self.no_sign {
-            Driver::sign_and_verify(
-                &SignVerifyOpts::builder()
-                    .image(&image_name)
-                    .retry_push(self.retry_push)
-                    .retry_count(self.retry_count)
-                    .maybe_tag(tags.first())
-                    .platform(self.platform)
-                    .build(),
-            )?;
+        #[cfg(feature = "sbom")]
+        if let Some(format) = self.sbom {
+            self.generate_sbom(recipe, image_name, tags, platform, format)?;
         }
 
         Ok(images)
     }
 
+    /// Attaches a signed SLSA provenance attestation to a pushed image.
+    fn attest_provenance(&self, image_digest: &str, provenance: &Provenance) -> Result<()> {
+        info!("Attesting provenance for {image_digest}");
+
+        let tempdir = self.tempdir()?;
+        let predicate = tempdir.path().join("provenance.json");
+        std::fs::write(
+            &predicate,
+            serde_json::to_vec(provenance).into_diagnostic()?,
+        )
+        .into_diagnostic()?;
+
+        let retry_count = if self.retry_push { self.retry_count } else { 0 };
+        retry(retry_count, 5, || {
+            Driver::attest_and_verify(
+                &AttestOpts::builder()
+                    .image(image_digest)
+                    .predicate(&*predicate)
+                    .predicate_type(SLSA_PROVENANCE_PREDICATE_TYPE)
+                    .dir(Path::new("."))
+                    .build(),
+            )
+        })
+    }
+
+    /// Resolves the recipe's secrets into the secrets passed to the build.
+    ///
+    /// Secrets from a credential helper are written to temporary
+    /// files that are removed once the returned files are dropped.
+    /// They aren't looked up in dry-run mode, where a placeholder
+    /// is printed in place of the file instead.
+    fn build_secrets(&self, recipe: &Recipe) -> Result<(Vec<BuildSecret>, Vec<NamedTempFile>)> {
+        let mut files = Vec::new();
+        let secrets = recipe
+            .secrets
+            .iter()
+            .map(|secret| {
+                let id = secret.id.to_string();
+                Ok(match &secret.source {
+                    SecretSource::Env(var) => {
+                        if env::var_os(&**var).is_none() {
+                            bail!("The environment variable {var} for the secret {id} isn't set");
+                        }
+                        BuildSecret::Env {
+                            id,
+                            var: var.to_string(),
+                        }
+                    }
+                    SecretSource::File(path) => BuildSecret::File {
+                        path: path.canonicalize().into_diagnostic().with_context(|| {
+                            format!("Failed to find {} for the secret {id}", path.display())
+                        })?,
+                        id,
+                    },
+                    SecretSource::CredentialHelper(registry) if is_dry_run() => {
+                        print_action(format!(
+                            "The credentials for {registry} would be written to a temporary file for the secret {id}"
+                        ));
+                        BuildSecret::File {
+                            id,
+                            path: PathBuf::from(format!("<{registry} credentials>")),
+                        }
+                    }
+                    SecretSource::CredentialHelper(registry) => {
+                        let value = credential_helper_secret(registry).ok_or_else(|| {
+                            miette!("No credentials for {registry} were found for the secret {id}")
+                        })?;
+                        let mut file = self
+                            .tempdir
+                            .as_ref()
+                            .map_or_else(NamedTempFile::new, NamedTempFile::new_in)
+                            .into_diagnostic()?;
+                        file.write_all(value.as_bytes()).into_diagnostic()?;
+
+                        let path = file.path().to_path_buf();
+                        files.push(file);
+                        BuildSecret::File { id, path }
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((secrets, files))
+    }
+
+    /// Creates a temporary directory in `--tempdir`
+    /// or the default location.
+    fn tempdir(&self) -> Result<TempDir> {
+        self.tempdir.as_ref().map_or_else(
+            || TempDir::new().into_diagnostic(),
+            |dir| TempDir::new_in(dir).into_diagnostic(),
+        )
+    }
+
+    /// Generates the SBOM for a built image.
+    ///
+    /// Archives have the SBOM written next to them while pushed
+    /// images have it attached as a signed attestation.
+    #[cfg(feature = "sbom")]
+    fn generate_sbom(
+        &self,
+        recipe: &Recipe,
+        image_name: &str,
+        tags: &[String],
+        platform: Platform,
+        format: SbomFormat,
+    ) -> Result<()> {
+        use blue_build_process_management::drivers::{
+            opts::{GenerateSbomOpts, SbomSource},
+            SbomDriver,
+        };
+
+        if let Some(ref archive_dir) = self.archive {
+            let archive = self.archive_path(archive_dir, recipe, platform, ARCHIVE_SUFFIX);
+            let output = self.archive_path(archive_dir, recipe, platform, format.extension());
+
+            return Driver::generate_sbom(
+                &GenerateSbomOpts::builder()
+                    .source(SbomSource::Archive(Path::new(&archive).into()))
+                    .output(Path::new(&output))
+                    .format(format)
+                    .platform(platform)
+                    .build(),
+            );
+        }
+
+        let image_digest = image_digest(image_name, tags.first().map(String::as_str), platform)?;
+        let tempdir = self.tempdir()?;
+        let output = tempdir.path().join(format!(
+            "{}.{}",
+            self.archive_name(recipe, platform),
+            format.extension()
+        ));
+
+        Driver::generate_sbom(
+            &GenerateSbomOpts::builder()
+                .source(SbomSource::Registry(image_digest.as_str().into()))
+                .output(&*output)
+                .format(format)
+                .platform(platform)
+                .build(),
+        )?;
+
+        info!("Attesting SBOM for {image_digest}");
+        Driver::attest(
+            &AttestOpts::builder()
+                .image(&image_digest)
+                .predicate(&*output)
+                .predicate_type(format.predicate_type())
+                .dir(Path::new("."))
+                .build(),
+        )
+    }
+
+    /// Resolves a `--cache-from`/`--cache-to` target for an image,
+    /// using the default location when no target was given.
+    ///
+    /// The architecture is appended when building
+    /// for multiple platforms.
+    #[allow(clippy::option_option)]
+    fn cache_target(
+        &self,
+        target: Option<&Option<CacheTarget>>,
+        image_name: &str,
+        platform: Platform,
+    ) -> Option<CacheTarget> {
+        let target = target?
+            .clone()
+            .unwrap_or_else(|| CacheTarget::default_for(image_name, self.push));
+
+        Some(if self.platforms().len() > 1 {
+            target.for_platform(platform)
+        } else {
+            target
+        })
+    }
+
+    /// The path of a file in the archive directory for a recipe.
+    fn archive_path(
+        &self,
+        archive_dir: &Path,
+        recipe: &Recipe,
+        platform: Platform,
+        extension: &str,
+    ) -> String {
+        format!(
+            "{}/{}.{extension}",
+            archive_dir.to_string_lossy().trim_end_matches('/'),
+            self.archive_name(recipe, platform),
+        )
+    }
+
+    /// The name of the archive file for a recipe.
+    ///
+    /// The architecture is appended when building
+    /// for multiple platforms.
+    fn archive_name(&self, recipe: &Recipe, platform: Platform) -> String {
+        let name = recipe.name.to_lowercase().replace('/', "_");
+
+        if self.platforms().len() > 1 {
+            format!("{name}-{}", platform.arch())
+        } else {
+            name
+        }
+    }
+
+    /// The path of the Containerfile generated for a recipe
+    /// and platform, relative to the temp directory.
+    fn containerfile_path(
+        &self,
+        recipe_path: &Path,
+        recipe_count: usize,
+        platform: Platform,
+    ) -> Result<PathBuf> {
+        let containerfile = if recipe_count > 1 {
+            blue_build_utils::generate_containerfile_path(recipe_path)?
+        } else {
+            PathBuf::from(CONTAINER_FILE)
+        };
+
+        Ok(if self.platforms().len() > 1 {
+            let mut containerfile = containerfile.into_os_string();
+            containerfile.push(format!(".{}", platform.arch()));
+            containerfile.into()
+        } else {
+            containerfile
+        })
+    }
+
+    /// The list of platforms to build for with
+    /// duplicate architectures removed.
+    fn platforms(&self) -> Vec<Platform> {
+        let mut seen = std::collections::HashSet::new();
+        let platforms = self
+            .platform
+            .iter()
+            .copied()
+            .filter(|platform| seen.insert(platform.arch().to_owned()))
+            .collect::<Vec<_>>();
+
+        if platforms.is_empty() {
+            vec![Platform::default()]
+        } else {
+            platforms
+        }
+    }
+
     fn image_name(&self, recipe: &Recipe) -> Result<String> {
         let image_name = Driver::generate_image_name(
             GenerateImageNameOpts::builder()
@@ -403,3 +1022,88 @@ impl BuildCommand {
         Ok(image_name)
     }
 }
+
+/// Resolves the digest of a pushed image
+/// as an `<image>@<digest>` reference.
+fn image_digest(image_name: &str, tag: Option<&str>, platform: Platform) -> Result<String> {
+    let digest = Driver::get_pushed_digest(
+        &GetMetadataOpts::builder()
+            .image(image_name)
+            .maybe_tag(tag)
+            .platform(platform)
+            .build(),
+    )?;
+
+    Ok(format!("{image_name}@{digest}"))
+}
+
+/// The digest of an `<image>@<digest>` reference.
+fn digest_of(image_digest: &str) -> Option<String> {
+    image_digest
+        .split_once('@')
+        .map(|(_, digest)| digest.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use blue_build_process_management::drivers::types::Platform;
+    use blue_build_recipe::{ModuleExt, Recipe};
+    use rstest::rstest;
+
+    use super::BuildCommand;
+
+    fn recipe() -> Recipe<'static> {
+        Recipe::builder()
+            .name("Octocat/Test")
+            .description("A test image")
+            .base_image("ghcr.io/ublue-os/silverblue-main")
+            .image_version("40")
+            .modules_ext(ModuleExt::builder().build())
+            .build()
+    }
+
+    #[rstest]
+    #[case(vec![], vec![Platform::Native])]
+    #[case(vec![Platform::LinuxArm64], vec![Platform::LinuxArm64])]
+    #[case(
+        vec![Platform::LinuxAmd64, Platform::LinuxArm64, Platform::LinuxAmd64],
+        vec![Platform::LinuxAmd64, Platform::LinuxArm64]
+    )]
+    fn platforms(#[case] platform: Vec<Platform>, #[case] expected: Vec<Platform>) {
+        let command = BuildCommand::builder().platform(platform).build();
+
+        assert_eq!(command.platforms(), expected);
+    }
+
+    #[rstest]
+    #[case(vec![Platform::LinuxAmd64], Platform::LinuxAmd64, "octocat_test", "Containerfile")]
+    #[case(
+        vec![Platform::LinuxAmd64, Platform::LinuxArm64],
+        Platform::LinuxArm64,
+        "octocat_test-arm64",
+        "Containerfile.arm64"
+    )]
+    fn platform_paths(
+        #[case] platforms: Vec<Platform>,
+        #[case] platform: Platform,
+        #[case] archive_name: &str,
+        #[case] containerfile: &str,
+    ) {
+        let command = BuildCommand::builder().platform(platforms).build();
+        let recipe = recipe();
+
+        assert_eq!(command.archive_name(&recipe, platform), archive_name);
+        assert_eq!(
+            command.archive_path(Path::new("/tmp/archive/"), &recipe, platform, "tar.gz"),
+            format!("/tmp/archive/{archive_name}.tar.gz")
+        );
+        assert_eq!(
+            command
+                .containerfile_path(Path::new("recipes/recipe.yml"), 1, platform)
+                .unwrap(),
+            PathBuf::from(containerfile)
+        );
+    }
+}
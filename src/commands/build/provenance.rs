@@ -0,0 +1,268 @@
+use std::path::Path;
+
+use blue_build_process_management::drivers::{types::Platform, CiDriver, Driver};
+use blue_build_recipe::{Lockfile, Recipe};
+use blue_build_utils::constants::LOCKFILE_PATH;
+use bon::Builder;
+use clap::ValueEnum;
+use miette::{IntoDiagnostic, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    commands::generate::{current_digest, current_scripts_image},
+    shadow,
+};
+
+const BUILD_TYPE: &str = "https://blue-build.org/provenance/build/v1";
+const BUILDER_ID: &str = "https://github.com/blue-build/cli";
+
+/// A SLSA v1 provenance predicate describing how an image was built.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Provenance {
+    build_definition: BuildDefinition,
+    run_details: RunDetails,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BuildDefinition {
+    build_type: &'static str,
+    external_parameters: ExternalParameters,
+    internal_parameters: CiMetadata,
+    resolved_dependencies: Vec<ResourceDescriptor>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExternalParameters {
+    recipe_path: String,
+    recipe: Value,
+    platforms: Vec<String>,
+}
+
+/// The information about the CI system
+/// the image was built in.
+#[derive(Debug, Default, Clone, Serialize, Builder)]
+#[serde(rename_all = "camelCase")]
+pub struct CiMetadata {
+    #[builder(into)]
+    driver: Option<String>,
+
+    #[builder(into)]
+    repository: Option<String>,
+
+    #[builder(into)]
+    registry: Option<String>,
+
+    #[builder(into)]
+    identity: Option<String>,
+
+    #[builder(default)]
+    default_branch: bool,
+}
+
+impl CiMetadata {
+    /// Collects the metadata from the active CI driver.
+    pub fn get() -> Self {
+        Self::builder()
+            .maybe_driver(
+                Driver::get_ci_driver()
+                    .to_possible_value()
+                    .map(|value| value.get_name().to_string()),
+            )
+            .maybe_repository(Driver::get_repo_url().ok())
+            .maybe_registry(Driver::get_registry().ok())
+            .maybe_identity(Driver::keyless_cert_identity().ok())
+            .default_branch(Driver::on_default_branch())
+            .build()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceDescriptor {
+    name: String,
+    uri: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    digest: Option<Value>,
+}
+
+impl ResourceDescriptor {
+    fn image(name: &str, image: &str, digest: Option<&str>) -> Self {
+        Self {
+            name: name.to_string(),
+            uri: format!("oci://{image}"),
+            digest: digest.and_then(|digest| {
+                digest
+                    .split_once(':')
+                    .map(|(algorithm, hex)| serde_json::json!({ algorithm: hex }))
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RunDetails {
+    builder: RunBuilder,
+    metadata: RunMetadata,
+}
+
+#[derive(Debug, Serialize)]
+struct RunBuilder {
+    id: &'static str,
+    version: Value,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RunMetadata {
+    invocation_id: String,
+}
+
+#[bon::bon]
+impl Provenance {
+    #[builder]
+    pub fn new(
+        recipe_path: &Path,
+        recipe: &Recipe<'_>,
+        platforms: &[Platform],
+        resolved_dependencies: Vec<ResourceDescriptor>,
+        ci: CiMetadata,
+        invocation_id: String,
+    ) -> Result<Self> {
+        Ok(Self {
+            build_definition: BuildDefinition {
+                build_type: BUILD_TYPE,
+                external_parameters: ExternalParameters {
+                    recipe_path: recipe_path.display().to_string(),
+                    recipe: serde_json::to_value(recipe).into_diagnostic()?,
+                    platforms: platforms.iter().map(ToString::to_string).collect(),
+                },
+                internal_parameters: ci,
+                resolved_dependencies,
+            },
+            run_details: RunDetails {
+                builder: RunBuilder {
+                    id: BUILDER_ID,
+                    version: serde_json::json!({
+                        "bluebuild": shadow::PKG_VERSION,
+                        "commit": shadow::COMMIT_HASH,
+                    }),
+                },
+                metadata: RunMetadata { invocation_id },
+            },
+        })
+    }
+
+    /// Resolves the base image digest and build scripts
+    /// image that were templated for each platform and
+    /// collects the CI metadata to create the provenance
+    /// of a build.
+    ///
+    /// # Errors
+    /// Will error if the lockfile can't be read
+    /// or the base image can't be inspected.
+    pub fn resolve(recipe_path: &Path, recipe: &Recipe, platforms: &[Platform]) -> Result<Self> {
+        let lockfile = Lockfile::load(LOCKFILE_PATH)?;
+        let base_image = format!("{}:{}", recipe.base_image, recipe.image_version);
+        let resolved_dependencies = platforms
+            .iter()
+            .map(|&platform| -> Result<_> {
+                Ok([
+                    ResourceDescriptor::image(
+                        &format!("base-image/{}", platform.arch()),
+                        &base_image,
+                        Some(&current_digest(&base_image, &lockfile, platform)?),
+                    ),
+                    ResourceDescriptor::image(
+                        &format!("build-scripts/{}", platform.arch()),
+                        &current_scripts_image(&lockfile, platform)?,
+                        None,
+                    ),
+                ])
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+
+        Self::builder()
+            .recipe_path(recipe_path)
+            .recipe(recipe)
+            .platforms(platforms)
+            .resolved_dependencies(resolved_dependencies)
+            .ci(CiMetadata::get())
+            .invocation_id(Driver::get_build_id().to_string())
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use blue_build_process_management::drivers::types::Platform;
+    use blue_build_recipe::Recipe;
+
+    use super::{CiMetadata, Provenance, ResourceDescriptor};
+
+    #[test]
+    fn provenance_predicate() {
+        let recipe = Recipe::builder()
+            .name("test")
+            .description("A test image")
+            .base_image("ghcr.io/ublue-os/silverblue-main")
+            .image_version("40")
+            .modules_ext(blue_build_recipe::ModuleExt::builder().build())
+            .build();
+
+        let provenance = Provenance::builder()
+            .recipe_path(Path::new("recipes/recipe.yml"))
+            .recipe(&recipe)
+            .platforms(&[Platform::LinuxAmd64])
+            .resolved_dependencies(vec![ResourceDescriptor::image(
+                "base-image/amd64",
+                "ghcr.io/ublue-os/silverblue-main:40",
+                Some("sha256:abc123"),
+            )])
+            .ci(CiMetadata::builder()
+                .driver("github")
+                .repository("https://github.com/blue-build/test")
+                .default_branch(true)
+                .build())
+            .invocation_id(String::from("1234"))
+            .build()
+            .unwrap();
+        let predicate = serde_json::to_value(&provenance).unwrap();
+
+        let definition = &predicate["buildDefinition"];
+        assert_eq!(
+            definition["buildType"],
+            "https://blue-build.org/provenance/build/v1"
+        );
+        assert_eq!(
+            definition["externalParameters"]["recipePath"],
+            "recipes/recipe.yml"
+        );
+        assert_eq!(definition["externalParameters"]["recipe"]["name"], "test");
+        assert_eq!(
+            definition["externalParameters"]["platforms"][0],
+            "linux/amd64"
+        );
+        assert_eq!(definition["internalParameters"]["driver"], "github");
+        assert_eq!(definition["internalParameters"]["defaultBranch"], true);
+        assert_eq!(
+            definition["resolvedDependencies"][0]["uri"],
+            "oci://ghcr.io/ublue-os/silverblue-main:40"
+        );
+        assert_eq!(
+            definition["resolvedDependencies"][0]["digest"]["sha256"],
+            "abc123"
+        );
+        assert_eq!(predicate["runDetails"]["metadata"]["invocationId"], "1234");
+    }
+}
@@ -0,0 +1,124 @@
+use std::{collections::BTreeSet, path::PathBuf};
+
+use serde::Serialize;
+
+/// The results of a build that are printed
+/// with `--output json`.
+#[derive(Debug, Serialize)]
+pub struct BuildReport {
+    /// The ID shared by every image in the build.
+    pub build_id: String,
+
+    /// How long the whole build took in seconds.
+    pub duration_secs: f64,
+
+    pub images: Vec<ImageReport>,
+}
+
+/// The results of building the image for a recipe.
+#[derive(Debug, Serialize)]
+pub struct ImageReport {
+    pub recipe: PathBuf,
+
+    /// The image name without a tag.
+    pub image: String,
+
+    pub tags: Vec<String>,
+
+    /// Every image reference that was built or pushed.
+    pub refs: Vec<String>,
+
+    /// The digest of the pushed image. This is the
+    /// manifest list when building for multiple platforms.
+    pub digest: Option<String>,
+
+    pub platforms: Vec<PlatformReport>,
+
+    /// The references of the `cosign` signatures
+    /// attached to the pushed images.
+    pub signatures: Vec<String>,
+
+    /// How long building the image took in seconds.
+    pub duration_secs: f64,
+}
+
+/// The results of building an image for a single platform.
+#[derive(Debug, Serialize)]
+pub struct PlatformReport {
+    pub platform: String,
+
+    /// The digest of the pushed image for the platform.
+    pub digest: Option<String>,
+
+    /// The path of the archive the image was written to.
+    pub archive: Option<String>,
+}
+
+impl ImageReport {
+    /// Fills in the signature references for every pushed digest.
+    #[must_use]
+    pub fn with_signatures(mut self) -> Self {
+        self.signatures = self
+            .digest
+            .iter()
+            .chain(
+                self.platforms
+                    .iter()
+                    .filter_map(|platform| platform.digest.as_ref()),
+            )
+            .map(|digest| signature_ref(&self.image, digest))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        self
+    }
+}
+
+/// The reference of the signature that `cosign`
+/// attaches to an image digest.
+pub fn signature_ref(image: &str, digest: &str) -> String {
+    format!("{image}:{}.sig", digest.replace(':', "-"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{signature_ref, ImageReport, PlatformReport};
+
+    #[test]
+    fn signatures() {
+        let report = ImageReport {
+            recipe: "recipes/recipe.yml".into(),
+            image: String::from("ghcr.io/octocat/my-image"),
+            tags: vec![String::from("latest")],
+            refs: vec![String::from("ghcr.io/octocat/my-image:latest")],
+            digest: Some(String::from("sha256:abc")),
+            platforms: vec![
+                PlatformReport {
+                    platform: String::from("linux/amd64"),
+                    digest: Some(String::from("sha256:def")),
+                    archive: None,
+                },
+                PlatformReport {
+                    platform: String::from("linux/arm64"),
+                    digest: Some(String::from("sha256:abc")),
+                    archive: None,
+                },
+            ],
+            signatures: vec![],
+            duration_secs: 0.0,
+        }
+        .with_signatures();
+
+        assert_eq!(
+            report.signatures,
+            [
+                "ghcr.io/octocat/my-image:sha256-abc.sig",
+                "ghcr.io/octocat/my-image:sha256-def.sig",
+            ]
+        );
+        assert_eq!(
+            signature_ref("localhost/image", "sha256:123"),
+            "localhost/image:sha256-123.sig"
+        );
+    }
+}
@@ -0,0 +1,349 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use blue_build_process_management::drivers::{
+    opts::{GenerateImageNameOpts, GetMetadataOpts},
+    types::Platform,
+    CiDriver, Driver, DriverArgs, InspectDriver,
+};
+use blue_build_recipe::{Lockfile, Recipe, VarOverride};
+use blue_build_utils::constants::{
+    BASE_DIGEST_LABEL, CONFIG_PATH, IMAGE_DIGESTS_LABEL, LOCKFILE_PATH, RECIPE_FILE, RECIPE_PATH,
+};
+use clap::Args;
+use colored::Colorize;
+use log::{debug, error, trace, warn};
+use miette::{bail, Context, IntoDiagnostic, Result};
+use oci_distribution::Reference;
+use serde::Serialize;
+
+use super::{generate::current_digest, BlueBuildCommand, OutputFormat};
+
+/// Exit code when the image is up to date.
+const EXIT_UP_TO_DATE: i32 = 0;
+
+/// Exit code when one of the images the image
+/// was built from has been updated.
+const EXIT_REBUILD: i32 = 1;
+
+/// Exit code when the images couldn't be checked.
+const EXIT_ERROR: i32 = 2;
+
+#[derive(Debug, Clone, Args)]
+pub struct CheckUpdatesCommand {
+    /// The recipe file of the image to check.
+    #[arg()]
+    recipe: Option<PathBuf>,
+
+    /// The published image to check.
+    ///
+    /// Defaults to the `latest` tag of the
+    /// image built from the recipe.
+    #[arg(long)]
+    image: Option<String>,
+
+    /// The registry domain the image is published to.
+    #[arg(long)]
+    registry: Option<String>,
+
+    /// The registry namespace the image is published to.
+    #[arg(long)]
+    registry_namespace: Option<String>,
+
+    /// The platform of the images to check.
+    #[arg(long, default_value = "native")]
+    platform: Platform,
+
+    /// Override a variable in the recipe's `vars:` section.
+    ///
+    /// Can be used multiple times. Values are parsed
+    /// as yaml (e.g. `--var packages=[micro,htop]`).
+    #[arg(long = "var", value_name = "KEY=VALUE")]
+    vars: Vec<VarOverride>,
+
+    /// The format to print the results in.
+    ///
+    /// With JSON output, the command exits with `0`
+    /// whether or not a rebuild is needed.
+    #[arg(short, long, default_value = "text")]
+    output: OutputFormat,
+
+    #[clap(flatten)]
+    drivers: DriverArgs,
+}
+
+/// The results of checking an image for updates
+/// that are printed with `--output json`.
+#[derive(Debug, Serialize)]
+struct UpdateReport {
+    image: String,
+    rebuild: bool,
+    images: Vec<ImageUpdate>,
+}
+
+/// The digest of an image the published image was
+/// built from compared to its current digest.
+#[derive(Debug, Serialize)]
+struct ImageUpdate {
+    image: String,
+
+    /// The digest recorded on the published image.
+    published_digest: Option<String>,
+    current_digest: String,
+    updated: bool,
+}
+
+impl ImageUpdate {
+    fn new(image: String, published_digest: Option<&str>, current_digest: String) -> Self {
+        Self {
+            updated: published_digest != Some(current_digest.as_str()),
+            published_digest: published_digest.map(ToString::to_string),
+            image,
+            current_digest,
+        }
+    }
+}
+
+impl BlueBuildCommand for CheckUpdatesCommand {
+    fn try_run(&mut self) -> Result<()> {
+        let report = self.check()?;
+        self.print(&report)?;
+
+        if report.rebuild && !self.output.is_json() {
+            bail!("{} needs to be rebuilt", report.image);
+        }
+        Ok(())
+    }
+
+    /// Runs the command and exits with `0` when the image is
+    /// up to date, `1` when it needs to be rebuilt, and `2`
+    /// when it couldn't be checked.
+    fn run(&mut self) {
+        std::process::exit(match self.check() {
+            Ok(report) => match self.print(&report) {
+                Ok(()) if report.rebuild && !self.output.is_json() => EXIT_REBUILD,
+                Ok(()) => EXIT_UP_TO_DATE,
+                Err(e) => {
+                    error!("Failed:\n{e:?}");
+                    EXIT_ERROR
+                }
+            },
+            Err(e) => {
+                error!("Failed:\n{e:?}");
+                EXIT_ERROR
+            }
+        });
+    }
+}
+
+impl CheckUpdatesCommand {
+    /// Compares the digests recorded on the published image
+    /// with the current digests of the images it was built from.
+    fn check(&self) -> Result<UpdateReport> {
+        trace!("CheckUpdatesCommand::check()");
+        self.output.redirect_stdout()?;
+
+        Driver::init(self.drivers);
+
+        let recipe_path = self.recipe.clone().unwrap_or_else(|| {
+            let legacy_path = Path::new(CONFIG_PATH);
+            let recipe_path = Path::new(RECIPE_PATH);
+            if recipe_path.exists() && recipe_path.is_dir() {
+                recipe_path.join(RECIPE_FILE)
+            } else {
+                warn!("Use of {CONFIG_PATH} for recipes is deprecated, please move your recipe files into {RECIPE_PATH}");
+                legacy_path.join(RECIPE_FILE)
+            }
+        });
+        let recipe = Recipe::parse_with_vars(&recipe_path, &self.vars)?;
+        let lockfile = Lockfile::load(LOCKFILE_PATH)?;
+
+        let image = self.published_image(&recipe)?;
+        let reference: Reference = image
+            .parse()
+            .into_diagnostic()
+            .with_context(|| format!("Unable to parse image reference {image}"))?;
+        let labels = Driver::get_metadata(
+            &GetMetadataOpts::builder()
+                .image(format!(
+                    "{}/{}",
+                    reference.resolve_registry(),
+                    reference.repository()
+                ))
+                .tag(reference.tag().unwrap_or("latest"))
+                .platform(self.platform)
+                .build(),
+        )
+        .with_context(|| format!("Failed to inspect the published image {image}"))?
+        .labels;
+        let label = |name: &str| labels.get(name).and_then(|value| value.as_str());
+
+        let base_image = format!("{}:{}", recipe.base_image, recipe.image_version);
+        let os_version = match lockfile
+            .get(&base_image)
+            .and_then(|locked| locked.os_version)
+        {
+            Some(os_version) => os_version,
+            None => Driver::get_os_version()
+                .oci_ref(&recipe.base_image_ref()?)
+                .platform(self.platform)
+                .call()?,
+        };
+
+        let mut images = vec![ImageUpdate::new(
+            base_image.clone(),
+            label(BASE_DIGEST_LABEL),
+            current_digest(&base_image, &lockfile, self.platform)?,
+        )];
+
+        let published_digests = label(IMAGE_DIGESTS_LABEL)
+            .map(parse_image_digests)
+            .unwrap_or_default();
+        for dependency in recipe.update_image_refs(os_version) {
+            let digest = current_digest(&dependency, &lockfile, self.platform)?;
+            images.push(ImageUpdate::new(
+                dependency.clone(),
+                published_digests.get(dependency.as_str()).copied(),
+                digest,
+            ));
+        }
+
+        for update in images.iter().filter(|update| update.updated) {
+            debug!(
+                "{} changed from {:?} to {}",
+                update.image, update.published_digest, update.current_digest
+            );
+        }
+
+        Ok(UpdateReport {
+            rebuild: images.iter().any(|update| update.updated),
+            image,
+            images,
+        })
+    }
+
+    /// The published image to compare against.
+    fn published_image(&self, recipe: &Recipe) -> Result<String> {
+        if let Some(image) = self.image.as_ref() {
+            return Ok(image.clone());
+        }
+
+        let image_name = Driver::generate_image_name(
+            GenerateImageNameOpts::builder()
+                .name(recipe.name.trim())
+                .maybe_registry(self.registry.as_deref())
+                .maybe_registry_namespace(self.registry_namespace.as_deref())
+                .build(),
+        )?;
+
+        Ok(format!("{}:latest", image_name.whole()))
+    }
+
+    fn print(&self, report: &UpdateReport) -> Result<()> {
+        if self.output.is_json() {
+            OutputFormat::print_json(report)
+        } else {
+            println!("{}", text_report(report));
+            Ok(())
+        }
+    }
+}
+
+/// Parses the `<image>@<digest>` references
+/// of the image digests label.
+fn parse_image_digests(label: &str) -> HashMap<&str, &str> {
+    label
+        .split(',')
+        .filter_map(|image| image.trim().rsplit_once('@'))
+        .collect()
+}
+
+fn text_report(report: &UpdateReport) -> String {
+    let status = |status: &str| format!("{status:<10}").bold();
+
+    let mut lines = vec![format!("{}{}", status("Image:"), report.image)];
+    lines.extend(report.images.iter().map(|update| {
+        match (&update.published_digest, update.updated) {
+            (_, false) => format!("{}{}", status("CURRENT").green(), update.image),
+            (Some(published), true) => format!(
+                "{}{} ({published} -> {})",
+                status("UPDATED").yellow(),
+                update.image,
+                update.current_digest
+            ),
+            (None, true) => format!(
+                "{}{} (no digest recorded on the image)",
+                status("UNKNOWN").yellow(),
+                update.image
+            ),
+        }
+    }));
+    lines.push(if report.rebuild {
+        format!("{}{}", status("Result:"), "REBUILD".bold().yellow())
+    } else {
+        format!("{}{}", status("Result:"), "UP TO DATE".bold().green())
+    });
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{parse_image_digests, text_report, ImageUpdate, UpdateReport};
+
+    #[test]
+    fn image_digests_label() {
+        assert_eq!(
+            parse_image_digests(
+                "ghcr.io/blue-build/modules:latest@sha256:abc,ghcr.io/ublue-os/akmods:main-40@sha256:def"
+            ),
+            HashMap::from([
+                ("ghcr.io/blue-build/modules:latest", "sha256:abc"),
+                ("ghcr.io/ublue-os/akmods:main-40", "sha256:def"),
+            ])
+        );
+        assert!(parse_image_digests("").is_empty());
+    }
+
+    #[test]
+    fn report_updates() {
+        colored::control::set_override(false);
+
+        let images = vec![
+            ImageUpdate::new(
+                String::from("ghcr.io/ublue-os/silverblue-main:40"),
+                Some("sha256:abc"),
+                String::from("sha256:abc"),
+            ),
+            ImageUpdate::new(
+                String::from("ghcr.io/blue-build/modules:latest"),
+                Some("sha256:abc"),
+                String::from("sha256:def"),
+            ),
+            ImageUpdate::new(
+                String::from("ghcr.io/ublue-os/akmods:main-40"),
+                None,
+                String::from("sha256:def"),
+            ),
+        ];
+        assert!(!images[0].updated);
+        assert!(images[1].updated);
+
+        assert_eq!(
+            text_report(&UpdateReport {
+                image: String::from("ghcr.io/octocat/test:latest"),
+                rebuild: true,
+                images,
+            }),
+            "Image:    ghcr.io/octocat/test:latest
+CURRENT   ghcr.io/ublue-os/silverblue-main:40
+UPDATED   ghcr.io/blue-build/modules:latest (sha256:abc -> sha256:def)
+UNKNOWN   ghcr.io/ublue-os/akmods:main-40 (no digest recorded on the image)
+Result:   REBUILD"
+        );
+    }
+}
@@ -0,0 +1,569 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs::{self, File},
+    io::{BufRead, BufReader},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use blue_build_process_management::drivers::{
+    opts::RunOpts,
+    types::{ImageSource, MountedImage, RunDriverType},
+    Driver, DriverArgs, ImageMountDriver, RunDriver,
+};
+use clap::Args;
+use colored::Colorize;
+use indicatif::ProgressBar;
+use log::{debug, trace};
+use miette::{bail, Context, IntoDiagnostic, Result};
+use serde::Serialize;
+use serde_json::Value;
+use walkdir::WalkDir;
+
+use super::{BlueBuildCommand, OutputFormat};
+
+/// The directories of the image whose files are compared.
+const COMPARED_DIRS: &[&str] = &["etc", "usr"];
+
+const RPM_QUERY_FORMAT: &str = "%{NAME}.%{ARCH}\t%|EPOCH?{%{EPOCH}:}:{}|%{VERSION}-%{RELEASE}\n";
+
+#[derive(Debug, Clone, Args)]
+pub struct DiffCommand {
+    /// The image to compare from.
+    ///
+    /// This can be an image in local storage or a
+    /// registry, or an `oci-archive:<path>`.
+    image_a: ImageSource,
+
+    /// The image to compare to.
+    ///
+    /// This can be an image in local storage or a
+    /// registry, or an `oci-archive:<path>`.
+    image_b: ImageSource,
+
+    /// The format to print the differences in.
+    #[arg(short, long, default_value_t, value_enum)]
+    output: OutputFormat,
+
+    #[clap(flatten)]
+    drivers: DriverArgs,
+}
+
+impl BlueBuildCommand for DiffCommand {
+    fn try_run(&mut self) -> Result<()> {
+        trace!("DiffCommand::try_run()");
+        self.output.redirect_stdout()?;
+
+        Driver::init(self.drivers);
+
+        if !matches!(Driver::get_run_driver(), RunDriverType::Podman) {
+            bail!("Comparing images requires the podman run driver");
+        }
+
+        if !nix::unistd::Uid::effective().is_root() {
+            bail!(
+                "You must be root to mount images, try running with {} or in {}",
+                "sudo".bold(),
+                "podman unshare".bold()
+            );
+        }
+
+        let progress = ProgressBar::new_spinner();
+        progress.enable_steady_tick(Duration::from_millis(100));
+        progress.set_message(format!("Comparing {} to {}...", self.image_a, self.image_b));
+
+        let image_a = Driver::mount_image(&self.image_a)?;
+        let image_b = match Driver::mount_image(&self.image_b) {
+            Ok(image_b) => image_b,
+            Err(e) => {
+                Driver::unmount_image(image_a)?;
+                return Err(e);
+            }
+        };
+
+        let report = DiffReport::try_new(&image_a, &image_b);
+        let cleanup_a = Driver::unmount_image(image_a);
+        let cleanup_b = Driver::unmount_image(image_b);
+        progress.finish_and_clear();
+
+        let report = report?;
+        cleanup_a?;
+        cleanup_b?;
+
+        if self.output.is_json() {
+            OutputFormat::print_json(&report)
+        } else {
+            println!("{}", report.text());
+            Ok(())
+        }
+    }
+}
+
+/// The differences between two images.
+#[derive(Debug, Serialize)]
+struct DiffReport {
+    image_a: String,
+    image_b: String,
+
+    /// The versions of each RPM keyed by `name.arch`.
+    ///
+    /// Some packages, like `kernel` and `gpg-pubkey`,
+    /// can have more than one version installed.
+    packages: Changes<Vec<String>>,
+
+    /// The files under `/etc` and `/usr` keyed by path.
+    files: Changes<FileInfo>,
+
+    labels: Changes<Value>,
+}
+
+/// The entries that were added, removed,
+/// or changed between two images.
+#[derive(Debug, Serialize, PartialEq)]
+struct Changes<V> {
+    added: Vec<Entry<V>>,
+    removed: Vec<Entry<V>>,
+    changed: Vec<Change<V>>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct Entry<V> {
+    name: String,
+    value: V,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct Change<V> {
+    name: String,
+    from: V,
+    to: V,
+}
+
+/// The metadata of a file that is compared.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+struct FileInfo {
+    kind: FileKind,
+    mode: String,
+    uid: u32,
+    gid: u32,
+
+    /// The size of regular files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+
+    /// The target of symlinks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<PathBuf>,
+
+    #[serde(skip)]
+    mtime: i64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum FileKind {
+    File,
+    Dir,
+    Symlink,
+    Other,
+}
+
+impl DiffReport {
+    fn try_new(image_a: &MountedImage, image_b: &MountedImage) -> Result<Self> {
+        trace!(
+            "DiffReport::try_new({}, {})",
+            image_a.source,
+            image_b.source
+        );
+
+        debug!("Comparing packages");
+        let packages = compare(&packages(image_a)?, &packages(image_b)?, |_, a, b| a != b);
+
+        debug!("Comparing files");
+        let files = compare(
+            &files(&image_a.mount)?,
+            &files(&image_b.mount)?,
+            |path, a, b| {
+                !a.same_metadata(b)
+                    || (a.kind == FileKind::File
+                        && a.mtime != b.mtime
+                        && contents_differ(&image_a.mount, &image_b.mount, path))
+            },
+        );
+
+        debug!("Comparing labels");
+        let labels = compare(
+            &image_a.labels.clone().into_iter().collect(),
+            &image_b.labels.clone().into_iter().collect(),
+            |_, a, b| a != b,
+        );
+
+        Ok(Self {
+            image_a: image_a.source.to_string(),
+            image_b: image_b.source.to_string(),
+            packages,
+            files,
+            labels,
+        })
+    }
+
+    fn text(&self) -> String {
+        let mut lines = vec![format!(
+            "Comparing {} to {}",
+            self.image_a.bold(),
+            self.image_b.bold()
+        )];
+
+        lines.extend(
+            self.packages
+                .text("Packages", |versions| versions.join(", ")),
+        );
+        lines.extend(self.files.text("Files", FileInfo::summary));
+        lines.extend(self.labels.text("Labels", |value| match value {
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        }));
+
+        lines.join("\n")
+    }
+}
+
+impl<V> Changes<V> {
+    fn text(&self, title: &str, display: impl Fn(&V) -> String) -> Vec<String> {
+        let mut lines = vec![
+            String::new(),
+            format!(
+                "{}: {} added, {} removed, {} changed",
+                title.bold(),
+                self.added.len(),
+                self.removed.len(),
+                self.changed.len()
+            ),
+        ];
+
+        lines.extend(
+            self.added.iter().map(|Entry { name, value }| {
+                format!("  {} {name} {}", "+".green(), display(value))
+            }),
+        );
+        lines.extend(
+            self.removed
+                .iter()
+                .map(|Entry { name, value }| format!("  {} {name} {}", "-".red(), display(value))),
+        );
+        lines.extend(self.changed.iter().map(|Change { name, from, to }| {
+            format!(
+                "  {} {name} {} -> {}",
+                "~".yellow(),
+                display(from),
+                display(to)
+            )
+        }));
+
+        lines
+    }
+}
+
+impl FileInfo {
+    fn from_metadata(metadata: &fs::Metadata, target: Option<PathBuf>) -> Self {
+        let kind = if metadata.is_symlink() {
+            FileKind::Symlink
+        } else if metadata.is_dir() {
+            FileKind::Dir
+        } else if metadata.is_file() {
+            FileKind::File
+        } else {
+            FileKind::Other
+        };
+
+        Self {
+            kind,
+            mode: format!("{:04o}", metadata.mode() & 0o7777),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            size: (kind == FileKind::File).then_some(metadata.len()),
+            target,
+            mtime: metadata.mtime(),
+        }
+    }
+
+    /// Whether the files are the same without
+    /// looking at their modification times.
+    fn same_metadata(&self, other: &Self) -> bool {
+        Self {
+            mtime: other.mtime,
+            ..self.clone()
+        } == *other
+    }
+
+    fn summary(&self) -> String {
+        let mut summary =
+            format!("({:?} {} {}:{}", self.kind, self.mode, self.uid, self.gid).to_lowercase();
+
+        if let Some(size) = self.size {
+            _ = write!(summary, " {size}B");
+        }
+        if let Some(target) = &self.target {
+            _ = write!(summary, " -> {}", target.display());
+        }
+        summary.push(')');
+        summary
+    }
+}
+
+/// Compares the entries of two images, using `changed`
+/// to check entries that exist in both.
+fn compare<V: Clone>(
+    a: &BTreeMap<String, V>,
+    b: &BTreeMap<String, V>,
+    changed: impl Fn(&str, &V, &V) -> bool,
+) -> Changes<V> {
+    let entry = |(name, value): (&String, &V)| Entry {
+        name: name.clone(),
+        value: value.clone(),
+    };
+
+    Changes {
+        added: b
+            .iter()
+            .filter(|(name, _)| !a.contains_key(*name))
+            .map(entry)
+            .collect(),
+        removed: a
+            .iter()
+            .filter(|(name, _)| !b.contains_key(*name))
+            .map(entry)
+            .collect(),
+        changed: a
+            .iter()
+            .filter_map(|(name, from)| {
+                b.get(name)
+                    .filter(|to| changed(name, from, to))
+                    .map(|to| Change {
+                        name: name.clone(),
+                        from: from.clone(),
+                        to: to.clone(),
+                    })
+            })
+            .collect(),
+    }
+}
+
+/// Gets the versions of each RPM installed in the image.
+fn packages(image: &MountedImage) -> Result<BTreeMap<String, Vec<String>>> {
+    let output = Driver::run_output(
+        &RunOpts::builder()
+            .image(image.image.to_string())
+            .args(bon::vec!["rpm", "-qa", "--qf", RPM_QUERY_FORMAT])
+            .remove(true)
+            .build(),
+    )?;
+
+    if !output.status.success() {
+        bail!(
+            "Failed to query the RPMs in {}:\n{}",
+            image.source,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(parse_packages(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn parse_packages(output: &str) -> BTreeMap<String, Vec<String>> {
+    let mut packages = BTreeMap::<String, Vec<String>>::new();
+
+    for (name, version) in output.lines().filter_map(|line| line.split_once('\t')) {
+        packages
+            .entry(name.to_string())
+            .or_default()
+            .push(version.to_string());
+    }
+    for versions in packages.values_mut() {
+        versions.sort();
+    }
+
+    packages
+}
+
+/// Gets the metadata of every file in the compared
+/// directories of the image's filesystem.
+fn files(mount: &Path) -> Result<BTreeMap<String, FileInfo>> {
+    COMPARED_DIRS
+        .iter()
+        .map(|dir| mount.join(dir))
+        .filter(|dir| dir.exists())
+        .flat_map(|dir| WalkDir::new(dir).min_depth(1))
+        .map(|entry| {
+            let entry = entry.into_diagnostic()?;
+            let metadata = entry
+                .metadata()
+                .into_diagnostic()
+                .with_context(|| format!("Failed to read {}", entry.path().display()))?;
+            let target = entry
+                .path_is_symlink()
+                .then(|| fs::read_link(entry.path()))
+                .transpose()
+                .into_diagnostic()?;
+            let path = entry.path().strip_prefix(mount).into_diagnostic()?;
+
+            Ok((
+                format!("/{}", path.display()),
+                FileInfo::from_metadata(&metadata, target),
+            ))
+        })
+        .collect()
+}
+
+/// Compares the contents of a file in both images a chunk at a
+/// time so that large files aren't read into memory.
+fn contents_differ(mount_a: &Path, mount_b: &Path, path: &str) -> bool {
+    let path = path.trim_start_matches('/');
+
+    let (Ok(a), Ok(b)) = (
+        File::open(mount_a.join(path)),
+        File::open(mount_b.join(path)),
+    ) else {
+        return true;
+    };
+    let (mut a, mut b) = (BufReader::new(a), BufReader::new(b));
+
+    loop {
+        let (Ok(chunk_a), Ok(chunk_b)) = (a.fill_buf(), b.fill_buf()) else {
+            return true;
+        };
+        if chunk_a.is_empty() || chunk_b.is_empty() {
+            return chunk_a.len() != chunk_b.len();
+        }
+
+        let len = chunk_a.len().min(chunk_b.len());
+        if chunk_a[..len] != chunk_b[..len] {
+            return true;
+        }
+        a.consume(len);
+        b.consume(len);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::BTreeMap, fs};
+
+    use super::{
+        compare, contents_differ, parse_packages, Change, Changes, Entry, FileInfo, FileKind,
+    };
+
+    #[test]
+    fn compare_packages() {
+        let a = parse_packages(
+            "bash.x86_64\t5.2.26-3.fc40\n\
+            vim-minimal.x86_64\t2:9.1.393-1.fc40\n\
+            kernel.x86_64\t6.10.10-200.fc40\n\
+            kernel.x86_64\t6.10.12-200.fc40\n\
+            nano.x86_64\t7.2-7.fc40\n",
+        );
+        let b = parse_packages(
+            "bash.x86_64\t5.2.26-3.fc40\n\
+            vim-minimal.x86_64\t2:9.1.452-1.fc40\n\
+            kernel.x86_64\t6.10.12-200.fc40\n\
+            kernel.x86_64\t6.10.10-200.fc40\n\
+            micro.x86_64\t2.0.13-4.fc40\n",
+        );
+
+        assert_eq!(
+            compare(&a, &b, |_, a, b| a != b),
+            Changes {
+                added: vec![Entry {
+                    name: String::from("micro.x86_64"),
+                    value: vec![String::from("2.0.13-4.fc40")],
+                }],
+                removed: vec![Entry {
+                    name: String::from("nano.x86_64"),
+                    value: vec![String::from("7.2-7.fc40")],
+                }],
+                changed: vec![Change {
+                    name: String::from("vim-minimal.x86_64"),
+                    from: vec![String::from("2:9.1.393-1.fc40")],
+                    to: vec![String::from("2:9.1.452-1.fc40")],
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn multi_installed_packages() {
+        let a = parse_packages(
+            "gpg-pubkey.(none)\tf4a80eb5-53a7ff4b\n\
+            gpg-pubkey.(none)\ta15b79cc-63d04c2c\n",
+        );
+        let b = parse_packages("gpg-pubkey.(none)\ta15b79cc-63d04c2c\n");
+
+        assert_eq!(
+            a["gpg-pubkey.(none)"],
+            ["a15b79cc-63d04c2c", "f4a80eb5-53a7ff4b"]
+        );
+        assert_eq!(
+            compare(&a, &b, |_, a, b| a != b).changed,
+            [Change {
+                name: String::from("gpg-pubkey.(none)"),
+                from: vec![
+                    String::from("a15b79cc-63d04c2c"),
+                    String::from("f4a80eb5-53a7ff4b")
+                ],
+                to: vec![String::from("a15b79cc-63d04c2c")],
+            }]
+        );
+    }
+
+    #[test]
+    fn file_contents() {
+        let mount_a = tempfile::tempdir().unwrap();
+        let mount_b = tempfile::tempdir().unwrap();
+        let large = "a".repeat(100_000);
+
+        for (name, a, b) in [
+            ("same", large.clone(), large.clone()),
+            ("end", large.clone(), format!("{}b", &large[1..])),
+            ("length", large.clone(), format!("{large}a")),
+        ] {
+            fs::write(mount_a.path().join(name), a).unwrap();
+            fs::write(mount_b.path().join(name), b).unwrap();
+        }
+
+        assert!(!contents_differ(mount_a.path(), mount_b.path(), "/same"));
+        assert!(contents_differ(mount_a.path(), mount_b.path(), "/end"));
+        assert!(contents_differ(mount_a.path(), mount_b.path(), "/length"));
+        assert!(contents_differ(mount_a.path(), mount_b.path(), "/missing"));
+    }
+
+    #[test]
+    fn file_metadata() {
+        let file = FileInfo {
+            kind: FileKind::File,
+            mode: String::from("0644"),
+            uid: 0,
+            gid: 0,
+            size: Some(42),
+            target: None,
+            mtime: 1,
+        };
+
+        assert!(file.same_metadata(&FileInfo {
+            mtime: 2,
+            ..file.clone()
+        }));
+        assert!(!file.same_metadata(&FileInfo {
+            mode: String::from("0755"),
+            ..file.clone()
+        }));
+        assert_eq!(file.summary(), "(file 0644 0:0 42B)");
+
+        let files = BTreeMap::from([(String::from("/etc/os-release"), file)]);
+        assert_eq!(
+            compare(&files, &files, |_, a, b| !a.same_metadata(b)).changed,
+            []
+        );
+    }
+}
@@ -6,23 +6,32 @@ use std::{
 use blue_build_process_management::drivers::{
     opts::GetMetadataOpts, types::Platform, CiDriver, Driver, DriverArgs, InspectDriver,
 };
-use blue_build_recipe::Recipe;
-use blue_build_template::{ContainerFileTemplate, Template};
+use blue_build_recipe::{Lockfile, Recipe, VarOverride};
+use blue_build_template::{ContainerFileTemplate, ModuleConditions, TemplateOverrides};
 use blue_build_utils::{
-    constants::{BUILD_SCRIPTS_IMAGE_REF, CONFIG_PATH, RECIPE_FILE, RECIPE_PATH},
+    constants::{
+        BB_TEMPLATE_DIR, BUILD_SCRIPTS_IMAGE_REF, CONFIG_PATH, LOCKFILE_PATH, RECIPE_FILE,
+        RECIPE_PATH,
+    },
     syntax_highlighting::{self, DefaultThemes},
 };
 use bon::Builder;
 use cached::proc_macro::cached;
 use clap::{crate_version, Args};
+use colored::Colorize;
 use log::{debug, info, trace, warn};
-use miette::{IntoDiagnostic, Result};
+use miette::{bail, Context, IntoDiagnostic, Result};
+use oci_distribution::Reference;
+use serde::Serialize;
+
+#[cfg(feature = "validate")]
+use blue_build_utils::constants::BB_SCHEMA_DIR;
 
 #[cfg(feature = "validate")]
 use crate::commands::validate::ValidateCommand;
 use crate::shadow;
 
-use super::BlueBuildCommand;
+use super::{BlueBuildCommand, OutputFormat};
 
 #[derive(Debug, Clone, Args, Builder)]
 pub struct GenerateCommand {
@@ -32,9 +41,9 @@ pub struct GenerateCommand {
     recipe: Option<PathBuf>,
 
     /// File to output to instead of STDOUT
-    #[arg(short, long)]
+    #[arg(long)]
     #[builder(into)]
-    output: Option<PathBuf>,
+    output_file: Option<PathBuf>,
 
     /// The registry domain the image will be published to.
     ///
@@ -53,7 +62,8 @@ pub struct GenerateCommand {
     registry_namespace: Option<String>,
 
     /// Instead of creating a Containerfile, display
-    /// the full recipe after traversing all `from-file` properties.
+    /// the full recipe after traversing all `from-file` properties
+    /// and interpolating all variables.
     ///
     /// This can be used to help debug the order
     /// you defined your recipe.
@@ -74,14 +84,112 @@ pub struct GenerateCommand {
     #[builder(default)]
     platform: Platform,
 
+    /// Override a variable in the recipe's `vars:` section.
+    ///
+    /// Can be used multiple times. Values are parsed
+    /// as yaml (e.g. `--var packages=[micro,htop]`).
+    #[arg(long = "var", value_name = "KEY=VALUE")]
+    #[builder(default, into)]
+    vars: Vec<VarOverride>,
+
+    /// A directory of templates that replace parts
+    /// of the built-in `Containerfile` template.
+    ///
+    /// A `Containerfile.j2`, `stages.j2`, or `modules/<module-type>.j2`
+    /// in the directory is rendered in place of the built-in template
+    /// for that part, which it can include with `{{ default }}`.
+    #[arg(long, env = BB_TEMPLATE_DIR)]
+    #[builder(into)]
+    template_dir: Option<PathBuf>,
+
+    /// A directory containing the json schemas to validate against.
+    ///
+    /// Schemas are otherwise taken from the cache in
+    /// `~/.cache/bluebuild/schemas` or retrieved from
+    /// <https://schema.blue-build.org>, falling back to
+    /// the ones embedded in the binary when offline.
+    #[cfg(feature = "validate")]
+    #[arg(long, env = BB_SCHEMA_DIR)]
+    #[builder(into)]
+    schema_dir: Option<PathBuf>,
+
+    /// The major version of the base image's OS.
+    ///
+    /// When set, the base image isn't inspected
+    /// to find its version.
+    #[arg(long)]
+    os_version: Option<u64>,
+
+    /// The digest of the base image (e.g. `sha256:...`).
+    ///
+    /// When set, the base image isn't inspected
+    /// to find its digest.
+    #[arg(long, value_parser = parse_digest)]
+    #[builder(into)]
+    base_digest: Option<String>,
+
+    /// The image containing the build scripts.
+    ///
+    /// When set, the registry isn't checked for the build
+    /// scripts image matching this version of the CLI.
+    #[arg(long)]
+    #[builder(into)]
+    build_scripts_image: Option<String>,
+
+    /// Fail instead of contacting a registry.
+    ///
+    /// This requires `--os-version`, `--base-digest`, and
+    /// `--build-scripts-image`, or a `bluebuild.lock` with
+    /// the recipe's base image. No container tools are
+    /// needed to generate the Containerfile.
+    #[arg(long)]
+    #[builder(default)]
+    offline: bool,
+
+    /// The format to print the results in.
+    ///
+    /// The JSON output contains the resolved base image,
+    /// the build ID, and the Containerfile, or its path
+    /// when using `--output-file`.
+    #[arg(short, long, default_value = "text")]
+    #[builder(default)]
+    output: OutputFormat,
+
     #[clap(flatten)]
     #[builder(default)]
     drivers: DriverArgs,
 }
 
+/// The results of templating a recipe that are
+/// printed with `--output json`.
+#[derive(Debug, Serialize)]
+struct GenerateReport<'a> {
+    recipe: &'a Path,
+
+    /// The name of the image that would be built.
+    image: String,
+    base_image: String,
+    base_digest: &'a str,
+    os_version: u64,
+    build_id: String,
+    build_scripts_image: &'a str,
+
+    /// The file the Containerfile was written to.
+    output_file: Option<&'a Path>,
+
+    /// The Containerfile when it wasn't written to a file.
+    containerfile: Option<&'a str>,
+}
+
 impl BlueBuildCommand for GenerateCommand {
     fn try_run(&mut self) -> Result<()> {
-        Driver::init(self.drivers);
+        self.output.redirect_stdout()?;
+
+        if self.offline {
+            Driver::init_ci();
+        } else {
+            Driver::init(self.drivers);
+        }
 
         self.template_file()
     }
@@ -105,6 +213,8 @@ impl GenerateCommand {
         #[cfg(feature = "validate")]
         ValidateCommand::builder()
             .recipe(recipe_path.clone())
+            .vars(self.vars.clone())
+            .maybe_schema_dir(self.schema_dir.clone())
             .build()
             .try_run()?;
 
@@ -117,11 +227,20 @@ impl GenerateCommand {
         };
 
         debug!("Deserializing recipe");
-        let recipe = Recipe::parse(&recipe_path)?;
+        let mut recipe = Recipe::parse_with_vars(&recipe_path, &self.vars)?;
         trace!("recipe_de: {recipe:#?}");
 
+        let lockfile = Lockfile::load(LOCKFILE_PATH)?;
+
         if self.display_full_recipe {
-            if let Some(output) = self.output.as_ref() {
+            if recipe.has_module_conditions() {
+                let os_version = self.resolve_os_version(&recipe, &lockfile)?;
+                self.skip_modules(&mut recipe, os_version)?;
+            }
+
+            if self.output.is_json() {
+                OutputFormat::print_json(&recipe)?;
+            } else if let Some(output) = self.output_file.as_ref() {
                 std::fs::write(output, serde_yaml::to_string(&recipe).into_diagnostic()?)
                     .into_diagnostic()?;
             } else {
@@ -132,44 +251,300 @@ impl GenerateCommand {
 
         info!("Templating for recipe at {}", recipe_path.display());
 
+        let (os_version, base_digest, build_scripts_image) =
+            self.resolve_image_info(&recipe, &lockfile)?;
+        self.skip_modules(&mut recipe, os_version)?;
+        let image_digests = if self.offline {
+            pinned_image_digests(&recipe, &lockfile, os_version)
+        } else {
+            resolve_image_digests(&recipe, &lockfile, os_version, self.platform)?
+        };
+        let build_scripts_image = lockfile.image_ref(&build_scripts_image);
+        let build_id = Driver::get_build_id();
+
+        let overrides = self
+            .template_dir
+            .as_deref()
+            .map(template_overrides)
+            .transpose()?;
+
         let template = ContainerFileTemplate::builder()
-            .os_version(
-                Driver::get_os_version()
-                    .oci_ref(&recipe.base_image_ref()?)
-                    .platform(self.platform)
-                    .call()?,
-            )
-            .build_id(Driver::get_build_id())
+            .os_version(os_version)
+            .build_id(build_id)
             .recipe(&recipe)
             .recipe_path(recipe_path.as_path())
-            .registry(registry)
+            .registry(registry.as_str())
             .repo(Driver::get_repo_url()?)
-            .build_scripts_image(determine_scripts_tag(self.platform)?)
-            .base_digest(
-                Driver::get_metadata(
-                    &GetMetadataOpts::builder()
-                        .image(&*recipe.base_image)
-                        .tag(&*recipe.image_version)
-                        .platform(self.platform)
-                        .build(),
-                )?
-                .digest,
-            )
+            .build_scripts_image(build_scripts_image.as_str())
+            .base_digest(base_digest.as_str())
+            .lockfile(lockfile)
+            .image_digests(image_digests)
+            .maybe_overrides(overrides)
             .build();
 
-        let output_str = template.render().into_diagnostic()?;
-        if let Some(output) = self.output.as_ref() {
+        let output_str = template.render_containerfile().into_diagnostic()?;
+        if let Some(output) = self.output_file.as_ref() {
             debug!("Templating to file {}", output.display());
             trace!("Containerfile:\n{output_str}");
 
-            std::fs::write(output, output_str).into_diagnostic()?;
-        } else {
+            std::fs::write(output, &output_str).into_diagnostic()?;
+        }
+
+        if self.output.is_json() {
+            OutputFormat::print_json(&GenerateReport {
+                recipe: &recipe_path,
+                image: format!("{registry}/{}", recipe.name),
+                base_image: format!("{}:{}", recipe.base_image, recipe.image_version),
+                base_digest: &base_digest,
+                os_version,
+                build_id: build_id.to_string(),
+                build_scripts_image: &build_scripts_image,
+                output_file: self.output_file.as_deref(),
+                containerfile: self.output_file.is_none().then_some(output_str.as_str()),
+            })?;
+        } else if self.output_file.is_none() {
             debug!("Templating to stdout");
             syntax_highlighting::print(&output_str, "Dockerfile", self.syntax_theme)?;
         }
 
         Ok(())
     }
+
+    /// Marks the modules whose `if:` condition
+    /// is false for this build as skipped.
+    fn skip_modules(&self, recipe: &mut Recipe, os_version: u64) -> Result<()> {
+        let conditions = ModuleConditions::new(
+            os_version,
+            &self.platform.to_string(),
+            self.platform.arch(),
+            recipe.vars.as_ref(),
+        );
+
+        recipe.skip_modules(|condition| {
+            conditions
+                .evaluate(condition)
+                .into_diagnostic()
+                .with_context(|| format!("Failed to evaluate the condition `{condition}`"))
+        })
+    }
+
+    /// Gets the OS version of the base image from
+    /// the CLI args, the lockfile, or the registry.
+    fn resolve_os_version(&self, recipe: &Recipe, lockfile: &Lockfile) -> Result<u64> {
+        let locked_base = lockfile.get(&format!("{}:{}", recipe.base_image, recipe.image_version));
+
+        match self
+            .os_version
+            .or_else(|| locked_base.and_then(|locked| locked.os_version))
+        {
+            Some(os_version) => Ok(os_version),
+            None if self.offline => {
+                bail!(
+                    "Evaluating the conditions of modules offline requires {} or a {} created with {}",
+                    "--os-version".bold(),
+                    LOCKFILE_PATH.bold(),
+                    "bluebuild lock".bold()
+                );
+            }
+            None => Driver::get_os_version()
+                .oci_ref(&recipe.base_image_ref()?)
+                .platform(self.platform)
+                .call(),
+        }
+    }
+
+    /// Gets the OS version and digest of the base image, and the
+    /// build scripts image, from the CLI args, the lockfile, or
+    /// the registry.
+    fn resolve_image_info(
+        &self,
+        recipe: &Recipe,
+        lockfile: &Lockfile,
+    ) -> Result<(u64, String, String)> {
+        let locked_base = lockfile.get(&format!("{}:{}", recipe.base_image, recipe.image_version));
+        let os_version = self
+            .os_version
+            .or_else(|| locked_base.and_then(|locked| locked.os_version));
+        let base_digest = self
+            .base_digest
+            .clone()
+            .or_else(|| locked_base.map(|locked| locked.digest.clone()));
+        let build_scripts_image = self
+            .build_scripts_image
+            .clone()
+            .or_else(|| lockfile.build_scripts_image.clone());
+
+        if self.offline {
+            let (Some(os_version), Some(base_digest), Some(build_scripts_image)) =
+                (os_version, base_digest, build_scripts_image)
+            else {
+                bail!(
+                    "Generating offline requires {}, {}, and {}, or a {} created with {}",
+                    "--os-version".bold(),
+                    "--base-digest".bold(),
+                    "--build-scripts-image".bold(),
+                    LOCKFILE_PATH.bold(),
+                    "bluebuild lock".bold()
+                );
+            };
+            return Ok((os_version, base_digest, build_scripts_image));
+        }
+
+        let os_version = match os_version {
+            Some(os_version) => os_version,
+            None => Driver::get_os_version()
+                .oci_ref(&recipe.base_image_ref()?)
+                .platform(self.platform)
+                .call()?,
+        };
+        let base_digest = match base_digest {
+            Some(base_digest) => base_digest,
+            None => current_digest(
+                &format!("{}:{}", recipe.base_image, recipe.image_version),
+                lockfile,
+                self.platform,
+            )?,
+        };
+        let build_scripts_image = match build_scripts_image {
+            Some(build_scripts_image) => build_scripts_image,
+            None => determine_scripts_tag(self.platform)?,
+        };
+
+        Ok((os_version, base_digest, build_scripts_image))
+    }
+}
+
+/// Gets the digest of an image, using the
+/// digest it's pinned to if there is one.
+///
+/// The digest is cached so that the provenance of a build
+/// records the same digest that was templated, even if the
+/// tag moves while the image is being built.
+///
+/// # Errors
+/// Will error if the image reference can't be
+/// parsed or the image can't be inspected.
+#[cached(
+    result = true,
+    key = "String",
+    convert = r#"{ format!("{image}-{platform}") }"#,
+    sync_writes = true
+)]
+pub(crate) fn current_digest(
+    image: &str,
+    lockfile: &Lockfile,
+    platform: Platform,
+) -> Result<String> {
+    if let Some(locked) = lockfile.get(image) {
+        return Ok(locked.digest.clone());
+    }
+
+    let reference: Reference = image
+        .parse()
+        .into_diagnostic()
+        .with_context(|| format!("Unable to parse image reference {image}"))?;
+    if let Some(digest) = reference.digest() {
+        return Ok(digest.to_string());
+    }
+
+    Ok(Driver::get_metadata(
+        &GetMetadataOpts::builder()
+            .image(format!(
+                "{}/{}",
+                reference.resolve_registry(),
+                reference.repository()
+            ))
+            .tag(reference.tag().unwrap_or("latest"))
+            .platform(platform)
+            .build(),
+    )?
+    .digest)
+}
+
+/// Gets the `<image>@<digest>` references of the images
+/// that a built image is checked against for updates.
+///
+/// # Errors
+/// Will error if one of the images can't be inspected.
+pub(crate) fn resolve_image_digests(
+    recipe: &Recipe,
+    lockfile: &Lockfile,
+    os_version: u64,
+    platform: Platform,
+) -> Result<Vec<String>> {
+    recipe
+        .update_image_refs(os_version)
+        .into_iter()
+        .map(|image| {
+            let digest = current_digest(&image, lockfile, platform)?;
+            Ok(format!("{image}@{digest}"))
+        })
+        .collect()
+}
+
+/// Gets the `<image>@<digest>` references of the images checked
+/// for updates that are pinned, since the others can't be
+/// resolved offline.
+fn pinned_image_digests(recipe: &Recipe, lockfile: &Lockfile, os_version: u64) -> Vec<String> {
+    recipe
+        .update_image_refs(os_version)
+        .into_iter()
+        .filter_map(|image| {
+            let locked = lockfile.get(&image)?;
+            Some(format!("{image}@{}", locked.digest))
+        })
+        .collect()
+}
+
+/// Loads the template overrides, warning about any
+/// templates that won't replace part of the `Containerfile`.
+fn template_overrides(template_dir: &Path) -> Result<TemplateOverrides> {
+    if !template_dir.is_dir() {
+        bail!(
+            "The template directory {} does not exist",
+            template_dir.display()
+        );
+    }
+
+    let overrides = TemplateOverrides::new(template_dir);
+    let unknown = overrides
+        .unknown_templates()
+        .into_diagnostic()
+        .with_context(|| format!("Failed to read {}", template_dir.display()))?;
+
+    for template in unknown {
+        warn!(
+            "The template {} in {} doesn't replace a part of the Containerfile and won't be used. \
+            Templates must be named Containerfile.j2, stages.j2, or modules/<module-type>.j2",
+            template.display(),
+            template_dir.display(),
+        );
+    }
+    Ok(overrides)
+}
+
+/// Checks that a digest is a `sha256` digest.
+fn parse_digest(digest: &str) -> std::result::Result<String, String> {
+    digest
+        .strip_prefix("sha256:")
+        .filter(|hash| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(|_| digest.to_string())
+        .ok_or_else(|| format!("Expected a digest like sha256:<64 hex characters>, got {digest}"))
+}
+
+/// Gets the build scripts image used when templating,
+/// preferring the one in the lockfile and pinning
+/// it to its locked digest.
+///
+/// # Errors
+/// Will error if the build scripts image
+/// for this version can't be found.
+pub(crate) fn current_scripts_image(lockfile: &Lockfile, platform: Platform) -> Result<String> {
+    let build_scripts_image = match lockfile.build_scripts_image.clone() {
+        Some(build_scripts_image) => build_scripts_image,
+        None => determine_scripts_tag(platform)?,
+    };
+    Ok(lockfile.image_ref(&build_scripts_image))
 }
 
 #[cached(
@@ -178,7 +553,7 @@ impl GenerateCommand {
     convert = r#"{ platform }"#,
     sync_writes = true
 )]
-fn determine_scripts_tag(platform: Platform) -> Result<String> {
+pub(crate) fn determine_scripts_tag(platform: Platform) -> Result<String> {
     let version = format!("v{}", crate_version!());
     let opts = GetMetadataOpts::builder()
         .image(BUILD_SCRIPTS_IMAGE_REF)
@@ -199,3 +574,18 @@ fn determine_scripts_tag(platform: Platform) -> Result<String> {
         })
         .inspect(|image| debug!("Using build scripts image: {image}"))
 }
+
+#[cfg(test)]
+mod test {
+    use super::parse_digest;
+
+    #[test]
+    fn digest_parsing() {
+        let digest = format!("sha256:{}", "a1".repeat(32));
+
+        assert_eq!(parse_digest(&digest), Ok(digest.clone()));
+        assert!(parse_digest("sha256:abc").is_err());
+        assert!(parse_digest(&digest.replace("sha256", "sha512")).is_err());
+        assert!(parse_digest(&digest.replace('a', "g")).is_err());
+    }
+}
@@ -0,0 +1,401 @@
+use std::{
+    env, fs,
+    path::{self, Path, PathBuf},
+};
+
+use blue_build_process_management::{
+    drivers::{opts::RunOpts, types::RunDriverType, Driver, DriverArgs, RunDriver},
+    run_volumes,
+};
+use blue_build_recipe::{Recipe, VarOverride};
+use blue_build_utils::{
+    constants::{ARCHIVE_SUFFIX, SKOPEO_IMAGE},
+    string_vec,
+    traits::CowCollecter,
+};
+use bon::Builder;
+use clap::{Args, Subcommand, ValueEnum};
+use log::{debug, info, trace};
+use miette::{bail, Context, IntoDiagnostic, Result};
+use oci_distribution::Reference;
+use tempfile::TempDir;
+
+use super::{build::BuildCommand, BlueBuildCommand};
+
+const BOOTC_IMAGE_BUILDER_IMAGE: &str = "quay.io/centos-bootc/bootc-image-builder:latest";
+const CONTAINER_STORAGE: &str = "/var/lib/containers/storage";
+
+#[derive(Clone, Debug, Builder, Args)]
+pub struct GenerateDiskCommand {
+    #[command(subcommand)]
+    command: GenDiskSubcommand,
+
+    /// The directory to save the resulting disk images.
+    #[arg(short, long)]
+    #[builder(into)]
+    output_dir: Option<PathBuf>,
+
+    /// The formats of the disk images to create.
+    ///
+    /// Multiple formats can be given as a comma separated list.
+    #[arg(short, long = "format", default_value = "qcow2", value_delimiter = ',')]
+    #[builder(default = vec![DiskFormat::Qcow2], into)]
+    formats: Vec<DiskFormat>,
+
+    /// The filesystem type of the root partition.
+    ///
+    /// Defaults to the filesystem set by the image.
+    #[arg(long)]
+    rootfs: Option<DiskFilesystem>,
+
+    /// The minimum size of the root filesystem
+    /// (e.g. `20 GiB`).
+    #[arg(long)]
+    #[builder(into)]
+    root_size: Option<String>,
+
+    /// A bootc-image-builder config file in TOML format
+    /// used to create users and add their SSH keys.
+    ///
+    /// See <https://github.com/osbuild/bootc-image-builder#-build-config>.
+    #[arg(short, long)]
+    #[builder(into)]
+    config: Option<PathBuf>,
+
+    /// The location to temporarily store files
+    /// while building. If unset, it will use `/tmp`.
+    #[arg(long)]
+    tempdir: Option<PathBuf>,
+
+    #[clap(flatten)]
+    #[builder(default)]
+    drivers: DriverArgs,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum GenDiskSubcommand {
+    /// Build a disk image from a remote image.
+    Image {
+        /// The image ref to create the disk image from.
+        #[arg()]
+        image: String,
+    },
+    /// Build a disk image from a recipe.
+    ///
+    /// This will build the image locally first
+    /// before creating the disk image. This is a long
+    /// process.
+    Recipe {
+        /// The path to the recipe file for your image.
+        #[arg()]
+        recipe: PathBuf,
+
+        /// Override a variable in the recipe's `vars:` section.
+        ///
+        /// Can be used multiple times. Values are parsed
+        /// as yaml (e.g. `--var packages=[micro,htop]`).
+        #[arg(long = "var", value_name = "KEY=VALUE")]
+        vars: Vec<VarOverride>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum DiskFormat {
+    Qcow2,
+    Raw,
+    Ami,
+}
+
+impl std::fmt::Display for DiskFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match *self {
+            Self::Qcow2 => "qcow2",
+            Self::Raw => "raw",
+            Self::Ami => "ami",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum DiskFilesystem {
+    Ext4,
+    Xfs,
+    Btrfs,
+}
+
+impl std::fmt::Display for DiskFilesystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match *self {
+            Self::Ext4 => "ext4",
+            Self::Xfs => "xfs",
+            Self::Btrfs => "btrfs",
+        })
+    }
+}
+
+impl BlueBuildCommand for GenerateDiskCommand {
+    fn try_run(&mut self) -> Result<()> {
+        Driver::init(self.drivers);
+
+        // bootc-image-builder reads the image from
+        // the host's podman container storage.
+        if !matches!(Driver::get_run_driver(), RunDriverType::Podman) {
+            bail!("Generating a disk image requires the podman run driver");
+        }
+
+        if !nix::unistd::Uid::effective().is_root() {
+            bail!("You must be root to build a disk image!");
+        }
+
+        let image_out_dir = if let Some(ref dir) = self.tempdir {
+            TempDir::new_in(dir).into_diagnostic()?
+        } else {
+            TempDir::new().into_diagnostic()?
+        };
+
+        let output_dir = if let Some(output_dir) = self.output_dir.clone() {
+            if output_dir.exists() && !output_dir.is_dir() {
+                bail!("The '--output-dir' arg must be a directory");
+            }
+
+            if !output_dir.exists() {
+                fs::create_dir(&output_dir).into_diagnostic()?;
+            }
+
+            path::absolute(output_dir).into_diagnostic()?
+        } else {
+            env::current_dir().into_diagnostic()?
+        };
+
+        if let GenDiskSubcommand::Recipe { recipe, vars } = &self.command {
+            #[cfg(feature = "multi-recipe")]
+            let mut build_command = {
+                BuildCommand::builder()
+                    .recipe(vec![recipe.clone()])
+                    .archive(image_out_dir.path())
+                    .maybe_tempdir(self.tempdir.clone())
+                    .vars(vars.clone())
+                    .build()
+            };
+            #[cfg(not(feature = "multi-recipe"))]
+            let mut build_command = {
+                BuildCommand::builder()
+                    .recipe(recipe.clone())
+                    .archive(image_out_dir.path())
+                    .maybe_tempdir(self.tempdir.clone())
+                    .vars(vars.clone())
+                    .build()
+            };
+
+            build_command.try_run()?;
+        }
+
+        let image = self.copy_to_storage(image_out_dir.path())?;
+        self.build_disk(&image, &output_dir, image_out_dir.path())?;
+
+        info!("Finished creating disk images in {}", output_dir.display());
+        Ok(())
+    }
+}
+
+impl GenerateDiskCommand {
+    /// Copies the image into the host's container storage
+    /// and returns the reference to use for it.
+    fn copy_to_storage(&self, image_out_dir: &Path) -> Result<String> {
+        let (source, image, vols) = match &self.command {
+            GenDiskSubcommand::Image { image } => {
+                let image: Reference = image
+                    .parse()
+                    .into_diagnostic()
+                    .with_context(|| format!("Unable to parse image reference {image}"))?;
+
+                (
+                    format!("docker://{image}"),
+                    image.whole(),
+                    run_volumes![CONTAINER_STORAGE => CONTAINER_STORAGE],
+                )
+            }
+            GenDiskSubcommand::Recipe { recipe, vars } => {
+                let recipe = Recipe::parse_with_vars(recipe, vars)?;
+                let name = recipe.name.to_lowercase().replace('/', "_");
+
+                (
+                    format!("oci-archive:/img_src/{name}.{ARCHIVE_SUFFIX}"),
+                    format!("localhost/{name}:latest"),
+                    run_volumes![
+                        CONTAINER_STORAGE => CONTAINER_STORAGE,
+                        image_out_dir.display().to_string() => "/img_src/",
+                    ],
+                )
+            }
+        };
+        debug!("Copying {source} into container storage as {image}");
+
+        let args = string_vec!["copy", source, format!("containers-storage:{image}")];
+        let status = Driver::run(
+            &RunOpts::builder()
+                .image(SKOPEO_IMAGE)
+                .privileged(true)
+                .remove(true)
+                .args(args.collect_cow_vec())
+                .volumes(vols)
+                .build(),
+        )?;
+
+        if !status.success() {
+            bail!("Failed to copy {image} into container storage");
+        }
+
+        Ok(image)
+    }
+
+    fn build_disk(&self, image: &str, output_dir: &Path, image_out_dir: &Path) -> Result<()> {
+        let mut args = string_vec![];
+        for format in &self.formats {
+            args.extend(string_vec!["--type", format.to_string()]);
+        }
+        if let Some(rootfs) = self.rootfs {
+            args.extend(string_vec!["--rootfs", rootfs.to_string()]);
+        }
+        args.push(image.to_string());
+
+        let mut vols = run_volumes![
+            output_dir.display().to_string() => "/output",
+            CONTAINER_STORAGE => CONTAINER_STORAGE,
+        ];
+
+        let user_config = self
+            .config
+            .as_ref()
+            .map(|config| {
+                fs::read_to_string(config)
+                    .into_diagnostic()
+                    .with_context(|| format!("Failed to read config {}", config.display()))
+            })
+            .transpose()?;
+
+        if let Some(config) = builder_config(user_config.as_deref(), self.root_size.as_deref())? {
+            let config_path = image_out_dir.join("config.toml");
+            trace!("config.toml:\n{config}");
+
+            fs::write(&config_path, config).into_diagnostic()?;
+            vols.extend(run_volumes![
+                config_path.display().to_string() => "/config.toml",
+            ]);
+        }
+
+        let status = Driver::run(
+            &RunOpts::builder()
+                .image(BOOTC_IMAGE_BUILDER_IMAGE)
+                .privileged(true)
+                .remove(true)
+                .pull(true)
+                .args(args.collect_cow_vec())
+                .volumes(vols)
+                .build(),
+        )?;
+
+        if !status.success() {
+            bail!("Failed to create disk image");
+        }
+        Ok(())
+    }
+}
+
+/// Creates the bootc-image-builder config from the user's
+/// config, adding the minimum size of the root filesystem.
+fn builder_config(user_config: Option<&str>, root_size: Option<&str>) -> Result<Option<String>> {
+    let Some(mut config) = user_config
+        .map(|config| {
+            toml::from_str::<toml::Value>(config)
+                .into_diagnostic()
+                .context("Failed to parse config")
+        })
+        .transpose()?
+        .or_else(|| root_size.map(|_| toml::Value::Table(toml::map::Map::new())))
+    else {
+        return Ok(None);
+    };
+
+    if let Some(root_size) = root_size {
+        let Some(filesystems) = config
+            .as_table_mut()
+            .map(|config| {
+                config
+                    .entry("customizations")
+                    .or_insert_with(|| toml::Value::Table(toml::map::Map::new()))
+            })
+            .and_then(toml::Value::as_table_mut)
+            .map(|customizations| {
+                customizations
+                    .entry("filesystem")
+                    .or_insert_with(|| toml::Value::Array(vec![]))
+            })
+            .and_then(toml::Value::as_array_mut)
+        else {
+            bail!("The config has an invalid `customizations.filesystem` section");
+        };
+
+        filesystems.retain(|filesystem| {
+            filesystem.get("mountpoint").and_then(toml::Value::as_str) != Some("/")
+        });
+        filesystems.push(toml::Value::Table(toml::map::Map::from_iter([
+            (String::from("mountpoint"), toml::Value::from("/")),
+            (String::from("minsize"), toml::Value::from(root_size)),
+        ])));
+    }
+
+    toml::to_string(&config).into_diagnostic().map(Some)
+}
+
+#[cfg(test)]
+mod test {
+    use super::builder_config;
+
+    const USER_CONFIG: &str = r#"
+[[customizations.user]]
+name = "octocat"
+key = "ssh-ed25519 AAAA octocat@example.com"
+groups = ["wheel"]
+
+[[customizations.filesystem]]
+mountpoint = "/"
+minsize = "10 GiB"
+"#;
+
+    #[test]
+    fn no_config() {
+        assert_eq!(builder_config(None, None).unwrap(), None);
+    }
+
+    #[test]
+    fn root_size_only() {
+        let config = builder_config(None, Some("20 GiB")).unwrap().unwrap();
+        let config: toml::Value = toml::from_str(&config).unwrap();
+
+        assert_eq!(
+            config["customizations"]["filesystem"][0]["mountpoint"].as_str(),
+            Some("/")
+        );
+        assert_eq!(
+            config["customizations"]["filesystem"][0]["minsize"].as_str(),
+            Some("20 GiB")
+        );
+    }
+
+    #[test]
+    fn user_config_root_size() {
+        let config = builder_config(Some(USER_CONFIG), Some("20 GiB"))
+            .unwrap()
+            .unwrap();
+        let config: toml::Value = toml::from_str(&config).unwrap();
+        let filesystems = config["customizations"]["filesystem"].as_array().unwrap();
+
+        assert_eq!(
+            config["customizations"]["user"][0]["name"].as_str(),
+            Some("octocat")
+        );
+        assert_eq!(filesystems.len(), 1);
+        assert_eq!(filesystems[0]["minsize"].as_str(), Some("20 GiB"));
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     path::{self, Path, PathBuf},
 };
 
-use blue_build_recipe::Recipe;
+use blue_build_recipe::{Recipe, VarOverride};
 use blue_build_utils::{constants::ARCHIVE_SUFFIX, string_vec, traits::CowCollecter};
 use bon::Builder;
 use clap::{Args, Subcommand, ValueEnum};
@@ -13,6 +13,7 @@ use tempfile::TempDir;
 
 use blue_build_process_management::{
     drivers::{opts::RunOpts, types::RunDriverType, Driver, DriverArgs, RunDriver},
+    dry_run::{is_dry_run, set_dry_run},
     run_volumes,
 };
 
@@ -76,6 +77,11 @@ pub struct GenerateIsoCommand {
     #[arg(long)]
     tempdir: Option<PathBuf>,
 
+    /// Print the commands that would be run instead of running them.
+    #[arg(long)]
+    #[builder(default)]
+    dry_run: bool,
+
     #[clap(flatten)]
     #[builder(default)]
     drivers: DriverArgs,
@@ -98,6 +104,13 @@ pub enum GenIsoSubcommand {
         /// The path to the recipe file for your image.
         #[arg()]
         recipe: PathBuf,
+
+        /// Override a variable in the recipe's `vars:` section.
+        ///
+        /// Can be used multiple times. Values are parsed
+        /// as yaml (e.g. `--var packages=[micro,htop]`).
+        #[arg(long = "var", value_name = "KEY=VALUE")]
+        vars: Vec<VarOverride>,
     },
 }
 
@@ -127,7 +140,12 @@ impl BlueBuildCommand for GenerateIsoCommand {
     fn try_run(&mut self) -> Result<()> {
         Driver::init(self.drivers);
 
-        if !nix::unistd::Uid::effective().is_root()
+        if self.dry_run {
+            set_dry_run(true);
+        }
+
+        if !is_dry_run()
+            && !nix::unistd::Uid::effective().is_root()
             && matches!(Driver::get_run_driver(), RunDriverType::Podman)
         {
             bail!("You must be root to build an ISO!");
@@ -153,13 +171,14 @@ impl BlueBuildCommand for GenerateIsoCommand {
             env::current_dir().into_diagnostic()?
         };
 
-        if let GenIsoSubcommand::Recipe { recipe } = &self.command {
+        if let GenIsoSubcommand::Recipe { recipe, vars } = &self.command {
             #[cfg(feature = "multi-recipe")]
             let mut build_command = {
                 BuildCommand::builder()
                     .recipe(vec![recipe.clone()])
                     .archive(image_out_dir.path())
                     .maybe_tempdir(self.tempdir.clone())
+                    .vars(vars.clone())
                     .build()
             };
             #[cfg(not(feature = "multi-recipe"))]
@@ -168,6 +187,7 @@ impl BlueBuildCommand for GenerateIsoCommand {
                     .recipe(recipe.clone())
                     .archive(image_out_dir.path())
                     .maybe_tempdir(self.tempdir.clone())
+                    .vars(vars.clone())
                     .build()
             };
 
@@ -177,7 +197,7 @@ impl BlueBuildCommand for GenerateIsoCommand {
         let iso_name = self.iso_name.as_ref().map_or("deploy.iso", String::as_str);
         let iso_path = output_dir.join(iso_name);
 
-        if iso_path.exists() {
+        if iso_path.exists() && !is_dry_run() {
             fs::remove_file(iso_path).into_diagnostic()?;
         }
 
@@ -226,8 +246,8 @@ impl GenerateIsoCommand {
                     ),
                 ]);
             }
-            GenIsoSubcommand::Recipe { recipe } => {
-                let recipe = Recipe::parse(recipe)?;
+            GenIsoSubcommand::Recipe { recipe, vars } => {
+                let recipe = Recipe::parse_with_vars(recipe, vars)?;
 
                 args.extend([
                     format!(
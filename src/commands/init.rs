@@ -33,7 +33,7 @@ pub enum CiProvider {
 }
 
 impl CiProvider {
-    fn default_ci_file_path(self) -> std::path::PathBuf {
+    fn default_ci_file_path(self) -> Result<std::path::PathBuf> {
         match self {
             Self::Gitlab => GitlabDriver::default_ci_file_path(),
             Self::None | Self::Github => unimplemented!(),
@@ -431,7 +431,7 @@ impl InitCommand {
             .dir
             .as_ref()
             .unwrap()
-            .join(ci_provider.default_ci_file_path());
+            .join(ci_provider.default_ci_file_path()?);
         let parent_path = ci_file_path
             .parent()
             .ok_or_else(|| miette!("Couldn't get parent directory from {ci_file_path:?}"))?;
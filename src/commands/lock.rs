@@ -0,0 +1,168 @@
+use std::path::{Path, PathBuf};
+
+use blue_build_process_management::drivers::{types::Platform, Driver, DriverArgs};
+use blue_build_recipe::{LockedImage, Lockfile, Recipe, VarOverride};
+use blue_build_utils::constants::{CONFIG_PATH, LOCKFILE_PATH, RECIPE_FILE, RECIPE_PATH};
+use clap::Args;
+use colored::Colorize;
+use log::{debug, info, trace, warn};
+use miette::Result;
+
+use super::{
+    generate::{current_digest, determine_scripts_tag},
+    BlueBuildCommand,
+};
+
+#[derive(Debug, Clone, Args)]
+pub struct LockCommand {
+    /// The recipe files to pin the images of.
+    ///
+    /// Pins for images that aren't used by any of
+    /// these recipes are removed from the lockfile, so
+    /// every recipe in the repo should be included.
+    #[arg()]
+    recipes: Vec<PathBuf>,
+
+    /// Resolve the digest of every image again
+    /// instead of keeping the ones already pinned.
+    #[arg(short, long)]
+    update: bool,
+
+    /// Inspect the base images for a specific platform
+    /// when retrieving their versions.
+    #[arg(long, default_value = "native")]
+    platform: Platform,
+
+    /// Override a variable in the recipe's `vars:` section.
+    ///
+    /// Can be used multiple times. Values are parsed
+    /// as yaml (e.g. `--var packages=[micro,htop]`).
+    #[arg(long = "var", value_name = "KEY=VALUE")]
+    vars: Vec<VarOverride>,
+
+    #[clap(flatten)]
+    drivers: DriverArgs,
+}
+
+impl BlueBuildCommand for LockCommand {
+    fn try_run(&mut self) -> Result<()> {
+        trace!("LockCommand::try_run()");
+
+        Driver::init(self.drivers);
+
+        let current = Lockfile::load(LOCKFILE_PATH)?;
+        let mut lockfile = Lockfile::default();
+
+        for recipe_path in self.recipe_paths() {
+            info!("Pinning images for recipe at {}", recipe_path.display());
+            let recipe = Recipe::parse_with_vars(&recipe_path, &self.vars)?;
+
+            let base_image = format!("{}:{}", recipe.base_image, recipe.image_version);
+            let mut base = self.lock_image(&current, &base_image)?;
+            let os_version = match base.as_ref().and_then(|locked| locked.os_version) {
+                Some(os_version) if !self.update => os_version,
+                _ => Driver::get_os_version()
+                    .oci_ref(&recipe.base_image_ref()?)
+                    .platform(self.platform)
+                    .call()?,
+            };
+            if let Some(base) = base.as_mut() {
+                base.os_version = Some(os_version);
+            }
+
+            for image in std::iter::once(base_image.clone()).chain(recipe.image_refs(os_version)) {
+                if lockfile.images.contains_key(&image) {
+                    continue;
+                }
+
+                let locked = if image == base_image {
+                    base.clone()
+                } else {
+                    self.lock_image(&current, &image)?
+                };
+                if let Some(locked) = locked {
+                    lockfile.images.insert(image, locked);
+                }
+            }
+        }
+
+        let build_scripts_image = match current.build_scripts_image.clone() {
+            Some(build_scripts_image) if !self.update => build_scripts_image,
+            _ => determine_scripts_tag(self.platform)?,
+        };
+        if let Some(locked) = self.lock_image(&current, &build_scripts_image)? {
+            lockfile.images.insert(build_scripts_image.clone(), locked);
+        }
+        lockfile.build_scripts_image = Some(build_scripts_image);
+
+        print_changes(&current, &lockfile);
+        lockfile.write(LOCKFILE_PATH)?;
+        info!(
+            "Pinned {} images in {}",
+            lockfile.images.len(),
+            LOCKFILE_PATH.bold()
+        );
+
+        Ok(())
+    }
+}
+
+impl LockCommand {
+    fn recipe_paths(&self) -> Vec<PathBuf> {
+        if !self.recipes.is_empty() {
+            return self.recipes.clone();
+        }
+
+        let legacy_path = Path::new(CONFIG_PATH);
+        let recipe_path = Path::new(RECIPE_PATH);
+        if recipe_path.exists() && recipe_path.is_dir() {
+            vec![recipe_path.join(RECIPE_FILE)]
+        } else {
+            warn!("Use of {CONFIG_PATH} for recipes is deprecated, please move your recipe files into {RECIPE_PATH}");
+            vec![legacy_path.join(RECIPE_FILE)]
+        }
+    }
+
+    /// Gets the digest to pin an image to, keeping the
+    /// current pin unless updating.
+    ///
+    /// Images that are already referenced
+    /// by digest aren't pinned.
+    fn lock_image(&self, current: &Lockfile, image: &str) -> Result<Option<LockedImage>> {
+        trace!("LockCommand::lock_image({image})");
+
+        if let Some(locked) = current.get(image).filter(|_| !self.update) {
+            return Ok(Some(locked.clone()));
+        }
+
+        if image.contains('@') {
+            debug!("{image} is already pinned to a digest");
+            return Ok(None);
+        }
+
+        let digest = current_digest(image, &Lockfile::default(), self.platform)?;
+
+        Ok(Some(LockedImage {
+            digest,
+            os_version: None,
+        }))
+    }
+}
+
+fn print_changes(current: &Lockfile, lockfile: &Lockfile) {
+    for (image, locked) in &lockfile.images {
+        match current.get(image) {
+            Some(current) if current.digest == locked.digest => {}
+            Some(_) => info!("Updated {} to {}", image.bold(), locked.digest),
+            None => info!("Pinned {} to {}", image.bold(), locked.digest),
+        }
+    }
+
+    for image in current
+        .images
+        .keys()
+        .filter(|image| !lockfile.images.contains_key(*image))
+    {
+        info!("Removed {}", image.bold());
+    }
+}
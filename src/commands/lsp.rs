@@ -0,0 +1,509 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use blue_build_process_management::ASYNC_RUNTIME;
+use blue_build_recipe::Vars;
+use blue_build_utils::constants::BB_SCHEMA_DIR;
+use bon::Builder;
+use clap::Args;
+use log::{debug, info, trace, warn};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as LspNotification, PublishDiagnostics,
+    },
+    request::{Completion, GotoDefinition, HoverRequest, Request as LspRequest},
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, Documentation, GotoDefinitionParams, GotoDefinitionResponse, Hover,
+    HoverContents, HoverParams, HoverProviderCapability, InitializeParams, Location as LspLocation,
+    MarkupContent, MarkupKind, OneOf, Position, PublishDiagnosticsParams, Range,
+    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, Uri,
+};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::commands::validate::{
+    location::Location,
+    schema_validator::{
+        remove_json, remove_local_properties, remove_local_recipe_properties, SchemaValidator,
+        MODULE_STAGE_LIST_V1_SCHEMA_URL, MODULE_V1_SCHEMA_URL, RECIPE_V1_SCHEMA_URL,
+    },
+    yaml_span::YamlSpan,
+};
+
+use super::BlueBuildCommand;
+
+use document::{cursor_context, position_at, resolve_from_file, CursorContext};
+use modules::ModuleCatalog;
+
+mod document;
+mod modules;
+
+const SOURCE: &str = "bluebuild";
+const VAR_START: &str = "${{";
+
+/// Properties that every module can have.
+const COMMON_MODULE_PROPERTIES: &[(&str, &str)] = &[
+    (
+        "source",
+        "The image to pull the module from, or `local` for modules in the `modules` directory.",
+    ),
+    (
+        "no-cache",
+        "Forces the module to ignore the container layer cache.",
+    ),
+    (
+        "env",
+        "Environment variables to set when running the module.",
+    ),
+];
+
+#[derive(Debug, Clone, Args, Builder)]
+pub struct LspCommand {
+    /// A directory containing the json schemas to validate against.
+    ///
+    /// Schemas are otherwise taken from the cache in
+    /// `~/.cache/bluebuild/schemas` or retrieved from
+    /// <https://schema.blue-build.org>, falling back to
+    /// the ones embedded in the binary when offline.
+    #[arg(long, env = BB_SCHEMA_DIR)]
+    #[builder(into)]
+    schema_dir: Option<PathBuf>,
+}
+
+impl BlueBuildCommand for LspCommand {
+    fn try_run(&mut self) -> Result<()> {
+        let (connection, io_threads) = Connection::stdio();
+
+        let params = connection
+            .initialize(serde_json::to_value(capabilities()).into_diagnostic()?)
+            .into_diagnostic()
+            .context("Failed to initialize the language server")?;
+        let params: InitializeParams = serde_json::from_value(params).into_diagnostic()?;
+        trace!("{params:#?}");
+
+        #[allow(deprecated)]
+        let root = params
+            .workspace_folders
+            .as_ref()
+            .and_then(|folders| folders.first())
+            .map(|folder| &folder.uri)
+            .or(params.root_uri.as_ref())
+            .and_then(uri_to_path)
+            .or_else(|| std::env::current_dir().ok());
+
+        info!("Starting language server");
+        Server::new(connection, root, self.schema_dir.as_deref())?.run()?;
+
+        io_threads.join().into_diagnostic()?;
+        info!("Stopped language server");
+        Ok(())
+    }
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![":".into(), " ".into()]),
+            ..Default::default()
+        }),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+struct Server {
+    connection: Connection,
+    root: Option<PathBuf>,
+    documents: HashMap<Uri, String>,
+    recipe_validator: SchemaValidator,
+    module_stage_list_validator: SchemaValidator,
+    modules: ModuleCatalog,
+}
+
+impl Server {
+    fn new(
+        connection: Connection,
+        root: Option<PathBuf>,
+        schema_dir: Option<&Path>,
+    ) -> Result<Self> {
+        let (recipe_validator, module_stage_list_validator, module_validator) = ASYNC_RUNTIME
+            .block_on(async {
+                tokio::try_join!(
+                    SchemaValidator::builder()
+                        .url(RECIPE_V1_SCHEMA_URL)
+                        .maybe_schema_dir(schema_dir.map(Path::to_path_buf))
+                        .build(),
+                    SchemaValidator::builder()
+                        .url(MODULE_STAGE_LIST_V1_SCHEMA_URL)
+                        .maybe_schema_dir(schema_dir.map(Path::to_path_buf))
+                        .build(),
+                    SchemaValidator::builder()
+                        .url(MODULE_V1_SCHEMA_URL)
+                        .maybe_schema_dir(schema_dir.map(Path::to_path_buf))
+                        .build(),
+                )
+            })?;
+
+        let modules = ModuleCatalog::new(&module_validator.schema(), schema_dir, root.clone());
+
+        Ok(Self {
+            connection,
+            root,
+            documents: HashMap::new(),
+            recipe_validator,
+            module_stage_list_validator,
+            modules,
+        })
+    }
+
+    fn run(mut self) -> Result<()> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self
+                        .connection
+                        .handle_shutdown(&request)
+                        .into_diagnostic()?
+                    {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(request);
+                    self.send(Message::Response(response))?;
+                }
+                Message::Notification(notification) => self.handle_notification(notification)?,
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&self, message: Message) -> Result<()> {
+        self.connection
+            .sender
+            .send(message)
+            .map_err(|e| miette!("Failed to send message to client: {e}"))
+    }
+
+    fn handle_request(&mut self, request: Request) -> Response {
+        debug!("Received request {}", request.method);
+        let id = request.id.clone();
+
+        let result = match request.method.as_str() {
+            Completion::METHOD => handle(request, |params: CompletionParams| {
+                Ok(self.completion(&params))
+            }),
+            HoverRequest::METHOD => handle(request, |params: HoverParams| Ok(self.hover(&params))),
+            GotoDefinition::METHOD => handle(request, |params: GotoDefinitionParams| {
+                Ok(self.definition(&params))
+            }),
+            method => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("Unsupported request {method}"),
+                )
+            }
+        };
+
+        result.unwrap_or_else(|e| {
+            Response::new_err(id, ErrorCode::InternalError as i32, format!("{e:?}"))
+        })
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> Result<()> {
+        debug!("Received notification {}", notification.method);
+
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams = extract(notification.params)?;
+                self.update(params.text_document.uri, params.text_document.text)
+            }
+            DidChangeTextDocument::METHOD => {
+                let mut params: DidChangeTextDocumentParams = extract(notification.params)?;
+                match params.content_changes.pop() {
+                    Some(change) => self.update(params.text_document.uri, change.text),
+                    None => Ok(()),
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams = extract(notification.params)?;
+                self.documents.remove(&params.text_document.uri);
+                self.publish_diagnostics(params.text_document.uri, Vec::new())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn update(&mut self, uri: Uri, text: String) -> Result<()> {
+        let diagnostics = self.diagnostics(&text);
+        self.documents.insert(uri.clone(), text);
+        self.publish_diagnostics(uri, diagnostics)
+    }
+
+    fn publish_diagnostics(&self, uri: Uri, diagnostics: Vec<Diagnostic>) -> Result<()> {
+        self.send(Message::Notification(Notification::new(
+            PublishDiagnostics::METHOD.to_string(),
+            PublishDiagnosticsParams::new(uri, diagnostics, None),
+        )))
+    }
+
+    /// Validates a recipe or a file of modules and stages against its schema.
+    fn diagnostics(&self, text: &str) -> Vec<Diagnostic> {
+        let mut value = match serde_yaml::from_str::<serde_yaml::Value>(text) {
+            Ok(value) => value,
+            Err(e) => {
+                let position = e.location().map_or_else(Position::default, |location| {
+                    position_at(text, location.index())
+                });
+                return vec![diagnostic(
+                    Range::new(position, position),
+                    remove_json(&e.to_string()).into_owned(),
+                )];
+            }
+        };
+
+        let Some(map) = value.as_mapping_mut() else {
+            return Vec::new();
+        };
+
+        let is_recipe = ["name", "base-image", "extends"]
+            .into_iter()
+            .any(|key| map.contains_key(key));
+        let extends = map.remove("extends").is_some();
+
+        let validator = if is_recipe {
+            &self.recipe_validator
+        } else if map.contains_key("modules") || map.contains_key("stages") {
+            &self.module_stage_list_validator
+        } else {
+            return Vec::new();
+        };
+
+        let vars = match Vars::resolve(map.remove("vars").as_ref(), &[]) {
+            Ok(vars) => vars,
+            Err(e) => return vec![diagnostic(Range::default(), format!("{e}"))],
+        };
+        interpolate_known(&vars, &mut value);
+
+        let mut instance = match serde_json::to_value(&value) {
+            Ok(instance) => instance,
+            Err(e) => return vec![diagnostic(Range::default(), e.to_string())],
+        };
+        if is_recipe {
+            remove_local_recipe_properties(&mut instance);
+        } else {
+            remove_local_properties(&mut instance);
+        }
+        let spanner = match YamlSpan::builder().file(Arc::new(text.to_string())).build() {
+            Ok(spanner) => spanner,
+            Err(e) => return vec![diagnostic(Range::default(), format!("{e}"))],
+        };
+
+        validator
+            .iter_errors(&instance)
+            // Recipes that extend another recipe inherit
+            // the required properties from it.
+            .filter(|err| {
+                !(extends
+                    && err.instance_path.as_str().is_empty()
+                    && matches!(
+                        err.kind,
+                        jsonschema::error::ValidationErrorKind::Required { .. }
+                    ))
+            })
+            // Files of modules use the variables of the recipe that
+            // includes them, so their values can't be checked here.
+            .filter(|err| {
+                !err.instance
+                    .as_str()
+                    .is_some_and(|value| value.contains(VAR_START))
+            })
+            .map(|err| {
+                let range = spanner
+                    .get_span(&Location::from(&err.instance_path))
+                    .map_or_else(
+                        |_| Range::default(),
+                        |span| {
+                            Range::new(
+                                position_at(text, span.offset()),
+                                position_at(text, span.offset() + span.len()),
+                            )
+                        },
+                    );
+                diagnostic(range, remove_json(&err.to_string()).into_owned())
+            })
+            .collect()
+    }
+
+    fn completion(&self, params: &CompletionParams) -> Option<CompletionResponse> {
+        let position = &params.text_document_position;
+        let text = self.documents.get(&position.text_document.uri)?;
+
+        let items = match cursor_context(text, position.position) {
+            CursorContext::ModuleType(_) => self
+                .modules
+                .modules()
+                .map(|(name, info)| CompletionItem {
+                    label: name.clone(),
+                    kind: Some(CompletionItemKind::MODULE),
+                    documentation: info.description.clone().map(Documentation::String),
+                    ..Default::default()
+                })
+                .collect(),
+            CursorContext::ModuleProperty { module_type } => self
+                .modules
+                .get(module_type)
+                .into_iter()
+                .flat_map(|info| {
+                    info.properties
+                        .iter()
+                        .map(|(name, description)| (name.as_str(), description.as_deref()))
+                })
+                .chain(
+                    COMMON_MODULE_PROPERTIES
+                        .iter()
+                        .map(|&(name, description)| (name, Some(description))),
+                )
+                .map(|(name, description)| CompletionItem {
+                    label: name.to_string(),
+                    kind: Some(CompletionItemKind::PROPERTY),
+                    insert_text: Some(format!("{name}: ")),
+                    documentation: description
+                        .map(|description| Documentation::String(description.to_string())),
+                    ..Default::default()
+                })
+                .collect(),
+            CursorContext::FromFile(_) | CursorContext::None => return None,
+        };
+
+        Some(CompletionResponse::Array(items))
+    }
+
+    fn hover(&mut self, params: &HoverParams) -> Option<Hover> {
+        let position = &params.text_document_position_params;
+        let text = self.documents.get(&position.text_document.uri)?;
+
+        let CursorContext::ModuleType(module_type) = cursor_context(text, position.position) else {
+            return None;
+        };
+        let module_type = module_type.to_string();
+
+        let description = self
+            .modules
+            .get(&module_type)
+            .and_then(|info| info.description.clone());
+        let docs = self.modules.docs(&module_type).cloned().unwrap_or_default();
+
+        let mut value = format!("**{module_type}**");
+        if let Some(shortdesc) = docs.shortdesc.or(description) {
+            value = format!("{value}\n\n{}", shortdesc.trim());
+        }
+        if let Some(example) = docs.example {
+            value = format!("{value}\n\n```yaml\n{}\n```", example.trim_end());
+        }
+
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: None,
+        })
+    }
+
+    fn definition(&self, params: &GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = &params.text_document_position_params;
+        let text = self.documents.get(&position.text_document.uri)?;
+
+        let CursorContext::FromFile(from_file) = cursor_context(text, position.position) else {
+            return None;
+        };
+
+        let document = uri_to_path(&position.text_document.uri)?;
+        let path = resolve_from_file(&document, self.root.as_deref(), from_file)?;
+
+        Some(GotoDefinitionResponse::Scalar(LspLocation::new(
+            path_to_uri(&path)?,
+            Range::default(),
+        )))
+    }
+}
+
+fn handle<P, R, F>(request: Request, f: F) -> Result<Response>
+where
+    P: DeserializeOwned,
+    R: Serialize,
+    F: FnOnce(P) -> Result<R>,
+{
+    let params = extract(request.params)?;
+    Ok(Response::new_ok(request.id, f(params)?))
+}
+
+fn extract<P: DeserializeOwned>(params: serde_json::Value) -> Result<P> {
+    serde_json::from_value(params).into_diagnostic()
+}
+
+/// Interpolates the variables that are defined, leaving any others as is.
+fn interpolate_known(vars: &Vars, value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::Sequence(sequence) => {
+            for value in sequence {
+                interpolate_known(vars, value);
+            }
+        }
+        serde_yaml::Value::Mapping(map) => {
+            for (_, value) in map.iter_mut() {
+                interpolate_known(vars, value);
+            }
+        }
+        serde_yaml::Value::Tagged(tagged) => interpolate_known(vars, &mut tagged.value),
+        value => {
+            if let Err(e) = vars.interpolate(value) {
+                trace!("Leaving value uninterpolated: {e}");
+            }
+        }
+    }
+}
+
+fn diagnostic(range: Range, message: String) -> Diagnostic {
+    Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some(SOURCE.to_string()),
+        message,
+        ..Default::default()
+    }
+}
+
+fn uri_to_path(uri: &Uri) -> Option<PathBuf> {
+    if !uri
+        .scheme()
+        .is_some_and(|scheme| scheme.as_str().eq_ignore_ascii_case("file"))
+    {
+        warn!("Unsupported uri {}", uri.as_str());
+        return None;
+    }
+    urlencoding::decode(uri.path().as_str())
+        .ok()
+        .map(|path| PathBuf::from(path.into_owned()))
+}
+
+fn path_to_uri(path: &Path) -> Option<Uri> {
+    let path = path
+        .canonicalize()
+        .ok()?
+        .iter()
+        .skip(1)
+        .map(|segment| urlencoding::encode(&segment.to_string_lossy()).into_owned())
+        .collect::<Vec<_>>()
+        .join("/");
+    format!("file:///{path}").parse().ok()
+}
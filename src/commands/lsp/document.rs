@@ -0,0 +1,244 @@
+use std::path::{Path, PathBuf};
+
+use lsp_types::Position;
+
+const TYPE_KEY: &str = "type:";
+const FROM_FILE_KEY: &str = "from-file:";
+
+/// What the cursor is on in a recipe or module file.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CursorContext<'a> {
+    /// The value of a module's `type:` property.
+    ModuleType(&'a str),
+
+    /// A property key within a module of the given type.
+    ModuleProperty {
+        module_type: &'a str,
+    },
+
+    /// The value of a `from-file:` property.
+    FromFile(&'a str),
+
+    None,
+}
+
+/// The layout of a single line of yaml.
+#[derive(Debug, Clone, Copy)]
+struct LineInfo<'a> {
+    /// The column that the key of the line starts at.
+    key_col: usize,
+
+    /// Whether the line starts a new sequence item.
+    is_item: bool,
+
+    /// The contents of the line starting at the key.
+    content: &'a str,
+}
+
+impl<'a> LineInfo<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let trimmed = line.trim_start_matches(' ');
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            return None;
+        }
+
+        let indent = line.len() - trimmed.len();
+        let (is_item, content) = trimmed
+            .strip_prefix('-')
+            .filter(|rest| rest.is_empty() || rest.starts_with(' '))
+            .map_or((false, trimmed), |rest| {
+                (true, rest.trim_start_matches(' '))
+            });
+
+        Some(Self {
+            key_col: indent + (trimmed.len() - content.len()),
+            is_item,
+            content,
+        })
+    }
+
+    fn value_of(&self, key: &str) -> Option<&'a str> {
+        self.content.strip_prefix(key).map(clean_value)
+    }
+}
+
+/// Determines what the cursor is on at `position`.
+#[must_use]
+pub fn cursor_context(text: &str, position: Position) -> CursorContext<'_> {
+    let lines = text.lines().collect::<Vec<_>>();
+    let Some(line) = lines.get(position.line as usize) else {
+        return CursorContext::None;
+    };
+    let prefix = &line[..byte_index(line, position.character)];
+
+    let Some(info) = LineInfo::parse(line) else {
+        // Nothing has been typed on the line yet
+        // so the key will start at the cursor.
+        return module_type_at(&lines, position.line as usize, prefix.len())
+            .map_or(CursorContext::None, |module_type| {
+                CursorContext::ModuleProperty { module_type }
+            });
+    };
+
+    if let Some(module_type) = info.value_of(TYPE_KEY) {
+        return CursorContext::ModuleType(module_type);
+    }
+
+    if let Some(from_file) = info.value_of(FROM_FILE_KEY) {
+        return CursorContext::FromFile(from_file);
+    }
+
+    if info.is_item || prefix.contains(':') {
+        return CursorContext::None;
+    }
+
+    module_type_at(&lines, position.line as usize, info.key_col)
+        .map_or(CursorContext::None, |module_type| {
+            CursorContext::ModuleProperty { module_type }
+        })
+}
+
+/// Finds the `type:` of the module whose properties
+/// start at `key_col` and include line `line`.
+fn module_type_at<'a>(lines: &[&'a str], line: usize, key_col: usize) -> Option<&'a str> {
+    let mut block = Vec::new();
+
+    for info in lines[..line]
+        .iter()
+        .rev()
+        .filter_map(|l| LineInfo::parse(l))
+    {
+        if info.key_col < key_col {
+            break;
+        }
+        if info.key_col == key_col {
+            block.push(info);
+            if info.is_item {
+                break;
+            }
+        }
+    }
+
+    for info in lines[line + 1..].iter().filter_map(|l| LineInfo::parse(l)) {
+        if info.key_col < key_col || (info.key_col == key_col && info.is_item) {
+            break;
+        }
+        if info.key_col == key_col {
+            block.push(info);
+        }
+    }
+
+    block.iter().find_map(|info| info.value_of(TYPE_KEY))
+}
+
+fn clean_value(value: &str) -> &str {
+    let value = value.split(" #").next().unwrap_or_default().trim();
+    value.trim_matches(|c| c == '"' || c == '\'')
+}
+
+/// Converts a UTF-16 column into a byte index into `line`.
+fn byte_index(line: &str, character: u32) -> usize {
+    let mut units = 0;
+    for (index, c) in line.char_indices() {
+        if units >= character as usize {
+            return index;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+/// Converts a character offset into the file into an LSP position.
+#[must_use]
+pub fn position_at(text: &str, offset: usize) -> Position {
+    let mut line = 0;
+    let mut character = 0;
+
+    for c in text.chars().take(offset) {
+        if c == '\n' {
+            line += 1;
+            character = 0;
+        } else {
+            character += c.len_utf16();
+        }
+    }
+
+    Position::new(
+        u32::try_from(line).unwrap_or(u32::MAX),
+        u32::try_from(character).unwrap_or(u32::MAX),
+    )
+}
+
+/// Resolves the path of a `from-file:` reference made in `document`.
+///
+/// The path is looked up next to the document first and then
+/// in the `recipes` and legacy `config` directories of `root`.
+#[must_use]
+pub fn resolve_from_file(document: &Path, root: Option<&Path>, from_file: &str) -> Option<PathBuf> {
+    document
+        .parent()
+        .map(|dir| dir.join(from_file))
+        .into_iter()
+        .chain(
+            root.into_iter()
+                .flat_map(|root| [root.join("recipes"), root.join("config")])
+                .map(|dir| dir.join(from_file)),
+        )
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod test {
+    use lsp_types::Position;
+    use rstest::rstest;
+
+    use super::{cursor_context, position_at, CursorContext};
+
+    const RECIPE: &str = "name: test
+modules:
+  - from-file: flatpaks.yml
+  - type: rpm-ostree
+    install:
+      - micro
+    re
+  - source: local
+    no
+    type: test-module
+  - type: 'script' # comment
+    \n";
+
+    #[rstest]
+    #[case(Position::new(0, 2), CursorContext::None)]
+    #[case(Position::new(2, 18), CursorContext::FromFile("flatpaks.yml"))]
+    #[case(Position::new(3, 12), CursorContext::ModuleType("rpm-ostree"))]
+    #[case(
+        Position::new(4, 6),
+        CursorContext::ModuleProperty { module_type: "rpm-ostree" }
+    )]
+    #[case(Position::new(4, 12), CursorContext::None)]
+    #[case(Position::new(5, 8), CursorContext::None)]
+    #[case(
+        Position::new(6, 4),
+        CursorContext::ModuleProperty { module_type: "rpm-ostree" }
+    )]
+    #[case(
+        Position::new(8, 4),
+        CursorContext::ModuleProperty { module_type: "test-module" }
+    )]
+    #[case(Position::new(10, 10), CursorContext::ModuleType("script"))]
+    #[case(
+        Position::new(11, 4),
+        CursorContext::ModuleProperty { module_type: "script" }
+    )]
+    fn context(#[case] position: Position, #[case] expected: CursorContext) {
+        assert_eq!(cursor_context(RECIPE, position), expected);
+    }
+
+    #[rstest]
+    #[case(0, Position::new(0, 0))]
+    #[case(11, Position::new(1, 0))]
+    #[case(14, Position::new(1, 3))]
+    fn position(#[case] offset: usize, #[case] expected: Position) {
+        assert_eq!(position_at(RECIPE, offset), expected);
+    }
+}
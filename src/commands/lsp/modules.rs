@@ -0,0 +1,363 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use blue_build_process_management::ASYNC_RUNTIME;
+use indexmap::IndexMap;
+use log::{debug, trace, warn};
+use miette::{Context, IntoDiagnostic, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::commands::validate::schema_validator::{is_fresh, retrieve_schema, BASE_SCHEMA_URL};
+
+const MODULE_DOCS_URL: &str = "https://raw.githubusercontent.com/blue-build/modules/main/modules";
+const MAX_REF_DEPTH: usize = 8;
+
+/// The documentation of a module from its `module.yml`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ModuleDocs {
+    pub shortdesc: Option<String>,
+    pub example: Option<String>,
+}
+
+/// A module and its properties as described by its schema.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleInfo {
+    pub description: Option<String>,
+    pub properties: IndexMap<String, Option<String>>,
+}
+
+/// All the modules known to the language server.
+#[derive(Debug, Default)]
+pub struct ModuleCatalog {
+    modules: BTreeMap<String, ModuleInfo>,
+    docs: HashMap<String, Option<ModuleDocs>>,
+    root: Option<PathBuf>,
+}
+
+impl ModuleCatalog {
+    /// Builds the catalog from the modules referenced by the
+    /// module schema and any local modules in `root`.
+    pub fn new(module_schema: &Value, schema_dir: Option<&Path>, root: Option<PathBuf>) -> Self {
+        let mut catalog = Self {
+            root,
+            ..Default::default()
+        };
+
+        let mut visited = HashSet::new();
+        catalog.collect(module_schema, schema_dir, &mut visited, 0);
+
+        for dir in catalog.local_module_dirs() {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.filter_map(Result::ok) {
+                if entry.path().is_dir() {
+                    catalog
+                        .modules
+                        .entry(entry.file_name().to_string_lossy().into_owned())
+                        .or_default();
+                }
+            }
+        }
+
+        debug!("Found {} modules", catalog.modules.len());
+        catalog
+    }
+
+    pub fn modules(&self) -> impl Iterator<Item = (&String, &ModuleInfo)> {
+        self.modules.iter()
+    }
+
+    pub fn get(&self, module_type: &str) -> Option<&ModuleInfo> {
+        self.modules.get(module_type)
+    }
+
+    /// Gets the docs for a module from its `module.yml`.
+    ///
+    /// Local modules are checked first, then the on-disk
+    /// cache, and finally the modules repository.
+    pub fn docs(&mut self, module_type: &str) -> Option<&ModuleDocs> {
+        if !self.docs.contains_key(module_type) {
+            let docs = self
+                .read_docs(module_type)
+                .inspect_err(|e| warn!("Unable to get docs for module {module_type}: {e:?}"))
+                .ok();
+            self.docs.insert(module_type.to_string(), docs);
+        }
+        self.docs.get(module_type).and_then(Option::as_ref)
+    }
+
+    fn read_docs(&self, module_type: &str) -> Result<ModuleDocs> {
+        let local_file = self
+            .local_module_dirs()
+            .into_iter()
+            .map(|dir| dir.join(module_type).join("module.yml"))
+            .find(|file| file.is_file());
+        let cache_file = blue_build_utils::cache_dir()
+            .map(|dir| dir.join("modules").join(format!("{module_type}.yml")));
+
+        let contents = match (local_file, cache_file) {
+            (Some(file), _) => fs::read_to_string(file).into_diagnostic()?,
+            (None, Some(cache_file)) if is_fresh(&cache_file) => {
+                fs::read_to_string(cache_file).into_diagnostic()?
+            }
+            (None, cache_file) => {
+                let url = format!("{MODULE_DOCS_URL}/{module_type}/module.yml");
+                debug!("Retrieving module docs from {url}");
+
+                match ASYNC_RUNTIME.block_on(fetch(url)) {
+                    Ok(contents) => {
+                        if let Some(cache_file) = cache_file {
+                            if let Err(e) = cache_file
+                                .parent()
+                                .map_or(Ok(()), fs::create_dir_all)
+                                .and_then(|()| fs::write(&cache_file, &contents))
+                            {
+                                warn!("Failed to cache docs for {module_type}: {e}");
+                            }
+                        }
+                        contents
+                    }
+                    Err(e) => match cache_file.filter(|file| file.is_file()) {
+                        Some(cache_file) => fs::read_to_string(cache_file).into_diagnostic()?,
+                        None => return Err(e),
+                    },
+                }
+            }
+        };
+
+        serde_yaml::from_str(&contents)
+            .into_diagnostic()
+            .with_context(|| format!("Failed to parse module.yml for {module_type}"))
+    }
+
+    fn local_module_dirs(&self) -> Vec<PathBuf> {
+        self.root
+            .iter()
+            .flat_map(|root| [root.join("modules"), root.join("config/modules")])
+            .collect()
+    }
+
+    /// Walks a schema looking for subschemas that describe
+    /// a module, following any `$ref` to another schema.
+    fn collect(
+        &mut self,
+        schema: &Value,
+        schema_dir: Option<&Path>,
+        visited: &mut HashSet<String>,
+        depth: usize,
+    ) {
+        let Value::Object(object) = schema else {
+            return;
+        };
+
+        if let Some(module_type) = object
+            .get("properties")
+            .and_then(|properties| properties.get("type"))
+            .and_then(|module_type| module_type.get("const"))
+            .and_then(Value::as_str)
+        {
+            trace!("Found schema for module {module_type}");
+            self.modules
+                .insert(module_type.to_string(), module_info(schema));
+            return;
+        }
+
+        for key in ["anyOf", "oneOf", "allOf"] {
+            for subschema in object
+                .get(key)
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                self.collect(subschema, schema_dir, visited, depth);
+            }
+        }
+
+        for subschema in object
+            .get("$defs")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flat_map(|defs| defs.values())
+        {
+            self.collect(subschema, schema_dir, visited, depth);
+        }
+
+        if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+            if reference.starts_with('#') || depth >= MAX_REF_DEPTH {
+                return;
+            }
+
+            let url = if reference.starts_with("http") {
+                reference.to_string()
+            } else {
+                format!("{BASE_SCHEMA_URL}/{}", reference.trim_start_matches('/'))
+            };
+
+            if visited.insert(url.clone()) {
+                // Schemas in `/modules/` are named after the module
+                // they describe, which is used when the schema doesn't
+                // have a `const` for its `type`.
+                let module_type = module_type_from_ref(&url);
+
+                match ASYNC_RUNTIME.block_on(retrieve_schema(
+                    url.clone(),
+                    schema_dir.map(Path::to_path_buf),
+                )) {
+                    Ok(schema) => {
+                        self.collect(&schema, schema_dir, visited, depth + 1);
+                        if let Some(module_type) = module_type {
+                            self.modules
+                                .entry(module_type)
+                                .or_insert_with(|| module_info(&schema));
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Unable to retrieve schema {url}: {e:?}");
+                        if let Some(module_type) = module_type {
+                            self.modules.entry(module_type).or_default();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn module_info(schema: &Value) -> ModuleInfo {
+    ModuleInfo {
+        description: description(schema),
+        properties: schema
+            .get("properties")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .filter(|(name, _)| *name != "type")
+            .map(|(name, property)| (name.clone(), description(property)))
+            .collect(),
+    }
+}
+
+/// Gets the module type from the url of a module's schema,
+/// e.g. `/modules/akmods-v1.json` is the `akmods` module.
+fn module_type_from_ref(url: &str) -> Option<String> {
+    let (_, file) = url.split_once("/modules/")?;
+    let name = file.strip_suffix(".json")?;
+    let name = name.strip_suffix("-latest").unwrap_or(name);
+    let name = name
+        .rsplit_once("-v")
+        .filter(|(_, version)| !version.is_empty() && version.chars().all(|c| c.is_ascii_digit()))
+        .map_or(name, |(name, _)| name);
+
+    Some(name.to_string())
+}
+
+fn description(schema: &Value) -> Option<String> {
+    schema
+        .get("description")
+        .and_then(Value::as_str)
+        .map(ToString::to_string)
+}
+
+async fn fetch(url: String) -> Result<String> {
+    tokio::spawn(async move {
+        reqwest::get(&url)
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .into_diagnostic()
+            .with_context(|| format!("Failed to retrieve {url}"))?
+            .text()
+            .await
+            .into_diagnostic()
+    })
+    .await
+    .expect("Should join task")
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::{module_type_from_ref, ModuleCatalog};
+
+    #[test]
+    fn collect_modules() {
+        let schema = json!({
+            "anyOf": [
+                { "$ref": "#/$defs/ModuleEntry" },
+            ],
+            "$defs": {
+                "ModuleEntry": {
+                    "anyOf": [
+                        {
+                            "description": "Installs packages",
+                            "properties": {
+                                "type": { "const": "rpm-ostree" },
+                                "install": {
+                                    "type": "array",
+                                    "description": "Packages to install",
+                                },
+                                "remove": { "type": "array" },
+                            },
+                        },
+                        {
+                            "properties": {
+                                "type": { "const": "script" },
+                            },
+                        },
+                    ],
+                },
+            },
+        });
+
+        let catalog = ModuleCatalog::new(&schema, None, None);
+        let rpm_ostree = catalog.get("rpm-ostree").unwrap();
+
+        assert_eq!(
+            catalog.modules().map(|(name, _)| name).collect::<Vec<_>>(),
+            ["rpm-ostree", "script"]
+        );
+        assert_eq!(rpm_ostree.description.as_deref(), Some("Installs packages"));
+        assert_eq!(
+            rpm_ostree.properties.get("install").unwrap().as_deref(),
+            Some("Packages to install")
+        );
+        assert_eq!(rpm_ostree.properties.get("remove"), Some(&None));
+    }
+
+    #[test]
+    fn collect_module_refs() {
+        let schema_dir = Path::new("test-files/schemas");
+        let schema =
+            serde_json::from_str(include_str!("../../../test-files/schemas/module-v1.json"))
+                .unwrap();
+
+        let catalog = ModuleCatalog::new(&schema, Some(schema_dir), None);
+        let rpm_ostree = catalog.get("rpm-ostree").unwrap();
+
+        assert!(rpm_ostree.properties.contains_key("install"));
+    }
+
+    #[rstest]
+    #[case("https://schema.blue-build.org/modules/akmods.json", Some("akmods"))]
+    #[case("https://schema.blue-build.org/modules/akmods-v1.json", Some("akmods"))]
+    #[case(
+        "https://schema.blue-build.org/modules/default-flatpaks-latest.json",
+        Some("default-flatpaks")
+    )]
+    #[case(
+        "https://schema.blue-build.org/modules/gnome-extensions-v2.json",
+        Some("gnome-extensions")
+    )]
+    #[case("https://schema.blue-build.org/module-v1.json", None)]
+    fn module_type_from_refs(#[case] url: &str, #[case] expected: Option<&str>) {
+        assert_eq!(module_type_from_ref(url).as_deref(), expected);
+    }
+}
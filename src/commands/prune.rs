@@ -1,4 +1,7 @@
-use blue_build_process_management::drivers::{opts::PruneOpts, BuildDriver, Driver, DriverArgs};
+use blue_build_process_management::{
+    drivers::{opts::PruneOpts, BuildDriver, Driver, DriverArgs},
+    dry_run::set_dry_run,
+};
 use bon::Builder;
 use clap::Args;
 use colored::Colorize;
@@ -6,6 +9,7 @@ use miette::bail;
 
 use super::BlueBuildCommand;
 
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Debug, Args, Builder)]
 pub struct PruneCommand {
     /// Remove all unused images
@@ -23,6 +27,11 @@ pub struct PruneCommand {
     #[arg(long)]
     volumes: bool,
 
+    /// Print the commands that would be run instead of running them
+    #[builder(default)]
+    #[arg(long)]
+    dry_run: bool,
+
     #[clap(flatten)]
     #[builder(default)]
     drivers: DriverArgs,
@@ -32,7 +41,11 @@ impl BlueBuildCommand for PruneCommand {
     fn try_run(&mut self) -> miette::Result<()> {
         Driver::init(self.drivers);
 
-        if !self.force {
+        if self.dry_run {
+            set_dry_run(true);
+        }
+
+        if !self.force && !self.dry_run {
             eprintln!(
                 "{} This will remove:{default}{images}{build_cache}{volumes}",
                 "WARNING!".bright_yellow(),
@@ -0,0 +1,55 @@
+use blue_build_process_management::logging::CommandLogging;
+use bon::Builder;
+use clap::Args;
+use log::{info, trace};
+use miette::{bail, IntoDiagnostic, Result};
+
+use crate::boot_status::get_boot_status;
+
+use super::{switch::sudo_notice, BlueBuildCommand};
+
+#[derive(Default, Clone, Debug, Builder, Args)]
+pub struct RollbackCommand {
+    /// Reboot your system after
+    /// the rollback is complete.
+    #[arg(short, long)]
+    #[builder(default)]
+    reboot: bool,
+}
+
+impl BlueBuildCommand for RollbackCommand {
+    fn try_run(&mut self) -> Result<()> {
+        trace!("RollbackCommand::try_run()");
+
+        let status = get_boot_status()?;
+        trace!("{status:?}");
+
+        if status.transaction_in_progress() {
+            bail!(
+                "There is a transaction in progress. Please cancel it using `{} cancel`",
+                status.name()
+            );
+        }
+
+        let Some(rollback) = status.rollback_deployment() else {
+            bail!("There is no deployment to roll back to");
+        };
+        info!("Rolling back to {}", rollback.image);
+
+        if status.requires_sudo() {
+            sudo_notice();
+        }
+
+        let command = status.rollback_command(self.reboot);
+        trace!("{command:?}");
+
+        let exit_status = command
+            .build_status(&rollback.image, "Rolling back to previous image")
+            .into_diagnostic()?;
+
+        if !exit_status.success() {
+            bail!("Failed to roll back to {}", rollback.image);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use clap::Args;
+use colored::Colorize;
+use log::trace;
+use miette::Result;
+
+use crate::boot_status::{get_boot_status, BootStatus, Deployment};
+
+use super::BlueBuildCommand;
+
+#[derive(Default, Clone, Debug, Args)]
+pub struct StatusCommand {}
+
+impl BlueBuildCommand for StatusCommand {
+    fn try_run(&mut self) -> Result<()> {
+        trace!("StatusCommand::try_run()");
+
+        let status = get_boot_status()?;
+        trace!("{status:?}");
+
+        println!("{}", report(&*status));
+        Ok(())
+    }
+}
+
+fn report(status: &dyn BootStatus) -> String {
+    let mut lines = vec![format!("{:<13}{}", "Managed by:", status.name())];
+
+    if status.transaction_in_progress() {
+        lines.push(format!(
+            "{:<13}{}",
+            "Transaction:",
+            "in progress".bold().yellow()
+        ));
+    }
+
+    for (name, deployment) in [
+        ("Booted", status.booted_deployment()),
+        ("Staged", status.staged_deployment()),
+        ("Rollback", status.rollback_deployment()),
+    ] {
+        lines.push(String::new());
+        lines.push(format!("{}:", name.bold()));
+        lines.extend(
+            deployment
+                .as_ref()
+                .map_or_else(|| vec![String::from("  none")], deployment_lines),
+        );
+    }
+
+    lines.join("\n")
+}
+
+fn deployment_lines(deployment: &Deployment) -> Vec<String> {
+    let field = |label: &str, value: Option<&str>| {
+        format!(
+            "  {:<11}{}",
+            format!("{label}:"),
+            value.unwrap_or("unknown")
+        )
+    };
+
+    vec![
+        field("Image", Some(&deployment.image)),
+        field("Digest", deployment.digest.as_deref()),
+        field("Version", deployment.version.as_deref()),
+        field("Recipe", deployment.recipe.as_deref()),
+        field("Build ID", deployment.build_id.as_deref()),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{bootc_status::BootcStatus, rpm_ostree_status::RpmOstreeStatus};
+
+    use super::report;
+
+    #[test]
+    fn rpm_ostree_report() {
+        colored::control::set_override(false);
+
+        let status: RpmOstreeStatus = serde_json::from_str(include_str!(
+            "../../test-files/boot-status/rpm-ostree-staged.json"
+        ))
+        .unwrap();
+
+        assert_eq!(
+            report(&status),
+            "Managed by:  rpm-ostree
+
+Booted:
+  Image:     ostree-image-signed:docker://ghcr.io/blue-build/cli/test:latest
+  Digest:    sha256:1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c8d9e0f1a2b
+  Version:   40.20241016.0
+  Recipe:    cli/test
+  Build ID:  0c8d7e6f-1a2b-4c3d-8e9f-a0b1c2d3e4f5
+
+Staged:
+  Image:     ostree-unverified-image:oci-archive:/etc/bluebuild/cli_test.tar.gz
+  Digest:    sha256:5b2a8c1e0d7f4a6b9c3e2d1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f2a1b
+  Version:   40.20241017.0
+  Recipe:    cli/test
+  Build ID:  5f0f9b0a-5b9e-4c6f-9d8a-2f1e3c4b5a69
+
+Rollback:
+  Image:     ostree-image-signed:docker://ghcr.io/blue-build/cli/test:latest
+  Digest:    sha256:9f8e7d6c5b4a39281706f5e4d3c2b1a09f8e7d6c5b4a39281706f5e4d3c2b1a0
+  Version:   40.20241015.0
+  Recipe:    cli/test
+  Build ID:  7a6b5c4d-3e2f-4a1b-9c8d-7e6f5a4b3c2d"
+        );
+    }
+
+    #[test]
+    fn bootc_report() {
+        colored::control::set_override(false);
+
+        let status: BootcStatus = serde_json::from_str(include_str!(
+            "../../test-files/boot-status/bootc-incompatible.json"
+        ))
+        .unwrap();
+
+        assert_eq!(
+            report(&status),
+            "Managed by:  bootc
+
+Booted:
+  none
+
+Staged:
+  none
+
+Rollback:
+  none"
+        );
+    }
+}
@@ -5,30 +5,56 @@ use std::{
 
 use blue_build_process_management::{
     drivers::{Driver, DriverArgs},
+    dry_run::{is_dry_run, set_dry_run, DryRunCommand},
     logging::CommandLogging,
 };
-use blue_build_recipe::Recipe;
+use blue_build_recipe::{Recipe, VarOverride};
 use blue_build_utils::{
     cmd,
-    constants::{ARCHIVE_SUFFIX, LOCAL_BUILD, OCI_ARCHIVE, OSTREE_UNVERIFIED_IMAGE},
+    constants::{ARCHIVE_SUFFIX, COSIGN_PUB_PATH, LOCAL_BUILD},
 };
 use bon::Builder;
 use clap::Args;
 use colored::Colorize;
 use indicatif::ProgressBar;
 use log::{debug, trace, warn};
-use miette::{bail, IntoDiagnostic, Result};
+use miette::{bail, Context, IntoDiagnostic, Result};
+use oci_distribution::Reference;
 use tempfile::TempDir;
 
-use crate::{commands::build::BuildCommand, rpm_ostree_status::RpmOstreeStatus};
+use crate::{
+    boot_status::{get_boot_status, BootStatus},
+    commands::build::BuildCommand,
+};
+
+use self::policy::SignaturePolicy;
 
 use super::BlueBuildCommand;
 
+mod policy;
+
 #[derive(Default, Clone, Debug, Builder, Args)]
 pub struct SwitchCommand {
     /// The recipe file to build an image.
-    #[arg()]
-    recipe: PathBuf,
+    #[arg(required_unless_present = "image")]
+    #[builder(into)]
+    recipe: Option<PathBuf>,
+
+    /// Switch to an image in a registry that has been
+    /// signed by CI instead of building it locally.
+    ///
+    /// The host is set up to verify the signature
+    /// of the image every time it updates.
+    #[arg(long, conflicts_with_all = ["recipe", "tempdir", "vars"])]
+    #[builder(into)]
+    image: Option<String>,
+
+    /// The public key the image is signed with.
+    ///
+    /// Defaults to `./cosign.pub`.
+    #[arg(long, requires = "image")]
+    #[builder(into)]
+    key: Option<PathBuf>,
 
     /// Reboot your system after
     /// the update is complete.
@@ -41,6 +67,22 @@ pub struct SwitchCommand {
     #[arg(long)]
     tempdir: Option<PathBuf>,
 
+    /// Override a variable in the recipe's `vars:` section.
+    ///
+    /// Can be used multiple times. Values are parsed
+    /// as yaml (e.g. `--var packages=[micro,htop]`).
+    #[arg(long = "var", value_name = "KEY=VALUE")]
+    #[builder(default, into)]
+    vars: Vec<VarOverride>,
+
+    /// Print the commands that would be run instead of running them.
+    ///
+    /// Nothing is built or installed on the host, but the
+    /// image is still resolved and the Containerfile rendered.
+    #[arg(long)]
+    #[builder(default)]
+    dry_run: bool,
+
     #[clap(flatten)]
     #[builder(default)]
     drivers: DriverArgs,
@@ -52,13 +94,28 @@ impl BlueBuildCommand for SwitchCommand {
 
         Driver::init(self.drivers);
 
-        let status = RpmOstreeStatus::try_new()?;
+        if self.dry_run {
+            set_dry_run(true);
+        }
+
+        let status = get_boot_status()?;
         trace!("{status:?}");
 
         if status.transaction_in_progress() {
-            bail!("There is a transaction in progress. Please cancel it using `rpm-ostree cancel`");
+            bail!(
+                "There is a transaction in progress. Please cancel it using `{} cancel`",
+                status.name()
+            );
+        }
+
+        if let Some(image) = self.image.as_deref() {
+            return self.switch_image(image, &*status);
         }
 
+        let Some(recipe_path) = self.recipe.as_ref() else {
+            bail!("Either a recipe or `--image` is required");
+        };
+
         let tempdir = if let Some(ref dir) = self.tempdir {
             TempDir::new_in(dir).into_diagnostic()?
         } else {
@@ -68,20 +125,22 @@ impl BlueBuildCommand for SwitchCommand {
 
         #[cfg(feature = "multi-recipe")]
         BuildCommand::builder()
-            .recipe([self.recipe.clone()])
+            .recipe([recipe_path.clone()])
             .archive(tempdir.path())
             .maybe_tempdir(self.tempdir.clone())
+            .vars(self.vars.clone())
             .build()
             .try_run()?;
         #[cfg(not(feature = "multi-recipe"))]
         BuildCommand::builder()
-            .recipe(self.recipe.clone())
+            .recipe(recipe_path.clone())
             .archive(tempdir.path())
             .maybe_tempdir(self.tempdir.clone())
+            .vars(self.vars.clone())
             .build()
             .try_run()?;
 
-        let recipe = Recipe::parse(&self.recipe)?;
+        let recipe = Recipe::parse_with_vars(recipe_path, &self.vars)?;
         let image_file_name = format!(
             "{}.{ARCHIVE_SUFFIX}",
             recipe.name.to_lowercase().replace('/', "_")
@@ -89,13 +148,7 @@ impl BlueBuildCommand for SwitchCommand {
         let temp_file_path = tempdir.path().join(&image_file_name);
         let archive_path = Path::new(LOCAL_BUILD).join(&image_file_name);
 
-        warn!(
-            "{notice}: {} {sudo} {}",
-            "The next few steps will require".yellow(),
-            "You may have to supply your password".yellow(),
-            notice = "NOTICE".bright_red().bold(),
-            sudo = "`sudo`.".italic().bright_red().bold(),
-        );
+        sudo_notice();
         Self::sudo_clean_local_build_dir()?;
         Self::sudo_move_archive(&temp_file_path, &archive_path)?;
 
@@ -103,51 +156,80 @@ impl BlueBuildCommand for SwitchCommand {
         // can be cleaned out.
         drop(tempdir);
 
-        self.switch(&archive_path, &status)
+        self.switch(&archive_path, &*status)
     }
 }
 
 impl SwitchCommand {
-    fn switch(&self, archive_path: &Path, status: &RpmOstreeStatus<'_>) -> Result<()> {
+    fn switch(&self, archive_path: &Path, status: &dyn BootStatus) -> Result<()> {
         trace!(
             "SwitchCommand::switch({}, {status:#?})",
             archive_path.display()
         );
 
-        let status = if status.is_booted_on_archive(archive_path)
+        let command = if status.is_booted_on_archive(archive_path)
             || status.is_staged_on_archive(archive_path)
         {
-            let mut command = cmd!("rpm-ostree", "upgrade");
+            status.upgrade_command(self.reboot)
+        } else {
+            status.switch_archive_command(archive_path, self.reboot)
+        };
+        trace!("{command:?}");
 
-            if self.reboot {
-                cmd!(command, "--reboot");
-            }
+        let status = command
+            .build_status(
+                format!("{}", archive_path.display()),
+                "Switching to new image",
+            )
+            .into_diagnostic()?;
 
-            trace!("{command:?}");
-            command
-        } else {
-            let image_ref = format!(
-                "{OSTREE_UNVERIFIED_IMAGE}:{OCI_ARCHIVE}:{path}",
-                path = archive_path.display()
-            );
+        if !status.success() {
+            bail!("Failed to switch to new image!");
+        }
+        Ok(())
+    }
 
-            let mut command = cmd!("rpm-ostree", "rebase", &image_ref);
+    fn switch_image(&self, image: &str, status: &dyn BootStatus) -> Result<()> {
+        trace!("SwitchCommand::switch_image({image}, {status:#?})");
 
-            if self.reboot {
-                cmd!(command, "--reboot");
-            }
+        let reference: Reference = image
+            .parse()
+            .into_diagnostic()
+            .with_context(|| format!("Unable to parse image reference {image}"))?;
+        let key = self
+            .key
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(COSIGN_PUB_PATH));
 
-            trace!("{command:?}");
-            command
+        if !key.is_file() {
+            bail!(
+                help = "Use `--key` to set the public key the image is signed with",
+                "The public key {} does not exist",
+                key.display()
+            );
         }
-        .build_status(
-            format!("{}", archive_path.display()),
-            "Switching to new image",
-        )
-        .into_diagnostic()?;
+
+        sudo_notice();
+        SignaturePolicy::new(&reference).install(&key)?;
+
+        let image = reference.whole();
+        let image_ref = status.signed_image_ref(&image);
+
+        let command = if status.booted_image().as_ref() == Some(&image_ref)
+            || status.staged_image().as_ref() == Some(&image_ref)
+        {
+            status.upgrade_command(self.reboot)
+        } else {
+            status.switch_signed_image_command(&image, self.reboot)
+        };
+        trace!("{command:?}");
+
+        let status = command
+            .build_status(&image, "Switching to signed image")
+            .into_diagnostic()?;
 
         if !status.success() {
-            bail!("Failed to switch to new image!");
+            bail!("Failed to switch to {image}!");
         }
         Ok(())
     }
@@ -164,7 +246,9 @@ impl SwitchCommand {
         progress.set_message(format!("Moving image archive to {}...", to.display()));
 
         trace!("sudo mv {} {}", from.display(), to.display());
-        let status = cmd!("sudo", "mv", from, to).status().into_diagnostic()?;
+        let status = cmd!("sudo", "mv", from, to)
+            .dry_run_status()
+            .into_diagnostic()?;
 
         progress.finish_and_clear();
 
@@ -190,7 +274,7 @@ impl SwitchCommand {
             trace!("sudo ls {LOCAL_BUILD}");
             let output = String::from_utf8(
                 cmd!("sudo", "ls", LOCAL_BUILD)
-                    .output()
+                    .dry_run_output()
                     .into_diagnostic()?
                     .stdout,
             )
@@ -212,7 +296,9 @@ impl SwitchCommand {
                 progress.set_message("Removing old image archive files...");
 
                 trace!("sudo rm -f {files}");
-                let status = cmd!("sudo", "rm", "-f", files).status().into_diagnostic()?;
+                let status = cmd!("sudo", "rm", "-f", files)
+                    .dry_run_status()
+                    .into_diagnostic()?;
 
                 progress.finish_and_clear();
 
@@ -227,7 +313,7 @@ impl SwitchCommand {
             );
 
             let status = cmd!("sudo", "mkdir", "-p", LOCAL_BUILD)
-                .status()
+                .dry_run_status()
                 .into_diagnostic()?;
 
             if !status.success() {
@@ -238,3 +324,18 @@ impl SwitchCommand {
         Ok(())
     }
 }
+
+/// Lets the user know that they may be asked for their password.
+pub(super) fn sudo_notice() {
+    if is_dry_run() {
+        return;
+    }
+
+    warn!(
+        "{notice}: {} {sudo} {}",
+        "The next few steps will require".yellow(),
+        "You may have to supply your password".yellow(),
+        notice = "NOTICE".bright_red().bold(),
+        sudo = "`sudo`.".italic().bright_red().bold(),
+    );
+}
@@ -0,0 +1,220 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use blue_build_process_management::dry_run::DryRunCommand;
+use blue_build_utils::cmd;
+use indicatif::ProgressBar;
+use log::{debug, trace};
+use miette::{bail, Context, IntoDiagnostic, Result};
+use oci_distribution::Reference;
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+const POLICY_PATH: &str = "/etc/containers/policy.json";
+const REGISTRIES_D_PATH: &str = "/etc/containers/registries.d";
+const CONTAINERS_PKI_PATH: &str = "/etc/pki/containers";
+
+/// The host's signature policy for an image signed
+/// with a `cosign` key pair.
+#[derive(Debug)]
+pub struct SignaturePolicy {
+    scope: String,
+    key_path: PathBuf,
+    registries_d_path: PathBuf,
+}
+
+impl SignaturePolicy {
+    pub fn new(image: &Reference) -> Self {
+        let name = image.repository().replace('/', "_");
+
+        Self {
+            scope: format!("{}/{}", image.registry(), image.repository()),
+            key_path: Path::new(CONTAINERS_PKI_PATH).join(format!("{name}.pub")),
+            registries_d_path: Path::new(REGISTRIES_D_PATH).join(format!("{name}.yaml")),
+        }
+    }
+
+    /// Installs the public key, the policy, and the `registries.d`
+    /// entry so that the host verifies the signature of the image
+    /// every time it's pulled.
+    ///
+    /// # Errors
+    /// Will error if the current policy can't be read or
+    /// any of the files can't be installed.
+    pub fn install(&self, public_key: &Path) -> Result<()> {
+        trace!("SignaturePolicy::install({})", public_key.display());
+
+        let current_policy = Path::new(POLICY_PATH)
+            .exists()
+            .then(|| fs::read_to_string(POLICY_PATH))
+            .transpose()
+            .into_diagnostic()
+            .with_context(|| format!("Failed to read {POLICY_PATH}"))?;
+
+        let tempdir = TempDir::new().into_diagnostic()?;
+        let policy_path = tempdir.path().join("policy.json");
+        let registries_d_path = tempdir.path().join("registries.yaml");
+
+        fs::write(&policy_path, self.policy(current_policy.as_deref())?).into_diagnostic()?;
+        fs::write(&registries_d_path, self.registries_d()?).into_diagnostic()?;
+
+        let progress = ProgressBar::new_spinner();
+        progress.enable_steady_tick(Duration::from_millis(100));
+        progress.set_message(format!("Installing signature policy for {}...", self.scope));
+
+        let result = [
+            (public_key, self.key_path.as_path()),
+            (
+                registries_d_path.as_path(),
+                self.registries_d_path.as_path(),
+            ),
+            (policy_path.as_path(), Path::new(POLICY_PATH)),
+        ]
+        .into_iter()
+        .try_for_each(|(from, to)| sudo_install(from, to));
+
+        progress.finish_and_clear();
+        result
+    }
+
+    /// Adds a requirement for the image to be signed with the key
+    /// to the host's current policy.
+    fn policy(&self, current_policy: Option<&str>) -> Result<String> {
+        let mut policy: Value = current_policy.map_or_else(
+            || Ok(json!({ "default": [{ "type": "insecureAcceptAnything" }] })),
+            |policy| {
+                serde_json::from_str(policy)
+                    .into_diagnostic()
+                    .with_context(|| format!("Failed to parse {POLICY_PATH}"))
+            },
+        )?;
+
+        let Some(policy_map) = policy.as_object_mut() else {
+            bail!("The policy in {POLICY_PATH} must be an object");
+        };
+        let Some(docker) = policy_map
+            .entry("transports")
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .map(|transports| transports.entry("docker").or_insert_with(|| json!({})))
+            .and_then(Value::as_object_mut)
+        else {
+            bail!("The policy in {POLICY_PATH} has an invalid `transports` section");
+        };
+
+        docker.insert(
+            self.scope.clone(),
+            json!([{
+                "type": "sigstoreSigned",
+                "keyPath": self.key_path,
+                "signedIdentity": { "type": "matchRepository" },
+            }]),
+        );
+
+        serde_json::to_string_pretty(&policy).into_diagnostic()
+    }
+
+    /// Tells the host to look for the signatures
+    /// that `cosign` attaches to the image.
+    fn registries_d(&self) -> Result<String> {
+        serde_yaml::to_string(&json!({
+            "docker": {
+                &self.scope: { "use-sigstore-attachments": true },
+            },
+        }))
+        .into_diagnostic()
+    }
+}
+
+fn sudo_install(from: &Path, to: &Path) -> Result<()> {
+    debug!("Installing {} to {}", from.display(), to.display());
+
+    trace!(
+        "sudo install -D -m 0644 {} {}",
+        from.display(),
+        to.display()
+    );
+    let status = cmd!("sudo", "install", "-D", "-m", "0644", from, to)
+        .dry_run_status()
+        .into_diagnostic()?;
+
+    if !status.success() {
+        bail!("Failed to install {}", to.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::SignaturePolicy;
+
+    fn policy() -> SignaturePolicy {
+        SignaturePolicy::new(&"ghcr.io/octocat/my-image:latest".parse().unwrap())
+    }
+
+    #[test]
+    fn default_policy() {
+        let policy: serde_json::Value =
+            serde_json::from_str(&policy().policy(None).unwrap()).unwrap();
+
+        assert_eq!(
+            policy,
+            json!({
+                "default": [{ "type": "insecureAcceptAnything" }],
+                "transports": {
+                    "docker": {
+                        "ghcr.io/octocat/my-image": [{
+                            "type": "sigstoreSigned",
+                            "keyPath": "/etc/pki/containers/octocat_my-image.pub",
+                            "signedIdentity": { "type": "matchRepository" },
+                        }],
+                    },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn existing_policy() {
+        let current = json!({
+            "default": [{ "type": "reject" }],
+            "transports": {
+                "docker": {
+                    "ghcr.io/octocat/my-image": [{ "type": "insecureAcceptAnything" }],
+                    "registry.access.redhat.com": [{ "type": "signedBy" }],
+                },
+                "docker-daemon": { "": [{ "type": "insecureAcceptAnything" }] },
+            },
+        });
+
+        let policy: serde_json::Value =
+            serde_json::from_str(&policy().policy(Some(&current.to_string())).unwrap()).unwrap();
+
+        assert_eq!(policy["default"], current["default"]);
+        assert_eq!(
+            policy["transports"]["docker-daemon"],
+            current["transports"]["docker-daemon"]
+        );
+        assert_eq!(
+            policy["transports"]["docker"]["registry.access.redhat.com"],
+            current["transports"]["docker"]["registry.access.redhat.com"]
+        );
+        assert_eq!(
+            policy["transports"]["docker"]["ghcr.io/octocat/my-image"][0]["type"],
+            "sigstoreSigned"
+        );
+    }
+
+    #[test]
+    fn registries_d() {
+        assert_eq!(
+            policy().registries_d().unwrap(),
+            "docker:\n  ghcr.io/octocat/my-image:\n    use-sigstore-attachments: true\n"
+        );
+    }
+}
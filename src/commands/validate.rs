@@ -6,7 +6,9 @@ use std::{
 };
 
 use blue_build_process_management::ASYNC_RUNTIME;
-use blue_build_recipe::{FromFileList, ModuleExt, Recipe, StagesExt};
+use blue_build_recipe::{FromFileList, ModuleExt, Recipe, StagesExt, VarOverride, Vars};
+use blue_build_template::ModuleConditions;
+use blue_build_utils::constants::BB_SCHEMA_DIR;
 use bon::Builder;
 use clap::Args;
 use colored::Colorize;
@@ -14,17 +16,18 @@ use log::{debug, info, trace};
 use miette::{bail, miette, Context, IntoDiagnostic, Report};
 use rayon::prelude::*;
 use schema_validator::{
-    SchemaValidator, MODULE_STAGE_LIST_V1_SCHEMA_URL, MODULE_V1_SCHEMA_URL, RECIPE_V1_SCHEMA_URL,
+    remove_local_properties, remove_local_recipe_properties, SchemaValidator,
+    MODULE_STAGE_LIST_V1_SCHEMA_URL, MODULE_V1_SCHEMA_URL, RECIPE_V1_SCHEMA_URL,
     STAGE_V1_SCHEMA_URL,
 };
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
 
-use super::BlueBuildCommand;
+use super::{BlueBuildCommand, OutputFormat};
 
-mod location;
-mod schema_validator;
-mod yaml_span;
+pub(crate) mod location;
+pub(crate) mod schema_validator;
+pub(crate) mod yaml_span;
 
 #[derive(Debug, Args, Builder)]
 pub struct ValidateCommand {
@@ -41,6 +44,32 @@ pub struct ValidateCommand {
     #[builder(default)]
     pub all_errors: bool,
 
+    /// Override a variable in the recipe's `vars:` section.
+    ///
+    /// Can be used multiple times. Values are parsed
+    /// as yaml (e.g. `--var packages=[micro,htop]`).
+    #[arg(long = "var", value_name = "KEY=VALUE")]
+    #[builder(default, into)]
+    pub vars: Vec<VarOverride>,
+
+    /// A directory containing the json schemas to validate against.
+    ///
+    /// Schemas are otherwise taken from the cache in
+    /// `~/.cache/bluebuild/schemas` or retrieved from
+    /// <https://schema.blue-build.org>, falling back to
+    /// the ones embedded in the binary when offline.
+    #[arg(long, env = BB_SCHEMA_DIR)]
+    #[builder(into)]
+    pub schema_dir: Option<PathBuf>,
+
+    /// The format to print the results in.
+    ///
+    /// The JSON output contains every error
+    /// with the file, line, and column it's at.
+    #[arg(short, long, default_value = "text")]
+    #[builder(default)]
+    pub output: OutputFormat,
+
     #[clap(skip)]
     recipe_validator: Option<SchemaValidator>,
 
@@ -62,9 +91,29 @@ impl BlueBuildCommand for ValidateCommand {
             bail!("File {recipe_path_display} must exist");
         }
 
+        if self.output.is_json() {
+            // Colors would end up in the error messages
+            colored::control::set_override(false);
+        }
+        self.output.redirect_stdout()?;
+
         ASYNC_RUNTIME.block_on(self.setup_validators())?;
 
-        if let Err(errors) = self.validate_recipe() {
+        let result = self.validate_recipe();
+
+        if self.output.is_json() {
+            OutputFormat::print_json(&ValidateReport {
+                recipe: &self.recipe,
+                valid: result.is_ok(),
+                errors: result
+                    .as_ref()
+                    .err()
+                    .map(|errors| errors.iter().flat_map(ErrorReport::from_report).collect())
+                    .unwrap_or_default(),
+            })?;
+        }
+
+        if let Err(errors) = result {
             let errors = errors.into_iter().fold(String::new(), |mut full, err| {
                 full.push_str(&format!("{err:?}"));
                 full
@@ -97,11 +146,21 @@ impl BlueBuildCommand for ValidateCommand {
 impl ValidateCommand {
     async fn setup_validators(&mut self) -> Result<(), Report> {
         let (rv, sv, mv, mslv) = tokio::try_join!(
-            SchemaValidator::builder().url(RECIPE_V1_SCHEMA_URL).build(),
-            SchemaValidator::builder().url(STAGE_V1_SCHEMA_URL).build(),
-            SchemaValidator::builder().url(MODULE_V1_SCHEMA_URL).build(),
+            SchemaValidator::builder()
+                .url(RECIPE_V1_SCHEMA_URL)
+                .maybe_schema_dir(self.schema_dir.clone())
+                .build(),
+            SchemaValidator::builder()
+                .url(STAGE_V1_SCHEMA_URL)
+                .maybe_schema_dir(self.schema_dir.clone())
+                .build(),
+            SchemaValidator::builder()
+                .url(MODULE_V1_SCHEMA_URL)
+                .maybe_schema_dir(self.schema_dir.clone())
+                .build(),
             SchemaValidator::builder()
                 .url(MODULE_STAGE_LIST_V1_SCHEMA_URL)
+                .maybe_schema_dir(self.schema_dir.clone())
                 .build(),
         )?;
         self.recipe_validator = Some(rv);
@@ -116,6 +175,7 @@ impl ValidateCommand {
         path: &Path,
         traversed_files: &[&Path],
         single_validator: &SchemaValidator,
+        vars: &Vars,
     ) -> Vec<Report>
     where
         DF: DeserializeOwned + FromFileList,
@@ -140,11 +200,10 @@ impl ValidateCommand {
             Ok(f) => Arc::new(f),
         };
 
-        match serde_yaml::from_str::<Value>(&file_str)
-            .into_diagnostic()
+        match interpolate_instance(&file_str, vars, false)
             .with_context(|| format!("Failed to deserialize file {path_display}"))
         {
-            Ok(instance) => {
+            Ok((yaml_instance, instance)) => {
                 trace!("{path_display}:\n{instance}");
 
                 if instance.get(DF::LIST_KEY).is_some() {
@@ -153,15 +212,19 @@ impl ValidateCommand {
                         .module_stage_list_validator
                         .as_ref()
                         .unwrap()
-                        .process_validation(path, file_str.clone(), self.all_errors)
-                    {
+                        .process_instance_validation(
+                            path,
+                            file_str.clone(),
+                            &instance,
+                            self.all_errors,
+                        ) {
                         Err(e) => return vec![e],
                         Ok(e) => e,
                     };
 
                     err.map_or_else(
                         || {
-                            serde_yaml::from_str::<DF>(&file_str)
+                            serde_yaml::from_value::<DF>(yaml_instance)
                                 .into_diagnostic()
                                 .map_or_else(
                                     |e| vec![e],
@@ -174,6 +237,7 @@ impl ValidateCommand {
                                                     file_path,
                                                     &traversed_files,
                                                     single_validator,
+                                                    vars,
                                                 )
                                             })
                                             .flatten()
@@ -186,6 +250,7 @@ impl ValidateCommand {
                                                         file_path,
                                                         &[],
                                                         self.module_validator.as_ref().unwrap(),
+                                                        vars,
                                                     )
                                                 })
                                                 .flatten()
@@ -200,7 +265,7 @@ impl ValidateCommand {
                 } else {
                     debug!("{path_display} is a single file file");
                     single_validator
-                        .process_validation(path, file_str, self.all_errors)
+                        .process_instance_validation(path, file_str, &instance, self.all_errors)
                         .map_or_else(|e| vec![e], |e| e.map_or_else(Vec::new, |e| vec![e]))
                 }
             }
@@ -212,22 +277,23 @@ impl ValidateCommand {
         let recipe_path_display = self.recipe.display().to_string().bold().italic();
         debug!("Validating recipe {recipe_path_display}");
 
-        let recipe_str = Arc::new(read_file(&self.recipe).map_err(err_vec)?);
-        let recipe: Value = serde_yaml::from_str(&recipe_str)
-            .into_diagnostic()
+        let (recipe_str, vars) =
+            Recipe::resolve_source(&self.recipe, &self.vars).map_err(err_vec)?;
+        let recipe_str = Arc::new(recipe_str);
+        let (yaml_recipe, recipe) = interpolate_instance(&recipe_str, &vars, true)
             .with_context(|| format!("Failed to deserialize recipe {recipe_path_display}"))
             .map_err(err_vec)?;
         trace!("{recipe_path_display}:\n{recipe}");
 
         let schema_validator = self.recipe_validator.as_ref().unwrap();
         let err = schema_validator
-            .process_validation(&self.recipe, recipe_str.clone(), self.all_errors)
+            .process_instance_validation(&self.recipe, recipe_str, &recipe, self.all_errors)
             .map_err(err_vec)?;
 
         if let Some(err) = err {
             Err(vec![err])
         } else {
-            let recipe: Recipe = serde_yaml::from_str(&recipe_str)
+            let recipe: Recipe = serde_yaml::from_value(yaml_recipe)
                 .into_diagnostic()
                 .with_context(|| {
                     format!("Unable to convert Value to Recipe for {recipe_path_display}")
@@ -251,6 +317,7 @@ impl ValidateCommand {
                                 stage_path,
                                 &[],
                                 self.stage_validator.as_ref().unwrap(),
+                                &vars,
                             )
                         })
                         .flatten()
@@ -273,11 +340,17 @@ impl ValidateCommand {
                             module_path,
                             &[],
                             self.module_validator.as_ref().unwrap(),
+                            &vars,
                         )
                     })
                     .flatten()
                     .collect::<Vec<_>>(),
             );
+
+            if errors.is_empty() {
+                errors.extend(self.validate_local_properties());
+            }
+
             if errors.is_empty() {
                 Ok(())
             } else {
@@ -285,6 +358,126 @@ impl ValidateCommand {
             }
         }
     }
+
+    /// Checks the properties that aren't in the published schemas.
+    ///
+    /// Parsing the recipe checks the `secrets:` of the recipe and of
+    /// every module, and then the syntax of the `if:` conditions of every
+    /// module is checked, including `from-file` modules and stages.
+    fn validate_local_properties(&self) -> Vec<Report> {
+        debug!(
+            "Validating secrets and module conditions for recipe {}",
+            self.recipe.display()
+        );
+
+        let recipe = match Recipe::parse_with_vars(&self.recipe, &self.vars) {
+            Ok(recipe) => recipe,
+            Err(e) => return vec![e],
+        };
+
+        recipe
+            .all_modules()
+            .filter_map(|module| {
+                let condition = module.condition.as_deref()?;
+                ModuleConditions::check(condition).err().map(|e| {
+                    miette!(
+                        "Invalid condition `{condition}` on the {} module: {e}",
+                        module.module_type.bold()
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+/// The results of validating a recipe that
+/// are printed with `--output json`.
+#[derive(Debug, Serialize)]
+struct ValidateReport<'a> {
+    recipe: &'a Path,
+    valid: bool,
+    errors: Vec<ErrorReport>,
+}
+
+/// A single error found while validating.
+///
+/// Errors that aren't about a specific part
+/// of a file don't have a location.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct ErrorReport {
+    file: Option<String>,
+    line: Option<usize>,
+    column: Option<usize>,
+    message: String,
+}
+
+impl ErrorReport {
+    /// Creates an error for every message in the labels of the report,
+    /// using the span of the label to find the line and column.
+    fn from_report(report: &Report) -> Vec<Self> {
+        let (Some(labels), Some(source)) = (report.labels(), report.source_code()) else {
+            return vec![Self {
+                file: None,
+                line: None,
+                column: None,
+                message: report.to_string(),
+            }];
+        };
+
+        labels
+            .flat_map(|label| {
+                let contents = source.read_span(label.inner(), 0, 0).ok();
+                let file = contents
+                    .as_ref()
+                    .and_then(|contents| contents.name().map(ToString::to_string));
+                let (line, column) = contents.as_ref().map_or((None, None), |contents| {
+                    (Some(contents.line() + 1), Some(contents.column() + 1))
+                });
+
+                // All errors for the same location are
+                // given as a list in a single label
+                label
+                    .label()
+                    .unwrap_or_default()
+                    .lines()
+                    .map(|message| Self {
+                        file: file.clone(),
+                        line,
+                        column,
+                        message: message.trim_start_matches("- ").to_string(),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+/// Parses a file and interpolates its variables, returning
+/// the document as yaml and as the json used for validation.
+///
+/// Interpolating only changes the values of the document, so errors
+/// found in the instance can be reported against the original file.
+/// The properties that aren't in the published schemas are left out
+/// of the json, as they're checked when the recipe is parsed.
+fn interpolate_instance(
+    file: &str,
+    vars: &Vars,
+    is_recipe: bool,
+) -> Result<(serde_yaml::Value, Value), Report> {
+    let mut instance = serde_yaml::from_str::<serde_yaml::Value>(file).into_diagnostic()?;
+    vars.interpolate_document(&mut instance)?;
+
+    if let Some(map) = instance.as_mapping_mut().filter(|_| is_recipe) {
+        map.remove("vars");
+    }
+
+    let mut json = serde_json::to_value(&instance).into_diagnostic()?;
+    if is_recipe {
+        remove_local_recipe_properties(&mut json);
+    } else {
+        remove_local_properties(&mut json);
+    }
+    Ok((instance, json))
 }
 
 fn err_vec(err: Report) -> Vec<Report> {
@@ -309,3 +502,156 @@ fn read_file(path: &Path) -> Result<String, Report> {
     .into_diagnostic()?;
     Ok(recipe)
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    use blue_build_process_management::ASYNC_RUNTIME;
+    use blue_build_recipe::Vars;
+    use miette::{miette, LabeledSpan, NamedSource};
+    use serde_json::Value;
+
+    use super::{interpolate_instance, ErrorReport, SchemaValidator, RECIPE_V1_SCHEMA_URL};
+
+    const RECIPE: &str = "name: test\nmodules:\n  - type: rpm-ostree\n    install: micro\n";
+
+    #[test]
+    fn error_report_locations() {
+        let offset = RECIPE.find("install").unwrap();
+        let report = miette!(
+            labels = vec![LabeledSpan::new(
+                Some(String::from(
+                    "- \"micro\" is not of type \"array\"\n- another"
+                )),
+                offset,
+                7,
+            )],
+            "1 error encountered"
+        )
+        .with_source_code(NamedSource::new("recipes/recipe.yml", RECIPE));
+
+        assert_eq!(
+            ErrorReport::from_report(&report),
+            [
+                ErrorReport {
+                    file: Some(String::from("recipes/recipe.yml")),
+                    line: Some(4),
+                    column: Some(5),
+                    message: String::from("\"micro\" is not of type \"array\""),
+                },
+                ErrorReport {
+                    file: Some(String::from("recipes/recipe.yml")),
+                    line: Some(4),
+                    column: Some(5),
+                    message: String::from("another"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn error_report_without_location() {
+        assert_eq!(
+            ErrorReport::from_report(&miette!("Circular dependency detected!")),
+            [ErrorReport {
+                file: None,
+                line: None,
+                column: None,
+                message: String::from("Circular dependency detected!"),
+            }]
+        );
+    }
+
+    #[test]
+    fn error_report_locations_with_vars() {
+        const RECIPE_VARS: &str = "\
+vars:
+  version: [40]
+name: test
+description: test
+base-image: ghcr.io/ublue-os/silverblue-main
+image-version: ${{ version }}
+modules: []
+";
+        let file = Arc::new(String::from(RECIPE_VARS));
+        let vars = Vars::resolve(
+            serde_yaml::from_str::<serde_yaml::Value>(RECIPE_VARS)
+                .unwrap()
+                .get("vars"),
+            &[],
+        )
+        .unwrap();
+        let (_, instance) = interpolate_instance(&file, &vars, true).unwrap();
+
+        let validator = ASYNC_RUNTIME
+            .block_on(
+                SchemaValidator::builder()
+                    .url(RECIPE_V1_SCHEMA_URL)
+                    .schema_dir(PathBuf::from("schemas"))
+                    .build(),
+            )
+            .unwrap();
+        let report = validator
+            .process_instance_validation(Path::new("recipe.yml"), file, &instance, false)
+            .unwrap()
+            .unwrap();
+
+        let errors = ErrorReport::from_report(&report);
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column), (Some(6), Some(16)));
+    }
+
+    #[test]
+    fn local_properties() {
+        const RECIPE_LOCAL: &str = "\
+name: test
+description: test
+base-image: ghcr.io/ublue-os/silverblue-main
+image-version: 40
+secrets:
+  - id: repo-token
+    env: REPO_TOKEN
+stages:
+  - name: build
+    from: registry.fedoraproject.org/fedora:40
+    modules:
+      - type: rpm-ostree
+        secrets: [repo-token]
+        install: [gcc]
+modules:
+  - type: rpm-ostree
+    if: os_version >= 40 and arch == 'amd64'
+    secrets: [repo-token]
+    install: [micro]
+";
+        let file = Arc::new(String::from(RECIPE_LOCAL));
+        let path = Path::new("recipe.yml");
+
+        // The schemas in test-files are shaped like the published
+        // schemas, which don't have the local properties.
+        let validator = ASYNC_RUNTIME
+            .block_on(
+                SchemaValidator::builder()
+                    .url(RECIPE_V1_SCHEMA_URL)
+                    .schema_dir(PathBuf::from("test-files/schemas"))
+                    .build(),
+            )
+            .unwrap();
+
+        let instance: Value = serde_yaml::from_str(&file).unwrap();
+        assert!(validator
+            .process_instance_validation(path, file.clone(), &instance, false)
+            .unwrap()
+            .is_some());
+
+        let (_, instance) = interpolate_instance(&file, &Vars::default(), true).unwrap();
+        let errors = validator
+            .process_instance_validation(path, file, &instance, true)
+            .unwrap();
+        assert!(errors.is_none(), "{errors:?}");
+    }
+}
@@ -1,8 +1,13 @@
 use std::{
     borrow::Cow,
     collections::HashSet,
-    path::Path,
-    sync::{Arc, LazyLock},
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, LazyLock,
+    },
+    time::Duration,
 };
 
 use blue_build_process_management::ASYNC_RUNTIME;
@@ -13,8 +18,10 @@ use indexmap::IndexMap;
 use jsonschema::{
     output::Output, BasicOutput, ErrorIterator, Retrieve, Uri, ValidationError, Validator,
 };
-use log::{debug, trace};
-use miette::{bail, miette, Context, IntoDiagnostic, LabeledSpan, NamedSource, Report, Result};
+use log::{debug, trace, warn};
+use miette::{
+    bail, miette, Context, IntoDiagnostic, LabeledSpan, NamedSource, Report, Result, SourceSpan,
+};
 use regex::Regex;
 use serde_json::Value;
 
@@ -27,6 +34,53 @@ pub const MODULE_V1_SCHEMA_URL: &str = "https://schema.blue-build.org/module-v1.
 pub const MODULE_STAGE_LIST_V1_SCHEMA_URL: &str =
     "https://schema.blue-build.org/module-stage-list-v1.json";
 
+/// How long a schema in the on-disk cache is used
+/// before trying to retrieve it again.
+const SCHEMA_CACHE_TTL: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// How long to wait to connect to the schema server before
+/// falling back to the cached or embedded schemas, so that
+/// hosts without network access don't hang.
+const SCHEMA_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for a schema to be retrieved in total.
+const SCHEMA_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Set once retrieving a schema from the network fails.
+static NETWORK_UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+
+/// The schemas that are embedded in the binary so that
+/// validation still works when the network is unavailable.
+///
+/// These don't include the schemas of the individual modules,
+/// so module specific properties aren't validated when they're used.
+const EMBEDDED_SCHEMAS: &[(&str, &str)] = &[
+    (
+        "/recipe-v1.json",
+        include_str!("../../../schemas/recipe-v1.json"),
+    ),
+    (
+        "/stage-v1.json",
+        include_str!("../../../schemas/stage-v1.json"),
+    ),
+    (
+        "/module-v1.json",
+        include_str!("../../../schemas/module-v1.json"),
+    ),
+    (
+        "/module-stage-list-v1.json",
+        include_str!("../../../schemas/module-stage-list-v1.json"),
+    ),
+];
+
+/// The properties of a recipe that the schemas at <https://schema.blue-build.org>
+/// don't have. These are checked when the recipe is parsed instead.
+pub const LOCAL_RECIPE_PROPERTIES: &[&str] = &["vars", "secrets"];
+
+/// The properties of a module that the schemas at <https://schema.blue-build.org>
+/// don't have. These are checked when the recipe is parsed instead.
+const LOCAL_MODULE_PROPERTIES: &[&str] = &["if", "secrets"];
+
 #[derive(Debug, Clone)]
 pub struct SchemaValidator {
     schema: Arc<Value>,
@@ -36,25 +90,24 @@ pub struct SchemaValidator {
 
 #[bon]
 impl SchemaValidator {
+    /// Creates a validator for the schema at `url`.
+    ///
+    /// Schemas are retrieved from `schema_dir` if set, then the on-disk cache,
+    /// then the network, and finally the schemas embedded in the binary.
     #[builder]
-    pub async fn new(url: &'static str) -> Result<Self, Report> {
+    pub async fn new(url: &'static str, schema_dir: Option<PathBuf>) -> Result<Self, Report> {
         tokio::spawn(async move {
             let schema: Arc<Value> = Arc::new(
-                reqwest::get(url)
-                    .await
-                    .into_diagnostic()
-                    .with_context(|| format!("Failed to get schema at {url}"))?
-                    .json()
+                retrieve_schema(url.to_string(), schema_dir.clone())
                     .await
-                    .into_diagnostic()
-                    .with_context(|| format!("Failed to get json for schema {url}"))?,
+                    .with_context(|| format!("Failed to get schema at {url}"))?,
             );
             let validator = Arc::new(
                 tokio::task::spawn_blocking({
                     let schema = schema.clone();
                     move || {
                         jsonschema::options()
-                            .with_retriever(ModuleSchemaRetriever)
+                            .with_retriever(ModuleSchemaRetriever { schema_dir })
                             .build(&schema)
                             .into_diagnostic()
                             .with_context(|| format!("Failed to build validator for schema {url}"))
@@ -98,16 +151,33 @@ impl SchemaValidator {
     ) -> Result<Option<Report>> {
         let recipe_path_display = path.display().to_string().bold().italic();
 
-        let spanner = YamlSpan::builder().file(file.clone()).build()?;
         let instance: Value = serde_yaml::from_str(&file)
             .into_diagnostic()
             .with_context(|| format!("Failed to deserialize recipe {recipe_path_display}"))?;
+
+        self.process_instance_validation(path, file, &instance, all_errors)
+    }
+
+    /// Validates `instance` and reports any errors against the contents of `file`.
+    ///
+    /// This is used when the instance was created from the file by interpolating
+    /// variables, so that error locations point into the file the user wrote.
+    pub fn process_instance_validation(
+        &self,
+        path: &Path,
+        file: Arc<String>,
+        instance: &Value,
+        all_errors: bool,
+    ) -> Result<Option<Report>> {
+        let recipe_path_display = path.display().to_string().bold().italic();
+
+        let spanner = YamlSpan::builder().file(file.clone()).build()?;
         trace!("{recipe_path_display}:\n{file}");
 
         Ok(if all_errors {
-            self.process_basic_output(self.apply(&instance).basic(), file, &spanner, path)
+            self.process_basic_output(self.apply(instance).basic(), file, &spanner, path)
         } else {
-            self.process_err(self.iter_errors(&instance), path, file, &spanner)
+            self.process_err(self.iter_errors(instance), path, file, &spanner)
         })
     }
 
@@ -160,7 +230,7 @@ impl SchemaValidator {
                     .map(|(key, value)| {
                         LabeledSpan::new_with_span(
                             Some(value.join("\n")),
-                            spanner.get_span(&key).unwrap(),
+                            closest_span(spanner, &key),
                         )
                     })
                     .collect::<Vec<_>>();
@@ -198,9 +268,7 @@ impl SchemaValidator {
             .map(|err| {
                 LabeledSpan::new_primary_with_span(
                     Some(remove_json(&err.to_string()).bold().red().to_string()),
-                    spanner
-                        .get_span(&Location::from(err.instance_path))
-                        .unwrap(),
+                    closest_span(spanner, &Location::from(err.instance_path)),
                 )
             })
             .collect::<Vec<_>>();
@@ -228,7 +296,59 @@ impl SchemaValidator {
     }
 }
 
-fn remove_json(string: &str) -> Cow<'_, str> {
+/// Gets the span of `location` in the file, falling back to its closest
+/// parent. A location can be missing from the file when a variable is
+/// interpolated into a list or map.
+fn closest_span(spanner: &YamlSpan, location: &Location) -> SourceSpan {
+    let mut path = location.as_str();
+
+    loop {
+        if let Ok(span) = Location::try_from(path).and_then(|location| spanner.get_span(&location))
+        {
+            break span;
+        }
+
+        match path.rsplit_once('/') {
+            Some((parent, _)) => path = parent,
+            None => break (0, 0).into(),
+        }
+    }
+}
+
+/// Removes the [`LOCAL_RECIPE_PROPERTIES`] from a recipe
+/// and the properties of its modules that aren't in the
+/// published schemas.
+pub fn remove_local_recipe_properties(instance: &mut Value) {
+    if let Some(map) = instance.as_object_mut() {
+        for property in LOCAL_RECIPE_PROPERTIES {
+            map.remove(*property);
+        }
+    }
+    remove_local_properties(instance);
+}
+
+/// Removes the properties that aren't in the published schemas
+/// from a module, a stage, or a file of modules and stages, so
+/// that they don't fail validation against the published schemas.
+pub fn remove_local_properties(instance: &mut Value) {
+    let Some(map) = instance.as_object_mut() else {
+        return;
+    };
+
+    if map.contains_key("type") {
+        for property in LOCAL_MODULE_PROPERTIES {
+            map.remove(*property);
+        }
+    }
+
+    for key in ["modules", "stages"] {
+        if let Some(items) = map.get_mut(key).and_then(Value::as_array_mut) {
+            items.iter_mut().for_each(remove_local_properties);
+        }
+    }
+}
+
+pub fn remove_json(string: &str) -> Cow<'_, str> {
     static REGEX_OBJECT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\{.*\}\s(.*)$").unwrap());
     static REGEX_ARRAY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\[.*\]\s(.*)$").unwrap());
 
@@ -243,19 +363,25 @@ fn remove_json(string: &str) -> Cow<'_, str> {
     }
 }
 
-struct ModuleSchemaRetriever;
+struct ModuleSchemaRetriever {
+    schema_dir: Option<PathBuf>,
+}
 
 impl Retrieve for ModuleSchemaRetriever {
     fn retrieve(
         &self,
         uri: &Uri<&str>,
     ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
-        Ok(ASYNC_RUNTIME.block_on(cache_retrieve(uri))?)
+        Ok(ASYNC_RUNTIME.block_on(cache_retrieve(uri, self.schema_dir.clone()))?)
     }
 }
 
-#[cached(result = true, key = "String", convert = r#"{ format!("{uri}") }"#)]
-async fn cache_retrieve(uri: &Uri<&str>) -> miette::Result<Value> {
+#[cached(
+    result = true,
+    key = "String",
+    convert = r#"{ format!("{uri}{schema_dir:?}") }"#
+)]
+async fn cache_retrieve(uri: &Uri<&str>, schema_dir: Option<PathBuf>) -> miette::Result<Value> {
     let scheme = uri.scheme();
     let path = uri.path();
 
@@ -267,18 +393,213 @@ async fn cache_retrieve(uri: &Uri<&str>) -> miette::Result<Value> {
         scheme => bail!("Unknown scheme {scheme}"),
     };
 
+    retrieve_schema(uri.clone(), schema_dir)
+        .await
+        .with_context(|| format!("Unable to retrieve schema {}", uri.bold().italic()))
+}
+
+/// Retrieves a schema, preferring local copies over the network.
+///
+/// The order of precedence is the `schema_dir`, the on-disk cache, and then
+/// the network. If the network is unavailable an expired copy in the on-disk
+/// cache will be used, falling back to the schemas embedded in the binary.
+/// Once the network has failed it isn't tried again, so that hosts without
+/// network access only wait for [`SCHEMA_CONNECT_TIMEOUT`] once.
+pub async fn retrieve_schema(uri: String, schema_dir: Option<PathBuf>) -> miette::Result<Value> {
+    let path = uri.strip_prefix(BASE_SCHEMA_URL).map(ToString::to_string);
+
+    if let Some(path) = path.as_deref() {
+        if let Some(schema_file) = schema_dir
+            .as_deref()
+            .map(|dir| dir.join(path.trim_start_matches('/')))
+            .filter(|file| file.is_file())
+        {
+            debug!("Retrieving schema from {}", schema_file.display());
+            return read_schema(&schema_file);
+        }
+    }
+
+    let cache_file = path.as_deref().and_then(|path| {
+        blue_build_utils::cache_dir()
+            .map(|dir| dir.join("schemas").join(path.trim_start_matches('/')))
+    });
+
+    if let Some(cache_file) = cache_file.as_deref().filter(|file| is_fresh(file)) {
+        debug!("Retrieving schema from cache {}", cache_file.display());
+        return read_schema(cache_file);
+    }
+
     debug!("Retrieving schema from {}", uri.bold().italic());
-    tokio::spawn(async move {
-        reqwest::get(&uri)
-            .await
-            .into_diagnostic()
-            .with_context(|| format!("Failed to retrieve schema from {uri}"))?
-            .json()
-            .await
-            .into_diagnostic()
-            .with_context(|| format!("Failed to parse json from {uri}"))
-            .inspect(|value| trace!("{}:\n{value}", uri.bold().italic()))
+    let result = tokio::spawn({
+        let uri = uri.clone();
+        async move {
+            if NETWORK_UNAVAILABLE.load(Ordering::Relaxed) {
+                bail!("Skipped retrieving {uri} as the network is unavailable");
+            }
+
+            schema_client()?
+                .get(&uri)
+                .send()
+                .await
+                .into_diagnostic()
+                .inspect_err(|_| NETWORK_UNAVAILABLE.store(true, Ordering::Relaxed))
+                .with_context(|| format!("Failed to retrieve schema from {uri}"))?
+                .json::<Value>()
+                .await
+                .into_diagnostic()
+                .with_context(|| format!("Failed to parse json from {uri}"))
+                .inspect(|value| trace!("{}:\n{value}", uri.bold().italic()))
+        }
     })
     .await
-    .expect("Should join task")
+    .expect("Should join task");
+
+    match (result, cache_file) {
+        (Ok(value), Some(cache_file)) => {
+            if let Err(e) = write_schema(&cache_file, &value) {
+                warn!("Failed to cache schema {uri}: {e:?}");
+            }
+            Ok(value)
+        }
+        (Err(e), Some(cache_file)) if cache_file.is_file() => {
+            warn!("Using expired cached schema for {uri}: {e:?}");
+            read_schema(&cache_file)
+        }
+        (Err(e), _) => {
+            let Some(schema) = path.as_deref().and_then(embedded_schema) else {
+                return Err(e);
+            };
+
+            warn!(
+                "Using embedded schema for {}, module specific properties won't be validated: {e:?}",
+                uri.bold().italic()
+            );
+            schema
+        }
+        (result, _) => result,
+    }
+}
+
+/// The client used to retrieve schemas, which gives
+/// up quickly when the network is unavailable.
+fn schema_client() -> miette::Result<reqwest::Client> {
+    static CLIENT: LazyLock<reqwest::Result<reqwest::Client>> = LazyLock::new(|| {
+        reqwest::Client::builder()
+            .connect_timeout(SCHEMA_CONNECT_TIMEOUT)
+            .timeout(SCHEMA_REQUEST_TIMEOUT)
+            .build()
+    });
+
+    CLIENT
+        .as_ref()
+        .map(Clone::clone)
+        .map_err(|e| miette!("Failed to create the client for retrieving schemas: {e}"))
+}
+
+/// Gets the schema embedded in the binary at `path`
+/// (e.g. `/recipe-v1.json`) if there is one.
+fn embedded_schema(path: &str) -> Option<miette::Result<Value>> {
+    EMBEDDED_SCHEMAS
+        .iter()
+        .find(|(p, _)| *p == path)
+        .map(|(_, schema)| {
+            serde_json::from_str(schema)
+                .into_diagnostic()
+                .with_context(|| format!("Failed to parse embedded schema {path}"))
+        })
+}
+
+/// Whether a cached file is younger than the cache TTL.
+pub fn is_fresh(file: &Path) -> bool {
+    fs::metadata(file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age < SCHEMA_CACHE_TTL)
+}
+
+fn read_schema(file: &Path) -> miette::Result<Value> {
+    serde_json::from_str(
+        &fs::read_to_string(file)
+            .into_diagnostic()
+            .with_context(|| format!("Failed to read schema {}", file.display()))?,
+    )
+    .into_diagnostic()
+    .with_context(|| format!("Failed to parse schema {}", file.display()))
+}
+
+fn write_schema(file: &Path, value: &Value) -> miette::Result<()> {
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent).into_diagnostic()?;
+    }
+    fs::write(file, serde_json::to_string(value).into_diagnostic()?).into_diagnostic()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    use blue_build_process_management::ASYNC_RUNTIME;
+    use rstest::rstest;
+
+    use super::{
+        embedded_schema, SchemaValidator, MODULE_STAGE_LIST_V1_SCHEMA_URL, MODULE_V1_SCHEMA_URL,
+        RECIPE_V1_SCHEMA_URL, STAGE_V1_SCHEMA_URL,
+    };
+
+    #[rstest]
+    #[case::recipe("/recipe-v1.json")]
+    #[case::stage("/stage-v1.json")]
+    #[case::module("/module-v1.json")]
+    #[case::module_stage_list("/module-stage-list-v1.json")]
+    fn embedded_schemas(#[case] path: &str) {
+        let schema = embedded_schema(path).unwrap().unwrap();
+
+        assert!(schema.is_object());
+        assert_eq!(schema["$id"], path.trim_start_matches('/'));
+    }
+
+    /// The embedded schemas are the ones in `schemas/`, which
+    /// only reference each other so no network access is needed.
+    #[rstest]
+    #[case::recipe(RECIPE_V1_SCHEMA_URL)]
+    #[case::stage(STAGE_V1_SCHEMA_URL)]
+    #[case::module(MODULE_V1_SCHEMA_URL)]
+    #[case::module_stage_list(MODULE_STAGE_LIST_V1_SCHEMA_URL)]
+    fn schema_dir_validators(#[case] url: &'static str) {
+        let validator = ASYNC_RUNTIME
+            .block_on(
+                SchemaValidator::builder()
+                    .url(url)
+                    .schema_dir(PathBuf::from("schemas"))
+                    .build(),
+            )
+            .unwrap();
+
+        assert!(validator.schema().is_object());
+    }
+
+    #[rstest]
+    #[case::valid("type: rpm-ostree\ninstall:\n  - micro\n", false)]
+    #[case::invalid_property("type: rpm-ostree\ninstall:\n  - micro\nnot-a-property: true\n", true)]
+    #[case::invalid_type("type: rpm-ostree\ninstall: micro\n", true)]
+    fn module_properties(#[case] module: &str, #[case] has_errors: bool) {
+        let validator = ASYNC_RUNTIME
+            .block_on(
+                SchemaValidator::builder()
+                    .url(MODULE_V1_SCHEMA_URL)
+                    .schema_dir(PathBuf::from("test-files/schemas"))
+                    .build(),
+            )
+            .unwrap();
+
+        let errors = validator
+            .process_validation(Path::new("module.yml"), Arc::new(module.into()), false)
+            .unwrap();
+
+        assert_eq!(errors.is_some(), has_errors);
+    }
 }
@@ -0,0 +1,365 @@
+use std::path::PathBuf;
+
+use blue_build_process_management::drivers::{
+    opts::{GetMetadataOpts, VerifyAttestationOpts, VerifyOpts, VerifyType},
+    types::{Platform, SigningDriverType},
+    Driver, DriverArgs, InspectDriver, SigningDriver, VERIFICATION_FAILED,
+};
+use blue_build_utils::constants::{COSIGN_PUB_PATH, SLSA_PROVENANCE_PREDICATE_TYPE};
+use clap::Args;
+use colored::{ColoredString, Colorize};
+use log::{debug, error, trace};
+use miette::{bail, miette, IntoDiagnostic, Report, Result};
+use oci_distribution::Reference;
+
+use super::BlueBuildCommand;
+
+/// Exit code when all of the checks passed.
+const EXIT_PASS: i32 = 0;
+
+/// Exit code when the image failed one of the checks.
+const EXIT_FAIL: i32 = 1;
+
+/// Exit code when the image couldn't be checked.
+const EXIT_ERROR: i32 = 2;
+
+#[derive(Debug, Clone, Args)]
+pub struct VerifyCommand {
+    /// The image to verify.
+    image: String,
+
+    /// The public key to verify the image with.
+    ///
+    /// Defaults to `./cosign.pub` when keyless
+    /// verification isn't used.
+    #[arg(long, conflicts_with_all = ["certificate_identity", "certificate_oidc_issuer"])]
+    key: Option<PathBuf>,
+
+    /// The identity of the certificate for keyless
+    /// verification as a regex.
+    ///
+    /// For GitHub this is
+    /// `https://github.com/<owner>/<repo>/.github/workflows/<workflow>@<ref>`.
+    #[arg(long, requires = "certificate_oidc_issuer")]
+    certificate_identity: Option<String>,
+
+    /// The OIDC issuer of the certificate for keyless verification
+    /// (e.g. `https://token.actions.githubusercontent.com`).
+    #[arg(long, requires = "certificate_identity")]
+    certificate_oidc_issuer: Option<String>,
+
+    /// Also verify the SLSA provenance attestation
+    /// attached to the image when it was built.
+    #[arg(long)]
+    provenance: bool,
+
+    /// The platform of the image to verify.
+    #[arg(long, default_value = "native")]
+    platform: Platform,
+
+    #[clap(flatten)]
+    drivers: DriverArgs,
+}
+
+impl BlueBuildCommand for VerifyCommand {
+    fn try_run(&mut self) -> Result<()> {
+        match self.verify()? {
+            Status::Pass => Ok(()),
+            Status::Fail => {
+                bail!("Verification failed for {}", self.image);
+            }
+            Status::Error => {
+                bail!("Unable to verify {}", self.image);
+            }
+        }
+    }
+
+    /// Runs the command and exits with `0` when the image
+    /// passed, `1` when it failed verification, and `2`
+    /// when it couldn't be verified.
+    fn run(&mut self) {
+        std::process::exit(match self.verify() {
+            Ok(Status::Pass) => EXIT_PASS,
+            Ok(Status::Fail) => EXIT_FAIL,
+            Ok(Status::Error) => EXIT_ERROR,
+            Err(e) => {
+                error!("Failed:\n{e:?}");
+                EXIT_ERROR
+            }
+        });
+    }
+}
+
+impl VerifyCommand {
+    /// Verifies the image and prints a report of the checks.
+    ///
+    /// Returns the combined status of the checks.
+    fn verify(&self) -> Result<Status> {
+        trace!("VerifyCommand::verify()");
+
+        Driver::init(self.drivers);
+
+        let (verify_type, method) = self.verify_type()?;
+        check_signing_driver(&verify_type)?;
+        let image_digest = self.resolve_digest()?;
+        debug!("Verifying {image_digest} with {method}");
+
+        let mut checks = vec![Check::new(
+            "Signature",
+            Driver::verify(
+                &VerifyOpts::builder()
+                    .image(&image_digest)
+                    .verify_type(verify_type.clone())
+                    .build(),
+            ),
+        )];
+
+        if self.provenance {
+            checks.push(Check::new(
+                "Provenance",
+                Driver::verify_attestation(
+                    &VerifyAttestationOpts::builder()
+                        .image(&image_digest)
+                        .predicate_type(SLSA_PROVENANCE_PREDICATE_TYPE)
+                        .verify_type(verify_type)
+                        .build(),
+                ),
+            ));
+        }
+
+        println!("{}", report(&self.image, &image_digest, &method, &checks));
+
+        Ok(Status::of(&checks))
+    }
+
+    /// Resolves the image to an `<image>@<digest>` reference.
+    fn resolve_digest(&self) -> Result<String> {
+        let reference: Reference = self.image.parse().into_diagnostic()?;
+        let image = format!(
+            "{}/{}",
+            reference.resolve_registry(),
+            reference.repository()
+        );
+
+        let digest = if let Some(digest) = reference.digest() {
+            digest.to_string()
+        } else {
+            Driver::get_metadata(
+                &GetMetadataOpts::builder()
+                    .image(&*image)
+                    .maybe_tag(reference.tag())
+                    .platform(self.platform)
+                    .build(),
+            )
+            .map_err(|e| miette!("Failed to resolve the digest of {}: {e:?}", self.image))?
+            .digest
+        };
+
+        Ok(format!("{image}@{digest}"))
+    }
+
+    /// Determines how the image is verified
+    /// along with a description of the method.
+    fn verify_type(&self) -> Result<(VerifyType<'_>, String)> {
+        Ok(
+            match (
+                self.key.as_ref(),
+                self.certificate_identity.as_ref(),
+                self.certificate_oidc_issuer.as_ref(),
+            ) {
+                (None, Some(identity), Some(issuer)) => (
+                    VerifyType::Keyless {
+                        issuer: issuer.into(),
+                        identity: identity.into(),
+                    },
+                    format!("keyless identity {identity} from {issuer}"),
+                ),
+                (key, _, _) => {
+                    let key = key.map_or_else(|| PathBuf::from(COSIGN_PUB_PATH), Clone::clone);
+                    if !key.is_file() {
+                        bail!(
+                            help = "Use `--key` or `--certificate-identity` and `--certificate-oidc-issuer`",
+                            "The public key {} does not exist",
+                            key.display()
+                        );
+                    }
+                    let method = format!("key {}", key.display());
+                    (VerifyType::File(key.into()), method)
+                }
+            },
+        )
+    }
+}
+
+/// Checks that the signing driver is able to verify the image.
+fn check_signing_driver(verify_type: &VerifyType) -> Result<()> {
+    match Driver::get_signing_driver() {
+        SigningDriverType::Cosign => blue_build_utils::check_command_exists("cosign"),
+
+        #[cfg(feature = "sigstore")]
+        SigningDriverType::Sigstore => {
+            if matches!(verify_type, VerifyType::Keyless { .. }) {
+                bail!(
+                    help = "Install cosign or use `--signing-driver cosign` to verify with a certificate identity",
+                    "Keyless verification is not supported by the sigstore signing driver",
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The status of a check, or of all of the checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Status {
+    Pass,
+    Fail,
+    Error,
+}
+
+impl Status {
+    /// The combined status of the checks, where a check that
+    /// couldn't be run takes precedence over a failed check.
+    fn of(checks: &[Check]) -> Self {
+        checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(Self::Pass)
+    }
+
+    fn colored(self) -> ColoredString {
+        match self {
+            Self::Pass => "PASS".bold().green(),
+            Self::Fail => "FAIL".bold().red(),
+            Self::Error => "ERROR".bold().yellow(),
+        }
+    }
+}
+
+/// The result of a single verification check.
+#[derive(Debug)]
+struct Check {
+    name: &'static str,
+    status: Status,
+    error: Option<String>,
+}
+
+impl Check {
+    /// Creates a check from the result of a signing driver.
+    ///
+    /// Errors with the [`VERIFICATION_FAILED`] code mean the image
+    /// was checked and failed, while any other error means the
+    /// check couldn't be run.
+    fn new(name: &'static str, result: Result<()>) -> Self {
+        let Err(e) = result else {
+            return Self {
+                name,
+                status: Status::Pass,
+                error: None,
+            };
+        };
+        debug!("{name} check failed: {e:?}");
+
+        Self {
+            name,
+            status: if is_verification_failure(&e) {
+                Status::Fail
+            } else {
+                Status::Error
+            },
+            error: Some(e.to_string()),
+        }
+    }
+}
+
+fn is_verification_failure(e: &Report) -> bool {
+    e.code()
+        .is_some_and(|code| code.to_string() == VERIFICATION_FAILED)
+}
+
+fn report(image: &str, image_digest: &str, method: &str, checks: &[Check]) -> String {
+    let mut lines = vec![
+        format!("{:<13}{image}", "Image:"),
+        format!("{:<13}{image_digest}", "Digest:"),
+        format!("{:<13}{method}", "Verified by:"),
+    ];
+    lines.extend(checks.iter().map(|check| {
+        let label = format!("{}:", check.name);
+        check.error.as_ref().map_or_else(
+            || format!("{label:<13}{}", check.status.colored()),
+            |error| format!("{label:<13}{} ({error})", check.status.colored()),
+        )
+    }));
+    lines.push(format!("{:<13}{}", "Result:", Status::of(checks).colored()));
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{report, Check};
+    use blue_build_process_management::drivers::VERIFICATION_FAILED;
+
+    #[test]
+    fn report_checks() {
+        colored::control::set_override(false);
+
+        let checks = [
+            Check::new("Signature", Ok(())),
+            Check::new(
+                "Provenance",
+                Err(miette::miette!(
+                    code = VERIFICATION_FAILED,
+                    "No attestation found"
+                )),
+            ),
+        ];
+
+        assert_eq!(
+            report(
+                "ghcr.io/octocat/test:latest",
+                "ghcr.io/octocat/test@sha256:abc123",
+                "key ./cosign.pub",
+                &checks,
+            ),
+            "Image:       ghcr.io/octocat/test:latest
+Digest:      ghcr.io/octocat/test@sha256:abc123
+Verified by: key ./cosign.pub
+Signature:   PASS
+Provenance:  FAIL (No attestation found)
+Result:      FAIL"
+        );
+    }
+
+    #[test]
+    fn report_errors() {
+        colored::control::set_override(false);
+
+        let checks = [
+            Check::new(
+                "Signature",
+                Err(miette::miette!(
+                    code = VERIFICATION_FAILED,
+                    "No matching signatures"
+                )),
+            ),
+            Check::new("Provenance", Err(miette::miette!("Network unreachable"))),
+        ];
+
+        assert_eq!(
+            report(
+                "ghcr.io/octocat/test:latest",
+                "ghcr.io/octocat/test@sha256:abc123",
+                "key ./cosign.pub",
+                &checks,
+            ),
+            "Image:       ghcr.io/octocat/test:latest
+Digest:      ghcr.io/octocat/test@sha256:abc123
+Verified by: key ./cosign.pub
+Signature:   FAIL (No matching signatures)
+Provenance:  ERROR (Network unreachable)
+Result:      ERROR"
+        );
+    }
+}
@@ -3,5 +3,7 @@
 
 shadow_rs::shadow!(shadow);
 
+pub mod boot_status;
+pub mod bootc_status;
 pub mod commands;
 pub mod rpm_ostree_status;
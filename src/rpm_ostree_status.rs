@@ -1,25 +1,67 @@
-use std::{borrow::Cow, path::Path};
+use std::{borrow::Cow, collections::HashMap, path::Path, process::Command};
 
-use blue_build_utils::cmd;
+use blue_build_utils::{
+    cmd,
+    constants::{OCI_ARCHIVE, OSTREE_IMAGE_SIGNED, OSTREE_UNVERIFIED_IMAGE},
+};
 use log::trace;
 use miette::{bail, IntoDiagnostic, Result};
 use serde::Deserialize;
 
+use crate::boot_status::{self, BootStatus, Deployment, IMAGE_CONFIG_META};
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct RpmOstreeStatus<'a> {
     deployments: Cow<'a, [RpmOstreeDeployments<'a>]>,
     transactions: Option<Cow<'a, [Cow<'a, str>]>>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Default, Clone, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 struct RpmOstreeDeployments<'a> {
     container_image_reference: Cow<'a, str>,
     booted: bool,
     staged: bool,
+
+    #[serde(default)]
+    container_image_reference_digest: Option<Cow<'a, str>>,
+
+    #[serde(default)]
+    version: Option<Cow<'a, str>>,
+
+    #[serde(default)]
+    osname: Cow<'a, str>,
+
+    #[serde(default)]
+    base_commit_meta: HashMap<String, serde_json::Value>,
+}
+
+impl RpmOstreeDeployments<'_> {
+    /// Reads the labels from the image config
+    /// stored in the commit metadata.
+    fn labels(&self) -> HashMap<String, String> {
+        self.base_commit_meta
+            .get(IMAGE_CONFIG_META)
+            .and_then(serde_json::Value::as_str)
+            .map(boot_status::image_config_labels)
+            .unwrap_or_default()
+    }
+
+    fn deployment(&self) -> Deployment {
+        Deployment {
+            image: self.container_image_reference.to_string(),
+            digest: self
+                .container_image_reference_digest
+                .as_ref()
+                .map(ToString::to_string),
+            version: self.version.as_ref().map(ToString::to_string),
+            ..Default::default()
+        }
+        .with_labels(&self.labels())
+    }
 }
 
-impl<'a> RpmOstreeStatus<'a> {
+impl RpmOstreeStatus<'_> {
     /// Creates a status struct for `rpm-ostree`.
     ///
     /// # Errors
@@ -40,61 +82,82 @@ impl<'a> RpmOstreeStatus<'a> {
 
         serde_json::from_slice(&output.stdout).into_diagnostic()
     }
+}
 
-    /// Checks if there is a transaction in progress.
-    #[must_use]
-    pub fn transaction_in_progress(&self) -> bool {
+impl BootStatus for RpmOstreeStatus<'_> {
+    fn name(&self) -> &'static str {
+        "rpm-ostree"
+    }
+
+    fn requires_sudo(&self) -> bool {
+        false
+    }
+
+    fn transaction_in_progress(&self) -> bool {
         self.transactions.as_ref().is_some_and(|tr| !tr.is_empty())
     }
 
-    /// Get the booted image's reference.
-    #[must_use]
-    pub fn booted_image(&self) -> Option<String> {
-        Some(
-            self.deployments
-                .iter()
-                .find(|deployment| deployment.booted)?
-                .container_image_reference
-                .to_string(),
+    fn booted_deployment(&self) -> Option<Deployment> {
+        self.deployments
+            .iter()
+            .find(|deployment| deployment.booted)
+            .map(RpmOstreeDeployments::deployment)
+    }
+
+    fn staged_deployment(&self) -> Option<Deployment> {
+        self.deployments
+            .iter()
+            .find(|deployment| deployment.staged)
+            .map(RpmOstreeDeployments::deployment)
+    }
+
+    /// The rollback deployment is the first deployment of the
+    /// booted OS after the booted deployment, which is the one
+    /// `ostree` picks when rolling back.
+    fn rollback_deployment(&self) -> Option<Deployment> {
+        let booted_index = self
+            .deployments
+            .iter()
+            .position(|deployment| deployment.booted)?;
+        let booted = &self.deployments[booted_index];
+
+        self.deployments[booted_index + 1..]
+            .iter()
+            .find(|deployment| !deployment.staged && deployment.osname == booted.osname)
+            .map(RpmOstreeDeployments::deployment)
+    }
+
+    fn upgrade_command(&self, reboot: bool) -> Command {
+        cmd!("rpm-ostree", "upgrade", if reboot => "--reboot")
+    }
+
+    fn switch_archive_command(&self, archive_path: &Path, reboot: bool) -> Command {
+        cmd!(
+            "rpm-ostree",
+            "rebase",
+            format!(
+                "{OSTREE_UNVERIFIED_IMAGE}:{OCI_ARCHIVE}:{path}",
+                path = archive_path.display()
+            ),
+            if reboot => "--reboot",
         )
     }
 
-    /// Get the booted image's reference.
-    #[must_use]
-    pub fn staged_image(&self) -> Option<String> {
-        Some(
-            self.deployments
-                .iter()
-                .find(|deployment| deployment.staged)?
-                .container_image_reference
-                .to_string(),
+    fn signed_image_ref(&self, image: &str) -> String {
+        format!("{OSTREE_IMAGE_SIGNED}:docker://{image}")
+    }
+
+    fn switch_signed_image_command(&self, image: &str, reboot: bool) -> Command {
+        cmd!(
+            "rpm-ostree",
+            "rebase",
+            self.signed_image_ref(image),
+            if reboot => "--reboot",
         )
     }
 
-    #[must_use]
-    pub fn is_booted_on_archive<P>(&self, archive_path: P) -> bool
-    where
-        P: AsRef<Path>,
-    {
-        self.booted_image().is_some_and(|deployment| {
-            deployment
-                .split(':')
-                .last()
-                .is_some_and(|boot_ref| Path::new(boot_ref) == archive_path.as_ref())
-        })
-    }
-
-    #[must_use]
-    pub fn is_staged_on_archive<P>(&self, archive_path: P) -> bool
-    where
-        P: AsRef<Path>,
-    {
-        self.staged_image().is_some_and(|deployment| {
-            deployment
-                .split(':')
-                .last()
-                .is_some_and(|boot_ref| Path::new(boot_ref) == archive_path.as_ref())
-        })
+    fn rollback_command(&self, reboot: bool) -> Command {
+        cmd!("rpm-ostree", "rollback", if reboot => "--reboot")
     }
 }
 
@@ -106,6 +169,8 @@ mod test {
         ARCHIVE_SUFFIX, LOCAL_BUILD, OCI_ARCHIVE, OSTREE_IMAGE_SIGNED, OSTREE_UNVERIFIED_IMAGE,
     };
 
+    use crate::boot_status::BootStatus;
+
     use super::{RpmOstreeDeployments, RpmOstreeStatus};
 
     fn create_image_status<'a>() -> RpmOstreeStatus<'a> {
@@ -118,6 +183,7 @@ mod test {
                     .into(),
                     booted: true,
                     staged: false,
+                    ..Default::default()
                 },
                 RpmOstreeDeployments {
                     container_image_reference: format!(
@@ -126,6 +192,7 @@ mod test {
                     .into(),
                     booted: false,
                     staged: false,
+                    ..Default::default()
                 },
             ]
             .into(),
@@ -143,6 +210,7 @@ mod test {
                     .into(),
                     booted: true,
                     staged: false,
+                    ..Default::default()
                 },
                 RpmOstreeDeployments {
                     container_image_reference: format!(
@@ -151,6 +219,7 @@ mod test {
                     .into(),
                     booted: false,
                     staged: false,
+                    ..Default::default()
                 },
             ]
             .into(),
@@ -166,12 +235,14 @@ mod test {
                         format!("{OSTREE_UNVERIFIED_IMAGE}:{OCI_ARCHIVE}:{LOCAL_BUILD}/cli_test.{ARCHIVE_SUFFIX}").into(),
                     booted: true,
                     staged: false,
+                    ..Default::default()
                 },
                 RpmOstreeDeployments {
                     container_image_reference:
                         format!("{OSTREE_IMAGE_SIGNED}:docker://ghcr.io/blue-build/cli/test:last").into(),
                     booted: false,
                     staged: false,
+                    ..Default::default()
                 },
             ]
             .into(),
@@ -187,18 +258,21 @@ mod test {
                         format!("{OSTREE_UNVERIFIED_IMAGE}:{OCI_ARCHIVE}:{LOCAL_BUILD}/cli_test.{ARCHIVE_SUFFIX}").into(),
                     booted: false,
                     staged: true,
+                    ..Default::default()
                 },
                 RpmOstreeDeployments {
                     container_image_reference:
                         format!("{OSTREE_UNVERIFIED_IMAGE}:{OCI_ARCHIVE}:{LOCAL_BUILD}/cli_test.{ARCHIVE_SUFFIX}").into(),
                     booted: true,
                     staged: false,
+                    ..Default::default()
                 },
                 RpmOstreeDeployments {
                     container_image_reference:
                         format!("{OSTREE_IMAGE_SIGNED}:docker://ghcr.io/blue-build/cli/test:last").into(),
                     booted: false,
                     staged: false,
+                    ..Default::default()
                 },
             ]
             .into(),
@@ -231,18 +305,116 @@ mod test {
     #[test]
     fn test_is_booted_archive() {
         assert!(!create_archive_status()
-            .is_booted_on_archive(Path::new(LOCAL_BUILD).join(format!("cli.{ARCHIVE_SUFFIX}"))));
+            .is_booted_on_archive(&Path::new(LOCAL_BUILD).join(format!("cli.{ARCHIVE_SUFFIX}"))));
         assert!(create_archive_status().is_booted_on_archive(
-            Path::new(LOCAL_BUILD).join(format!("cli_test.{ARCHIVE_SUFFIX}"))
+            &Path::new(LOCAL_BUILD).join(format!("cli_test.{ARCHIVE_SUFFIX}"))
         ));
     }
 
     #[test]
     fn test_is_staged_archive() {
         assert!(!create_archive_staged_status()
-            .is_staged_on_archive(Path::new(LOCAL_BUILD).join(format!("cli.{ARCHIVE_SUFFIX}"))));
+            .is_staged_on_archive(&Path::new(LOCAL_BUILD).join(format!("cli.{ARCHIVE_SUFFIX}"))));
         assert!(create_archive_staged_status().is_staged_on_archive(
-            Path::new(LOCAL_BUILD).join(format!("cli_test.{ARCHIVE_SUFFIX}"))
+            &Path::new(LOCAL_BUILD).join(format!("cli_test.{ARCHIVE_SUFFIX}"))
+        ));
+    }
+
+    #[test]
+    fn test_staged_fixture() {
+        let status: RpmOstreeStatus = serde_json::from_str(include_str!(
+            "../test-files/boot-status/rpm-ostree-staged.json"
+        ))
+        .unwrap();
+
+        assert!(!status.transaction_in_progress());
+        assert_eq!(
+            status.booted_image(),
+            Some(format!(
+                "{OSTREE_IMAGE_SIGNED}:docker://ghcr.io/blue-build/cli/test:latest"
+            ))
+        );
+        assert!(status.is_staged_on_archive(
+            &Path::new(LOCAL_BUILD).join(format!("cli_test.{ARCHIVE_SUFFIX}"))
         ));
     }
+
+    #[test]
+    fn test_deployment_labels() {
+        let status: RpmOstreeStatus = serde_json::from_str(include_str!(
+            "../test-files/boot-status/rpm-ostree-staged.json"
+        ))
+        .unwrap();
+
+        let booted = status
+            .booted_deployment()
+            .expect("Contains booted deployment");
+        assert_eq!(booted.recipe.as_deref(), Some("cli/test"));
+        assert_eq!(
+            booted.build_id.as_deref(),
+            Some("0c8d7e6f-1a2b-4c3d-8e9f-a0b1c2d3e4f5")
+        );
+        assert_eq!(booted.version.as_deref(), Some("40.20241016.0"));
+
+        let rollback = status
+            .rollback_deployment()
+            .expect("Contains rollback deployment");
+        assert_eq!(rollback.version.as_deref(), Some("40.20241015.0"));
+        assert_eq!(
+            rollback.build_id.as_deref(),
+            Some("7a6b5c4d-3e2f-4a1b-9c8d-7e6f5a4b3c2d")
+        );
+    }
+
+    #[test]
+    fn test_rollback_other_os() {
+        let status = RpmOstreeStatus {
+            deployments: vec![
+                RpmOstreeDeployments {
+                    container_image_reference: format!(
+                        "{OSTREE_IMAGE_SIGNED}:docker://ghcr.io/blue-build/cli/test"
+                    )
+                    .into(),
+                    osname: "default".into(),
+                    booted: true,
+                    ..Default::default()
+                },
+                RpmOstreeDeployments {
+                    container_image_reference: format!(
+                        "{OSTREE_IMAGE_SIGNED}:docker://ghcr.io/blue-build/cli/other"
+                    )
+                    .into(),
+                    osname: "other".into(),
+                    ..Default::default()
+                },
+                RpmOstreeDeployments {
+                    container_image_reference: format!(
+                        "{OSTREE_IMAGE_SIGNED}:docker://ghcr.io/blue-build/cli/test:last"
+                    )
+                    .into(),
+                    osname: "default".into(),
+                    ..Default::default()
+                },
+            ]
+            .into(),
+            transactions: None,
+        };
+
+        assert!(status
+            .rollback_deployment()
+            .expect("Contains rollback deployment")
+            .image
+            .ends_with("cli/test:last"));
+    }
+
+    #[test]
+    fn test_transaction_fixture() {
+        let status: RpmOstreeStatus = serde_json::from_str(include_str!(
+            "../test-files/boot-status/rpm-ostree-transaction.json"
+        ))
+        .unwrap();
+
+        assert!(status.transaction_in_progress());
+        assert_eq!(status.staged_image(), None);
+    }
 }
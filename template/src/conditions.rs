@@ -0,0 +1,126 @@
+use std::{collections::HashMap, env};
+
+use blue_build_recipe::Vars;
+use minijinja::{context, Environment, Error, ErrorKind, UndefinedBehavior, Value};
+
+/// The variables that the `if:` conditions of modules can use.
+pub const CONDITION_VARIABLES: [&str; 5] = ["os_version", "platform", "arch", "vars", "env"];
+
+/// Evaluates the `if:` conditions of modules.
+///
+/// Conditions are jinja expressions (e.g. `os_version >= 41 and arch == "amd64"`)
+/// that can use the major version of the base image's OS, the platform
+/// being built, the recipe's variables, and the environment variables.
+#[derive(Debug, Clone)]
+pub struct ModuleConditions {
+    env: Environment<'static>,
+    ctx: Value,
+}
+
+impl ModuleConditions {
+    /// Creates the conditions for building `platform`
+    /// (e.g. `linux/amd64`) with the architecture `arch` (e.g. `amd64`).
+    #[must_use]
+    pub fn new(os_version: u64, platform: &str, arch: &str, vars: Option<&Vars>) -> Self {
+        let mut env = Environment::new();
+
+        // Conditions that check variables that aren't
+        // set are false instead of failing the build
+        env.set_undefined_behavior(UndefinedBehavior::Chainable);
+
+        Self {
+            env,
+            ctx: context! {
+                os_version => os_version,
+                platform => platform,
+                arch => arch,
+                vars => vars.map_or_else(|| Value::from(()), Value::from_serialize),
+                env => env::vars().collect::<HashMap<_, _>>(),
+            },
+        }
+    }
+
+    /// Checks that a condition is a valid
+    /// expression that only uses known variables.
+    ///
+    /// # Errors
+    /// Will error if the condition can't be
+    /// parsed or uses an unknown variable.
+    pub fn check(condition: &str) -> Result<(), Error> {
+        let env = Environment::new();
+        let expression = env.compile_expression(condition)?;
+
+        let mut unknown = expression
+            .undeclared_variables(false)
+            .into_iter()
+            .filter(|name| !CONDITION_VARIABLES.contains(&name.as_str()))
+            .collect::<Vec<_>>();
+        unknown.sort();
+
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorKind::UndefinedError,
+                format!(
+                    "{} (conditions can only use {})",
+                    unknown.join(", "),
+                    CONDITION_VARIABLES.join(", ")
+                ),
+            ))
+        }
+    }
+
+    /// Evaluates a condition, returning whether it's true.
+    ///
+    /// # Errors
+    /// Will error if the condition is invalid or fails to be evaluated.
+    pub fn evaluate(&self, condition: &str) -> Result<bool, Error> {
+        Self::check(condition)?;
+
+        Ok(self
+            .env
+            .compile_expression(condition)?
+            .eval(&self.ctx)?
+            .is_true())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use blue_build_recipe::{VarOverride, Vars};
+    use rstest::rstest;
+
+    use super::ModuleConditions;
+
+    #[rstest]
+    #[case("os_version >= 41", false)]
+    #[case("os_version == 40 and arch == 'amd64'", true)]
+    #[case("platform == 'linux/arm64'", false)]
+    #[case("vars.nvidia", true)]
+    #[case("vars.missing", false)]
+    #[case("vars.missing.nested == 'value'", false)]
+    #[case("env.BB_TEST_UNSET_CONDITION_VAR is defined", false)]
+    #[case("'micro' in vars.packages", true)]
+    fn evaluate(#[case] condition: &str, #[case] expected: bool) {
+        let vars = Vars::resolve(
+            None,
+            &[
+                "nvidia=true".parse::<VarOverride>().unwrap(),
+                "packages=[micro,htop]".parse::<VarOverride>().unwrap(),
+            ],
+        )
+        .unwrap();
+        let conditions = ModuleConditions::new(40, "linux/amd64", "amd64", Some(&vars));
+
+        assert_eq!(conditions.evaluate(condition).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("os_version >=")]
+    #[case("os_verison >= 41")]
+    #[case("platform == 'linux/amd64' and nvidia")]
+    fn invalid(#[case] condition: &str) {
+        assert!(ModuleConditions::check(condition).is_err());
+    }
+}
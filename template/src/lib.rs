@@ -1,6 +1,9 @@
-use std::{borrow::Cow, fs, path::Path, process};
+mod conditions;
+mod overrides;
 
-use blue_build_recipe::Recipe;
+use std::{borrow::Cow, fs, ops::Deref, path::Path, process};
+
+use blue_build_recipe::{Lockfile, ModuleRequiredFields, Recipe};
 use blue_build_utils::constants::{
     CONFIG_PATH, CONTAINERFILES_PATH, CONTAINER_FILE, COSIGN_PUB_PATH, FILES_PATH,
 };
@@ -8,10 +11,15 @@ use bon::Builder;
 use chrono::Utc;
 use colored::control::ShouldColorize;
 use log::{debug, error, trace, warn};
+use minijinja::context;
 use uuid::Uuid;
 
+pub use conditions::{ModuleConditions, CONDITION_VARIABLES};
+pub use overrides::TemplateOverrides;
 pub use rinja::Template;
 
+use overrides::{CONTAINERFILE_TEMPLATE, STAGES_TEMPLATE};
+
 #[derive(Debug, Clone, Template, Builder)]
 #[template(path = "Containerfile.j2", escape = "none", whitespace = "minimize")]
 #[builder(on(Cow<'_, str>, into))]
@@ -29,6 +37,131 @@ pub struct ContainerFileTemplate<'a> {
     build_scripts_image: Cow<'a, str>,
     repo: Cow<'a, str>,
     base_digest: Cow<'a, str>,
+
+    /// The digests to pin the images pulled in
+    /// the `Containerfile` to.
+    #[builder(default)]
+    lockfile: Lockfile,
+
+    /// The `<image>@<digest>` references of the images
+    /// that the image is checked against for updates.
+    #[builder(default)]
+    image_digests: Vec<String>,
+
+    /// Templates that replace parts of the built-in template.
+    overrides: Option<TemplateOverrides>,
+}
+
+impl ContainerFileTemplate<'_> {
+    /// Renders the `Containerfile`, using the
+    /// template overrides in place of the built-in
+    /// templates they replace.
+    ///
+    /// # Errors
+    /// Will error if one of the templates fails to render.
+    pub fn render_containerfile(&self) -> rinja::Result<String> {
+        let containerfile = self.render()?;
+        self.render_override(CONTAINERFILE_TEMPLATE, containerfile, None)
+    }
+
+    fn render_stages(&self) -> rinja::Result<String> {
+        let stages = StagesTemplate {
+            containerfile: self,
+        }
+        .render()?;
+        self.render_override(STAGES_TEMPLATE, stages, None)
+    }
+
+    fn render_module(&self, module: &ModuleRequiredFields, main: bool) -> rinja::Result<String> {
+        if module.skipped {
+            return Ok(String::new());
+        }
+
+        let run = ModuleTemplate {
+            containerfile: self,
+            module,
+            main,
+        }
+        .render()?;
+        let run = self.render_override(
+            &format!("modules/{}.j2", module.module_type),
+            run.trim_start_matches('\n').to_string(),
+            Some(module),
+        )?;
+
+        // Each module's instructions start on a new line
+        Ok(if run.is_empty() {
+            run
+        } else {
+            format!("\n{run}")
+        })
+    }
+
+    /// Renders the override for the template `name` if there is
+    /// one, giving it the output of the built-in template as `default`.
+    fn render_override(
+        &self,
+        name: &str,
+        default: String,
+        module: Option<&ModuleRequiredFields>,
+    ) -> rinja::Result<String> {
+        let Some(overrides) = self.overrides.as_ref() else {
+            return Ok(default);
+        };
+
+        let ctx = context! {
+            recipe => self.recipe,
+            os_version => self.os_version,
+            build_id => self.build_id.to_string(),
+            registry => self.registry,
+            build_scripts_image => self.build_scripts_image,
+            base_digest => self.base_digest,
+            module => module,
+            default => default,
+        };
+
+        Ok(overrides
+            .render(name, ctx)
+            .map_err(|e| rinja::Error::Custom(e.into()))?
+            .unwrap_or(default))
+    }
+}
+
+/// The stages that are built before the
+/// main image, rendered on their own so
+/// that they can be overridden.
+#[derive(Debug, Template)]
+#[template(path = "stages.j2", escape = "none", whitespace = "minimize")]
+struct StagesTemplate<'a, 'b> {
+    containerfile: &'b ContainerFileTemplate<'a>,
+}
+
+impl<'a> Deref for StagesTemplate<'a, '_> {
+    type Target = ContainerFileTemplate<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        self.containerfile
+    }
+}
+
+/// The instructions for a single module.
+#[derive(Debug, Template)]
+#[template(path = "modules/module.j2", escape = "none", whitespace = "minimize")]
+struct ModuleTemplate<'a, 'b> {
+    containerfile: &'b ContainerFileTemplate<'a>,
+    module: &'b ModuleRequiredFields<'a>,
+
+    /// Whether the module is run in the main image
+    /// rather than a stage.
+    main: bool,
+}
+
+impl<'a> Deref for ModuleTemplate<'a, '_> {
+    type Target = ContainerFileTemplate<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        self.containerfile
+    }
 }
 
 #[derive(Debug, Clone, Template, Builder)]
@@ -137,3 +270,51 @@ mod filters {
         Ok(format!("{input}").replace(from, to))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use blue_build_recipe::{ModuleRequiredFields, Recipe};
+    use rstest::rstest;
+    use uuid::Uuid;
+
+    use super::ContainerFileTemplate;
+
+    #[rstest]
+    #[case::main(true)]
+    #[case::stage(false)]
+    fn module_secrets(#[case] main: bool) {
+        let recipe = Recipe {
+            name: "test".into(),
+            image_version: "40".into(),
+            ..Default::default()
+        };
+        let template = ContainerFileTemplate::builder()
+            .recipe(&recipe)
+            .recipe_path(Path::new("recipes/recipe.yml"))
+            .build_id(Uuid::nil())
+            .os_version(40)
+            .registry("ghcr.io/blue-build")
+            .build_scripts_image("ghcr.io/blue-build/cli:latest-build-scripts")
+            .repo("https://github.com/blue-build/cli")
+            .base_digest("sha256:abc123")
+            .build();
+        let module = ModuleRequiredFields::builder()
+            .module_type("script")
+            .secrets(vec!["repo-token".into(), "signing-key".into()])
+            .build();
+
+        let run = template.render_module(&module, main).unwrap();
+
+        assert!(
+            run.contains("--mount=type=secret,id=repo-token \\"),
+            "{run}"
+        );
+        assert!(
+            run.contains("--mount=type=secret,id=signing-key \\"),
+            "{run}"
+        );
+        assert_eq!(run.contains("ostree container commit"), main, "{run}");
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::{debug, trace};
+use minijinja::{path_loader, Environment, ErrorKind, UndefinedBehavior, Value};
+
+pub const CONTAINERFILE_TEMPLATE: &str = "Containerfile.j2";
+pub const STAGES_TEMPLATE: &str = "stages.j2";
+const MODULES_DIR: &str = "modules";
+const TEMPLATE_EXTENSION: &str = "j2";
+
+/// The built-in templates in the `modules` directory
+/// that aren't for a module type and can't be replaced.
+const BUILT_IN_MODULE_TEMPLATES: &[&str] = &["module.j2", "modules.j2"];
+
+/// User provided templates that replace parts of the
+/// built-in template when rendering a `Containerfile`.
+///
+/// The templates are looked up in a directory by the
+/// name of the part they replace, which is one of
+/// `Containerfile.j2`, `stages.j2`, or `modules/<module-type>.j2`.
+#[derive(Debug, Clone)]
+pub struct TemplateOverrides {
+    dir: PathBuf,
+    env: Environment<'static>,
+}
+
+impl TemplateOverrides {
+    #[must_use]
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        let dir = dir.as_ref().to_path_buf();
+        let mut env = Environment::new();
+        env.set_loader(path_loader(&dir));
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+
+        Self { dir, env }
+    }
+
+    /// Renders the template with the given name,
+    /// returning `None` if there isn't one.
+    ///
+    /// # Errors
+    /// Will error if the template can't be parsed or rendered.
+    pub fn render(&self, name: &str, ctx: Value) -> Result<Option<String>, minijinja::Error> {
+        trace!("TemplateOverrides::render({name})");
+
+        let template = match self.env.get_template(name) {
+            Ok(template) => template,
+            Err(e) if e.kind() == ErrorKind::TemplateNotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        debug!("Rendering {}", self.dir.join(name).display());
+        template.render(ctx).map(Some)
+    }
+
+    /// Finds the templates in the directory that don't replace a
+    /// part of the built-in template, like `modules/modules.j2` or
+    /// `modules/akmods/akmods.j2`. The paths are relative to the directory.
+    ///
+    /// # Errors
+    /// Will error if the directory can't be read.
+    pub fn unknown_templates(&self) -> io::Result<Vec<PathBuf>> {
+        let mut templates = Vec::new();
+        find_templates(&self.dir, Path::new(""), &mut templates)?;
+
+        templates.retain(|template| !is_known_template(template));
+        templates.sort();
+        Ok(templates)
+    }
+}
+
+fn find_templates(dir: &Path, relative: &Path, templates: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir.join(relative))? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            find_templates(dir, &path, templates)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == TEMPLATE_EXTENSION)
+        {
+            templates.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Whether the template is one of `Containerfile.j2`,
+/// `stages.j2`, or `modules/<module-type>.j2`.
+fn is_known_template(template: &Path) -> bool {
+    let components = template
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>();
+
+    match components.as_deref() {
+        Some([CONTAINERFILE_TEMPLATE | STAGES_TEMPLATE]) => true,
+        Some([MODULES_DIR, name]) => !BUILT_IN_MODULE_TEMPLATES.contains(name),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::PathBuf};
+
+    use minijinja::context;
+
+    use super::{TemplateOverrides, STAGES_TEMPLATE};
+
+    #[test]
+    fn render_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("modules")).unwrap();
+        fs::write(
+            dir.path().join("modules/rpm-ostree.j2"),
+            "ARG HTTPS_PROXY={{ proxy }}\n{{ default }}\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join(STAGES_TEMPLATE), "{{ missing }}").unwrap();
+
+        let overrides = TemplateOverrides::new(dir.path());
+
+        assert_eq!(
+            overrides
+                .render(
+                    "modules/rpm-ostree.j2",
+                    context! { proxy => "http://proxy:3128", default => "RUN true" },
+                )
+                .unwrap()
+                .as_deref(),
+            Some("ARG HTTPS_PROXY=http://proxy:3128\nRUN true")
+        );
+        assert_eq!(
+            overrides
+                .render("modules/script.j2", context! { default => "RUN true" })
+                .unwrap(),
+            None
+        );
+        assert!(overrides
+            .render(STAGES_TEMPLATE, context! { default => "" })
+            .is_err());
+    }
+
+    #[test]
+    fn unknown_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("modules/akmods")).unwrap();
+        for template in [
+            "Containerfile.j2",
+            "stages.j2",
+            "README.md",
+            "modules/rpm-ostree.j2",
+            "modules/modules.j2",
+            "modules/akmods/akmods.j2",
+            "containerfile.j2",
+        ] {
+            fs::write(dir.path().join(template), "{{ default }}").unwrap();
+        }
+
+        assert_eq!(
+            TemplateOverrides::new(dir.path())
+                .unknown_templates()
+                .unwrap(),
+            [
+                "containerfile.j2",
+                "modules/akmods/akmods.j2",
+                "modules/modules.j2"
+            ]
+            .map(PathBuf::from)
+        );
+    }
+}
@@ -7,21 +7,35 @@ pub const COSIGN_PUB_PATH: &str = "./cosign.pub";
 pub const COSIGN_PRIV_PATH: &str = "./cosign.key";
 pub const FILES_PATH: &str = "./files";
 pub const LOCAL_BUILD: &str = "/etc/bluebuild";
+pub const LOCKFILE_PATH: &str = "./bluebuild.lock";
 pub const MODULES_PATH: &str = "./config/modules";
 pub const RECIPE_FILE: &str = "recipe.yml";
 pub const RECIPE_PATH: &str = "./recipes";
 
 // Labels
+pub const BASE_DIGEST_LABEL: &str = "org.opencontainers.image.base.digest";
 pub const BUILD_ID_LABEL: &str = "org.blue-build.build-id";
+pub const IMAGE_DIGESTS_LABEL: &str = "org.blue-build.image-digests";
 pub const IMAGE_VERSION_LABEL: &str = "org.opencontainers.image.version";
+pub const IMAGE_TITLE_LABEL: &str = "org.opencontainers.image.title";
 
 // BlueBuild vars
 pub const BB_BUILDKIT_CACHE_GHA: &str = "BB_BUILDKIT_CACHE_GHA";
+pub const BB_CI_BRANCH_VAR: &str = "BB_CI_BRANCH_VAR";
+pub const BB_CI_COMMIT_SHA_VAR: &str = "BB_CI_COMMIT_SHA_VAR";
+pub const BB_CI_DEFAULT_BRANCH_VAR: &str = "BB_CI_DEFAULT_BRANCH_VAR";
+pub const BB_CI_PR_NUMBER_VAR: &str = "BB_CI_PR_NUMBER_VAR";
+pub const BB_CI_PRESET: &str = "BB_CI_PRESET";
+pub const BB_CI_REGISTRY_VAR: &str = "BB_CI_REGISTRY_VAR";
+pub const BB_CI_REPO_URL_VAR: &str = "BB_CI_REPO_URL_VAR";
 pub const BB_PASSWORD: &str = "BB_PASSWORD";
 pub const BB_PRIVATE_KEY: &str = "BB_PRIVATE_KEY";
 pub const BB_REGISTRY: &str = "BB_REGISTRY";
 pub const BB_REGISTRY_NAMESPACE: &str = "BB_REGISTRY_NAMESPACE";
+pub const BB_SCHEMA_DIR: &str = "BB_SCHEMA_DIR";
+pub const BB_TEMPLATE_DIR: &str = "BB_TEMPLATE_DIR";
 pub const BB_USERNAME: &str = "BB_USERNAME";
+pub const BB_VAR_PREFIX: &str = "BB_VAR_";
 
 // Docker vars
 pub const DOCKER_HOST: &str = "DOCKER_HOST";
@@ -38,6 +52,7 @@ pub const GITHUB_ACTIONS: &str = "GITHUB_ACTIONS";
 pub const GITHUB_ACTOR: &str = "GITHUB_ACTOR";
 pub const GITHUB_EVENT_NAME: &str = "GITHUB_EVENT_NAME";
 pub const GITHUB_EVENT_PATH: &str = "GITHUB_EVENT_PATH";
+pub const GITHUB_REF: &str = "GITHUB_REF";
 pub const GITHUB_REF_NAME: &str = "GITHUB_REF_NAME";
 pub const GITHUB_RESPOSITORY: &str = "GITHUB_REPOSITORY";
 pub const GITHUB_REPOSITORY_OWNER: &str = "GITHUB_REPOSITORY_OWNER";
@@ -47,6 +62,14 @@ pub const GITHUB_TOKEN: &str = "GH_TOKEN";
 pub const GITHUB_WORKFLOW_REF: &str = "GITHUB_WORKFLOW_REF";
 pub const PR_EVENT_NUMBER: &str = "GH_PR_EVENT_NUMBER";
 
+// Forgejo/Gitea CI vars
+pub const ACTIONS_ID_TOKEN_REQUEST_URL: &str = "ACTIONS_ID_TOKEN_REQUEST_URL";
+pub const FORGEJO_ACTIONS: &str = "FORGEJO_ACTIONS";
+pub const FORGEJO_REPOSITORY: &str = "FORGEJO_REPOSITORY";
+pub const FORGEJO_REPOSITORY_OWNER: &str = "FORGEJO_REPOSITORY_OWNER";
+pub const FORGEJO_SERVER_URL: &str = "FORGEJO_SERVER_URL";
+pub const GITEA_ACTIONS: &str = "GITEA_ACTIONS";
+
 // GitLab CI vars
 pub const CI_COMMIT_REF_NAME: &str = "CI_COMMIT_REF_NAME";
 pub const CI_COMMIT_SHORT_SHA: &str = "CI_COMMIT_SHORT_SHA";
@@ -63,6 +86,37 @@ pub const CI_REGISTRY_PASSWORD: &str = "CI_REGISTRY_PASSWORD";
 pub const CI_REGISTRY_USER: &str = "CI_REGISTRY_USER";
 pub const GITLAB_CI: &str = "GITLAB_CI";
 
+// Jenkins CI vars
+pub const JENKINS_URL: &str = "JENKINS_URL";
+pub const JENKINS_BRANCH_NAME: &str = "BRANCH_NAME";
+pub const JENKINS_CHANGE_ID: &str = "CHANGE_ID";
+pub const JENKINS_GIT_COMMIT: &str = "GIT_COMMIT";
+pub const JENKINS_GIT_URL: &str = "GIT_URL";
+
+// Woodpecker CI vars
+pub const CI: &str = "CI";
+pub const CI_COMMIT_BRANCH: &str = "CI_COMMIT_BRANCH";
+pub const CI_COMMIT_PULL_REQUEST: &str = "CI_COMMIT_PULL_REQUEST";
+pub const CI_COMMIT_SHA: &str = "CI_COMMIT_SHA";
+pub const CI_REPO_DEFAULT_BRANCH: &str = "CI_REPO_DEFAULT_BRANCH";
+pub const CI_REPO_URL: &str = "CI_REPO_URL";
+
+// Buildkite CI vars
+pub const BUILDKITE: &str = "BUILDKITE";
+pub const BUILDKITE_BRANCH: &str = "BUILDKITE_BRANCH";
+pub const BUILDKITE_COMMIT: &str = "BUILDKITE_COMMIT";
+pub const BUILDKITE_PIPELINE_DEFAULT_BRANCH: &str = "BUILDKITE_PIPELINE_DEFAULT_BRANCH";
+pub const BUILDKITE_PULL_REQUEST: &str = "BUILDKITE_PULL_REQUEST";
+pub const BUILDKITE_REPO: &str = "BUILDKITE_REPO";
+
+// Drone CI vars
+pub const DRONE: &str = "DRONE";
+pub const DRONE_COMMIT_BRANCH: &str = "DRONE_COMMIT_BRANCH";
+pub const DRONE_COMMIT_SHA: &str = "DRONE_COMMIT_SHA";
+pub const DRONE_PULL_REQUEST: &str = "DRONE_PULL_REQUEST";
+pub const DRONE_REPO_BRANCH: &str = "DRONE_REPO_BRANCH";
+pub const DRONE_REPO_LINK: &str = "DRONE_REPO_LINK";
+
 // Terminal vars
 pub const TERM_PROGRAM: &str = "TERM_PROGRAM";
 pub const LC_TERMINAL: &str = "LC_TERMINAL";
@@ -71,12 +125,18 @@ pub const LC_TERMINAL_VERSION: &str = "LC_TERMINAL_VERSION";
 pub const XDG_RUNTIME_DIR: &str = "XDG_RUNTIME_DIR";
 
 // Misc
+pub const AKMODS_IMAGE_REPO: &str = "ghcr.io/ublue-os";
+pub const BLUE_BUILD_IMAGE_REF: &str = "ghcr.io/blue-build/cli";
+pub const BLUE_BUILD_INSTALLER_TAG: &str = "latest-installer";
 pub const BUILD_SCRIPTS_IMAGE_REF: &str = "ghcr.io/blue-build/cli/build-scripts";
+pub const COSIGN_BIN_IMAGE_REF: &str = "gcr.io/projectsigstore/cosign";
 pub const COSIGN_IMAGE: &str = "ghcr.io/sigstore/cosign/cosign:latest";
+pub const MODULES_IMAGE_REF: &str = "ghcr.io/blue-build/modules:latest";
 pub const OCI_ARCHIVE: &str = "oci-archive";
 pub const OSTREE_IMAGE_SIGNED: &str = "ostree-image-signed";
 pub const OSTREE_UNVERIFIED_IMAGE: &str = "ostree-unverified-image";
 pub const SKOPEO_IMAGE: &str = "quay.io/skopeo/stable:latest";
+pub const SLSA_PROVENANCE_PREDICATE_TYPE: &str = "https://slsa.dev/provenance/v1";
 pub const TEMPLATE_REPO_URL: &str = "https://github.com/blue-build/template.git";
 pub const UNKNOWN_SHELL: &str = "<unknown shell>";
 pub const UNKNOWN_VERSION: &str = "<unknown version>";
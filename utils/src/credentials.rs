@@ -11,7 +11,8 @@ use log::trace;
 use crate::{
     constants::{
         BB_PASSWORD, BB_REGISTRY, BB_USERNAME, CI_REGISTRY, CI_REGISTRY_PASSWORD, CI_REGISTRY_USER,
-        GITHUB_ACTIONS, GITHUB_ACTOR, GITHUB_TOKEN,
+        FORGEJO_ACTIONS, FORGEJO_SERVER_URL, GITEA_ACTIONS, GITHUB_ACTIONS, GITHUB_ACTOR,
+        GITHUB_SERVER_URL, GITHUB_TOKEN,
     },
     string,
 };
@@ -53,10 +54,15 @@ static ENV_CREDENTIALS: LazyLock<Option<Credentials>> = LazyLock::new(|| {
     let registry = match (
         registry,
         env::var(CI_REGISTRY).ok(),
+        forgejo_registry(),
         env::var(GITHUB_ACTIONS).ok(),
     ) {
-        (Some(registry), _, _) | (_, Some(registry), _) if !registry.is_empty() => registry,
-        (_, _, Some(_)) => string!("ghcr.io"),
+        (Some(registry), _, _, _) | (_, Some(registry), _, _) | (_, _, Some(registry), _)
+            if !registry.is_empty() =>
+        {
+            registry
+        }
+        (_, _, _, Some(_)) => string!("ghcr.io"),
         _ => return None,
     };
     trace!("Registry: {registry:?}");
@@ -93,6 +99,40 @@ static ENV_CREDENTIALS: LazyLock<Option<Credentials>> = LazyLock::new(|| {
     )
 });
 
+/// Forgejo and Gitea serve their container
+/// registry from the host of the instance.
+fn forgejo_registry() -> Option<String> {
+    env::var(FORGEJO_ACTIONS)
+        .or_else(|_| env::var(GITEA_ACTIONS))
+        .ok()?;
+
+    let url = env::var(FORGEJO_SERVER_URL)
+        .or_else(|_| env::var(GITHUB_SERVER_URL))
+        .ok()?;
+    let url = url.trim().trim_end_matches('/');
+
+    Some(
+        url.split_once("://")
+            .map_or(url, |(_, host)| host)
+            .to_string(),
+    )
+}
+
+/// Gets the password or identity token for a registry
+/// from the docker or podman credential helper.
+#[must_use]
+pub fn credential_helper_secret(registry: &str) -> Option<String> {
+    trace!("credential_helper_secret({registry})");
+
+    match docker_credential::get_credential(registry)
+        .or_else(|_| docker_credential::get_podman_credential(registry))
+        .ok()?
+    {
+        DockerCredential::IdentityToken(token) => Some(token),
+        DockerCredential::UsernamePassword(_, password) => Some(password),
+    }
+}
+
 /// The credentials for logging into image registries.
 #[derive(Debug, Default, Clone, Builder)]
 pub struct Credentials {
@@ -85,7 +85,7 @@ where
                 warn!("Failed operation, will retry {retries} more time(s). Error:\n{e:?}");
                 thread::sleep(Duration::from_secs(delay_secs));
             }
-        };
+        }
     }
 }
 
@@ -94,6 +94,14 @@ pub fn home_dir() -> Option<PathBuf> {
     directories::BaseDirs::new().map(|base_dirs| base_dirs.home_dir().to_path_buf())
 }
 
+/// The directory to store cached files for `bluebuild` in.
+///
+/// This is `$XDG_CACHE_HOME/bluebuild` or `~/.cache/bluebuild`.
+#[must_use]
+pub fn cache_dir() -> Option<PathBuf> {
+    directories::BaseDirs::new().map(|base_dirs| base_dirs.cache_dir().join("bluebuild"))
+}
+
 /// Generates a 1-1 related Containerfile to a recipe.
 /// The file is in the format of `Containerfile.{path_hash}`.
 ///